@@ -49,6 +49,8 @@ pub enum BinOp {
     Le,
     And,
     Or,
+    /// `a ?? b` — `b` when `a` is null, otherwise `a`. Short-circuits.
+    Coalesce,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -82,7 +84,82 @@ pub enum Expr {
     Unary(UnaryOp, Box<Spanned<Expr>>),
     Call(Box<Spanned<Expr>>, Vec<Spanned<Expr>>),
     Access(Box<Spanned<Expr>>, Spanned<Symbol>),
+    /// `obj?.field` — `null` when `obj` is null, otherwise `obj.field`. A
+    /// call applied directly to it (`obj?.method(args)`) is skipped as well,
+    /// arguments included, so the whole call is `null`.
+    OptAccess(Box<Spanned<Expr>>, Spanned<Symbol>),
     Index(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// `match scrutinee { pat => body, pat if guard => body, ... }`. Arms
+    /// are tried top to bottom; each arm opens a resolver scope holding its
+    /// pattern's binders (see `Pattern::binders`) for the guard and body.
+    Match {
+        scrutinee: Box<Spanned<Expr>>,
+        arms: Vec<MatchArm>,
+    },
+    /// Source the parser skipped while recovering from a syntax error. Only
+    /// `parser::parse_recovering` returns trees containing it; `parse`
+    /// fails instead.
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub pattern: Spanned<Pattern>,
+    pub guard: Option<Spanned<Expr>>,
+    pub body: Spanned<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// `_` — matches anything, binds nothing.
+    Wildcard,
+    /// A bare identifier — matches anything and binds it.
+    Bind(Spanned<Symbol>),
+    Number(f64),
+    String(Rc<String>),
+    Bool(bool),
+    Null,
+    /// `[a, b]` matches lists of exactly that length; `[a, ...rest]` matches
+    /// lists with at least `items.len()` elements. `rest` is `Some(None)` for
+    /// an anonymous `...` and `Some(Some(name))` when the tail is bound.
+    List {
+        items: Vec<Spanned<Pattern>>,
+        rest: Option<Option<Spanned<Symbol>>>,
+    },
+    /// `{x, y: 0}` matches any record that has (at least) the listed fields.
+    /// A field without a sub-pattern binds the field's value to its name.
+    Record(Vec<(Spanned<Symbol>, Option<Spanned<Pattern>>)>),
+}
+
+impl Pattern {
+    /// The names this pattern binds, in the order the resolver assigns them
+    /// slots (left to right, depth first).
+    pub fn binders(&self, out: &mut Vec<Spanned<Symbol>>) {
+        match self {
+            Pattern::Bind(name) => out.push(name.clone()),
+            Pattern::List { items, rest } => {
+                for (item, _) in items {
+                    item.binders(out);
+                }
+                if let Some(Some(name)) = rest {
+                    out.push(name.clone());
+                }
+            }
+            Pattern::Record(fields) => {
+                for (key, sub) in fields {
+                    match sub {
+                        Some((p, _)) => p.binders(out),
+                        None => out.push(key.clone()),
+                    }
+                }
+            }
+            Pattern::Wildcard
+            | Pattern::Number(_)
+            | Pattern::String(_)
+            | Pattern::Bool(_)
+            | Pattern::Null => {}
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -0,0 +1,241 @@
+//! Stable diagnostic codes and the explanations `spctr --explain` prints.
+//!
+//! A code names a kind of problem, whichever phase finds it: a type
+//! mismatch is E0201 both as a typeck error and as a runtime error. Codes
+//! are never reused for something else once released.
+
+/// `(code, explanation)`; the explanation's first line is its title.
+pub const CODES: &[(&str, &str)] = &[
+    (
+        "E0001",
+        "Unexpected character
+
+The source contains a character that is not part of any token, such as
+`$` or `#` outside a string, or a string has an invalid escape sequence.
+
+    price: 5 $
+
+Strings support the JSON escapes (`\\n`, `\\\"`, `\\uXXXX`, ...) and `\\$`.",
+    ),
+    (
+        "E0002",
+        "Syntax error
+
+The tokens do not form a valid program. Entries of programs, blocks, lists,
+argument lists and `match` are separated by commas, and trailing commas are
+not allowed:
+
+    a: 1
+    b: 2      // missing `,` after `a: 1`
+    [1, 2,]   // trailing `,`
+
+Every syntax error is reported, not just the first one.",
+    ),
+    (
+        "E0003",
+        "More than one body
+
+A program (or block) has at most one entry without a `name:`, its body,
+which is the value of the whole. With two, it is not clear which one is
+meant:
+
+    x: 1,
+    x + 1,
+    x * 2
+
+Give the others a name, or drop them.",
+    ),
+    (
+        "E0101",
+        "Undefined variable
+
+A name is used that no enclosing block, function parameter or pattern
+binds, and that is not a root binding like `List` or `import`.
+
+    total: 1,
+    totl + 1
+
+Bindings are visible in the whole block they are in, including earlier
+entries, so the order of definitions does not matter.",
+    ),
+    (
+        "E0102",
+        "Duplicate binding in a pattern
+
+A `match` pattern binds the same name twice:
+
+    match pair { [x, x] => x }
+
+Use different names and compare them in a guard:
+
+    match pair { [x, y] if x == y => x }",
+    ),
+    (
+        "E0201",
+        "Type mismatch
+
+A value is used where a different type is expected: an arithmetic operand
+that is not a number, `!` on something other than a bool, two sides of
+`==` or the branches of an `if` that cannot have the same type.
+
+    1 + \"one\"
+
+typeck reports it as an error before the program runs, so `--check` fails
+on it; the evaluator stops with an error when the value is actually used.",
+    ),
+    (
+        "E0202",
+        "No such field
+
+A record or module is accessed with a field it does not have:
+
+    user: {name: \"ann\"},
+    user.nmae
+
+Use `?.` only for records that may be `null`; it does not make a missing
+field return `null`.",
+    ),
+    (
+        "E0203",
+        "Wrong kind of value
+
+A value is used in a way its kind does not support: a field access on
+something that is not a record, indexing something that is not a list (or
+a list by something that is not a number), or calling something that is not
+a function.
+
+    n: 5,
+    n.value",
+    ),
+    (
+        "E0204",
+        "Possibly null value
+
+A value whose type is `T?` may be `null`, and is used where `null` would be
+an error:
+
+    user: if ok then {name: \"ann\"} else null,
+    user.name
+
+Use `?.` to skip the access when it is `null`, or `??` to supply a default:
+
+    user?.name ?? \"nobody\"",
+    ),
+    (
+        "E0205",
+        "Non-exhaustive match
+
+Some values of the scrutinee are not matched by any arm (arms with a guard
+do not count, since the guard may be false). typeck warns about it ahead of
+time; at run time a value that matches no arm is an error.
+
+    match b { true => 1 }
+
+Add the missing cases, or a `_ => ...` arm for the rest.",
+    ),
+    (
+        "E0206",
+        "Cannot interpolate
+
+Only numbers, strings, bools and `null` can be put into a string with
+`${...}`. Records, lists and functions have no text form of their own:
+
+    \"items: ${[1, 2]}\"
+
+Interpolate its parts instead, e.g. `${xs[0]}`.",
+    ),
+    (
+        "E0207",
+        "Wrong number of arguments
+
+A function, builtin or `import` is called with more or fewer arguments
+than it takes:
+
+    add: (a, b) => a + b,
+    add(1)",
+    ),
+    (
+        "E0301",
+        "No such element
+
+A list is indexed past its end, or `List.head` / `List.tail` is applied to
+an empty list.
+
+    [1, 2, 3][3]
+
+Indices start at 0. Check the length first, or `match` on the list shape.",
+    ),
+    (
+        "E0302",
+        "Cyclic binding
+
+Evaluating a binding needs its own value:
+
+    a: b + 1,
+    b: a + 1,
+    a
+
+Bindings are lazy, so a definition may refer to itself only from inside a
+function that is not called while it is being evaluated.",
+    ),
+    (
+        "E0303",
+        "Recursion limit exceeded
+
+The program nested more (non-tail) calls than the interpreter allows,
+usually because a recursive function never reaches its base case.
+
+Calls in tail position do not count. If the recursion really is that deep,
+raise the limit with `--max-depth N`.",
+    ),
+    (
+        "E0304",
+        "Invalid number
+
+A string given to `Number.parse` is not a number.
+
+    Number.parse(\"12px\")",
+    ),
+    (
+        "E0401",
+        "Import failed
+
+`import` could not load the module: the file does not exist or cannot be
+read, no module by that name is available, or imports are disabled where the
+program runs. Relative paths are resolved from the importing file.",
+    ),
+    (
+        "E0402",
+        "Import cycle
+
+A module imports itself, directly or through other modules:
+
+    // a.spc
+    b: import(\"./b.spc\"), {x: b.y}
+    // b.spc
+    a: import(\"./a.spc\"), {y: a.x}
+
+Move what both need into a third module.",
+    ),
+    (
+        "E0501",
+        "Not supported by the JIT
+
+The program uses something `--jit` cannot compile (yet). The JIT covers a
+subset of the language; the same program runs with the interpreter.",
+    ),
+    (
+        "E0601",
+        "Cannot be converted
+
+The value cannot be written in the requested output format, or converted
+to or from JSON: functions have no representation, lists and records may
+nest at most 512 levels deep, and formats like TOML put their own
+restrictions on the shape of the document.",
+    ),
+];
+
+/// The explanation of `code`, if it is one.
+pub fn explain(code: &str) -> Option<&'static str> {
+    CODES.iter().find(|(c, _)| *c == code).map(|(_, text)| *text)
+}
@@ -0,0 +1,773 @@
+//! serde integration for `interp::Value`.
+//!
+//! - `Value` implements `Serialize` through the manifest's [`Node`] tree,
+//!   so it writes what `--output --sort-keys` does: records in sorted key
+//!   order, like `Display`, and integral numbers as integers. Functions and
+//!   non-record frames cannot be serialized.
+//! - `Value` is also a `Deserializer`, so [`from_value`] turns a program's
+//!   result into any `Deserialize` type.
+//! - [`to_value`] goes the other way, building a `Value` straight from any
+//!   `Serialize` data for injecting it (for example through
+//!   `engine::Engine`) as spctr values, and `Value` is `Deserialize`, so a
+//!   format such as JSON reads straight into one. [`data_scheme`] gives the
+//!   type to declare for such data; [`parse_json`] does both for a JSON
+//!   document.
+
+use crate::diag::Diagnostic;
+use crate::interp::{Frame, Value};
+use crate::manifest::{self, as_integer, Kind, Node};
+use crate::symbol::{display, intern, Symbol};
+use crate::types::{Scheme, Type, TypeVar};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, SerializeMap, SerializeSeq};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::rc::Rc;
+
+/// A conversion failure.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// A record field that failed to evaluate.
+    Eval(Diagnostic),
+    /// A value serde cannot represent, data a value cannot hold, or a shape
+    /// mismatch reported by serde. None of these has a place in a program
+    /// to point at.
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Eval(d) => f.write_str(&d.message),
+            Error::Message(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+/// Deserialize a `T` out of a spctr value.
+pub fn from_value<'de, T: Deserialize<'de>>(value: Value) -> Result<T, Error> {
+    T::deserialize(value)
+}
+
+/// Convert any `Serialize` data into a spctr value. Integers of more than
+/// 2^53 in magnitude, which a number cannot hold exactly, are an error.
+pub fn to_value<T: Serialize + ?Sized>(data: &T) -> Result<Value, Error> {
+    data.serialize(Serializer)
+}
+
+/// Builds the `Value` of the data serialized into it. Enums take the shape
+/// `Value`'s deserializer reads back: a unit variant is its name, any other
+/// variant a record with one field, named after the variant.
+struct Serializer;
+
+/// Integers up to this magnitude are held exactly by a number.
+const EXACT: u128 = 1 << 53;
+
+fn integer(i: i128) -> Result<Value, Error> {
+    if i.unsigned_abs() > EXACT {
+        return Err(inexact(i));
+    }
+    Ok(Value::Number(i as f64))
+}
+
+fn inexact(i: impl fmt::Display) -> Error {
+    Error::Message(format!("{} cannot be held exactly by a number", i))
+}
+
+/// `value` as the variant `variant` of an enum, if it is one.
+fn tagged(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(name) => Value::record(vec![(intern(name), value)]),
+        None => value,
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = ListBuilder;
+    type SerializeTuple = ListBuilder;
+    type SerializeTupleStruct = ListBuilder;
+    type SerializeTupleVariant = ListBuilder;
+    type SerializeMap = RecordBuilder;
+    type SerializeStruct = RecordBuilder;
+    type SerializeStructVariant = RecordBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        integer(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        integer(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        integer(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        integer(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, Error> {
+        integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        integer(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        integer(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        integer(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        integer(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, Error> {
+        if v > EXACT {
+            return Err(inexact(v));
+        }
+        integer(v as i128)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(Value::Number(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(Value::String(Rc::new(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(Value::String(Rc::new(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(Value::List(Rc::new(v.iter().map(|b| Value::Number((*b).into())).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        Ok(tagged(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListBuilder, Error> {
+        Ok(ListBuilder {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListBuilder, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ListBuilder, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ListBuilder, Error> {
+        Ok(ListBuilder {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<RecordBuilder, Error> {
+        Ok(RecordBuilder {
+            variant: None,
+            fields: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<RecordBuilder, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<RecordBuilder, Error> {
+        Ok(RecordBuilder {
+            variant: Some(variant),
+            fields: Vec::with_capacity(len),
+            key: None,
+        })
+    }
+}
+
+/// A list (or a tuple variant's fields) being serialized.
+struct ListBuilder {
+    variant: Option<&'static str>,
+    items: Vec<Value>,
+}
+
+impl ListBuilder {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        tagged(self.variant, Value::List(Rc::new(self.items)))
+    }
+}
+
+impl SerializeSeq for ListBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for ListBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for ListBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for ListBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+/// A record (or a struct variant's fields) being serialized.
+struct RecordBuilder {
+    variant: Option<&'static str>,
+    fields: Vec<(Symbol, Value)>,
+    /// A map key waiting for its value.
+    key: Option<Symbol>,
+}
+
+impl RecordBuilder {
+    fn push<T: Serialize + ?Sized>(&mut self, name: Symbol, value: &T) -> Result<(), Error> {
+        self.fields.push((name, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        tagged(self.variant, Value::record(self.fields))
+    }
+}
+
+impl SerializeMap for RecordBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    /// Field names are strings; integer keys are written out, as in JSON.
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(match &key.serialize(Serializer)? {
+            Value::String(s) => intern(s),
+            n @ Value::Number(_) => intern(&n.to_string()),
+            other => {
+                return Err(Error::Message(format!(
+                    "a record's field names are strings, got {}",
+                    other.type_name()
+                )))
+            }
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().expect("serialize_key before serialize_value");
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for RecordBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.push(intern(key), value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for RecordBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.push(intern(key), value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    fn from(json: serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+            serde_json::Value::String(s) => Value::String(Rc::new(s)),
+            serde_json::Value::Array(xs) => Value::List(Rc::new(xs.into_iter().map(Value::from).collect())),
+            serde_json::Value::Object(fields) => Value::record(
+                fields
+                    .into_iter()
+                    .map(|(k, v)| (intern(&k), Value::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl TryFrom<Value> for serde_json::Value {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        serde_json::to_value(&value).map_err(<Error as ser::Error>::custom)
+    }
+}
+
+/// Builds a `Value` straight from a self-describing format such as JSON,
+/// keeping the order of a map's keys.
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E>(self, i: i64) -> Result<Value, E> {
+        Ok(Value::Number(i as f64))
+    }
+
+    fn visit_u64<E>(self, i: u64) -> Result<Value, E> {
+        Ok(Value::Number(i as f64))
+    }
+
+    fn visit_f64<E>(self, n: f64) -> Result<Value, E> {
+        Ok(Value::Number(n))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(Rc::new(s.to_string())))
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(Rc::new(s)))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<Value, D::Error> {
+        Value::deserialize(d)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(x) = seq.next_element()? {
+            items.push(x);
+        }
+        Ok(Value::List(Rc::new(items)))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut fields = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((name, value)) = map.next_entry::<String, Value>()? {
+            fields.push((intern(&name), value));
+        }
+        Ok(Value::record(fields))
+    }
+}
+
+/// The type of `value`, plain data such as a converted JSON document.
+/// An array's element type joins its elements' types: nulls make it
+/// nullable, and elements of different types (including records with
+/// different fields) give `any`. An empty array's element type is left
+/// free, and the scheme is generalized over it.
+pub fn data_scheme(value: &Value) -> Scheme {
+    let mut next = 0;
+    let ty = data_type(value, &mut next);
+    Scheme {
+        vars: (0..next).map(TypeVar).collect(),
+        ty,
+    }
+}
+
+fn data_type(value: &Value, next: &mut u32) -> Type {
+    match value {
+        Value::Null => Type::Null,
+        Value::Bool(_) => Type::Bool,
+        Value::Number(_) => Type::Number,
+        Value::String(_) => Type::String,
+        Value::List(xs) => {
+            let elem = xs.iter().map(|x| data_type(x, next)).reduce(join).unwrap_or_else(|| {
+                *next += 1;
+                Type::Var(TypeVar(*next - 1))
+            });
+            Type::List(Box::new(elem))
+        }
+        Value::Block(frame) => match frame.fields() {
+            Some(fields) => Type::Record(
+                fields
+                    .into_iter()
+                    .map(|(name, slot)| (name, frame.get(slot).map_or(Type::Any, |v| data_type(&v, next))))
+                    .collect(),
+            ),
+            None => Type::Any,
+        },
+        Value::Function(_) => Type::Any,
+    }
+}
+
+/// The type of an array holding both an `a` and a `b`; what does not
+/// merge is `any`.
+fn join(a: Type, b: Type) -> Type {
+    match (a, b) {
+        (Type::Number, Type::Number) => Type::Number,
+        (Type::String, Type::String) => Type::String,
+        (Type::Bool, Type::Bool) => Type::Bool,
+        (Type::Any, _) | (_, Type::Any) => Type::Any,
+        // Only an empty array's element type is a variable.
+        (Type::Var(_), t) | (t, Type::Var(_)) => t,
+        (Type::Null, t) | (t, Type::Null) => t.nullable(),
+        (Type::Nullable(a), b) | (b, Type::Nullable(a)) => join(*a, b).nullable(),
+        (Type::List(a), Type::List(b)) => Type::List(Box::new(join(*a, *b))),
+        // As typeck merges record literals, a field only some records have
+        // is optional.
+        (Type::Record(a), Type::Record(mut b)) => {
+            let mut fields: Vec<_> = a
+                .into_iter()
+                .map(|(name, a)| match b.iter().position(|(y, _)| *y == name) {
+                    Some(at) => (name, join(a, b.remove(at).1)),
+                    None => (name, a.nullable()),
+                })
+                .collect();
+            fields.extend(b.into_iter().map(|(name, b)| (name, b.nullable())));
+            Type::Record(fields)
+        }
+        _ => Type::Any,
+    }
+}
+
+/// Read the JSON document `source` into a value, typed by [`data_scheme`].
+/// Nothing in it is evaluated or type-checked. Nesting deeper than
+/// serde_json's limit of 128 is an error rather than a deep recursion.
+pub fn parse_json(source: &str) -> Result<(Value, Scheme), Diagnostic> {
+    let value: Value = serde_json::from_str(source).map_err(|e| json_error(source, &e))?;
+    let scheme = data_scheme(&value);
+    Ok((value, scheme))
+}
+
+/// `e` as a `Diagnostic` at the line and column serde_json reports.
+fn json_error(source: &str, e: &serde_json::Error) -> Diagnostic {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(e.line().saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let mut at = (line_start + e.column().saturating_sub(1)).min(source.len());
+    while !source.is_char_boundary(at) {
+        at -= 1;
+    }
+    let text = e.to_string();
+    let message = text.rsplit_once(" at line ").map_or(text.as_str(), |(m, _)| m);
+    Diagnostic::new(at..at, message, "not JSON").with_code("E0601")
+}
+
+/// The forced fields of a record, sorted by name.
+fn sorted_fields(frame: &Rc<Frame>) -> Result<Vec<(&'static str, Value)>, Error> {
+    let Some(fields) = frame.fields() else {
+        return Err(Error::Message("cannot convert a non-record frame".to_string()));
+    };
+    let mut out = fields
+        .into_iter()
+        .map(|(name, slot)| Ok((display(name), frame.get(slot).map_err(Error::Eval)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    out.sort_by_key(|(name, _)| *name);
+    Ok(out)
+}
+
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let node = manifest::manifest(self, true, &(0..0)).map_err(|d| ser::Error::custom(d.message))?;
+        node.serialize(serializer)
+    }
+}
+
+impl Serialize for Node {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.kind {
+            Kind::Null => serializer.serialize_unit(),
+            Kind::Bool(b) => serializer.serialize_bool(*b),
+            Kind::Integer(i) => serializer.serialize_i64(*i),
+            Kind::Number(n) => serializer.serialize_f64(*n),
+            Kind::String(s) => serializer.serialize_str(s),
+            Kind::List(xs) => {
+                let mut seq = serializer.serialize_seq(Some(xs.len()))?;
+                for x in xs {
+                    seq.serialize_element(x)?;
+                }
+                seq.end()
+            }
+            Kind::Record(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (name, value) in fields {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match &self {
+            Value::Number(n) => match as_integer(*n) {
+                Some(i) => visitor.visit_i64(i),
+                None => visitor.visit_f64(*n),
+            },
+            Value::String(s) => visitor.visit_str(s),
+            Value::Bool(b) => visitor.visit_bool(*b),
+            Value::Null => visitor.visit_unit(),
+            Value::List(xs) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(xs.iter().cloned()))
+            }
+            Value::Function(_) => Err(de::Error::custom("cannot deserialize a function")),
+            Value::Block(frame) => {
+                let fields = sorted_fields(frame)?;
+                visitor.visit_map(de::value::MapDeserializer::new(fields.into_iter()))
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Null => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// A string is a unit variant; a record with a single field is the
+    /// variant named by that field, holding its value.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match &self {
+            Value::String(s) => visitor.visit_enum(s.as_str().into_deserializer()),
+            Value::Block(frame) => {
+                let mut fields = sorted_fields(frame)?;
+                if fields.len() != 1 {
+                    return Err(de::Error::custom(format!(
+                        "expected a record with one field for an enum, got {} fields",
+                        fields.len()
+                    )));
+                }
+                let (variant, value) = fields.pop().expect("one field");
+                visitor.visit_enum(Variant { variant, value })
+            }
+            other => Err(de::Error::custom(format!(
+                "expected a string or a record for an enum, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+struct Variant {
+    variant: &'static str,
+    value: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant {
+    type Error = Error;
+    type Variant = Value;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Value), Error> {
+        let name = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.variant))?;
+        Ok((name, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -1,32 +1,525 @@
 use crate::lexer::Span;
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    /// Something that may fail at run time but need not: typeck's possibly
+    /// null values and non-exhaustive matches. `--check` passes with them.
+    Warning,
+    Note,
+}
 
 #[derive(Debug, Clone)]
 pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable code, explained by `spctr --explain`; see `codes`.
+    pub code: Option<&'static str>,
     pub span: Span,
     pub message: String,
     pub label: String,
+    /// Boxed, as most diagnostics have none of it and every fallible
+    /// function returns a `Diagnostic`.
+    pub details: Box<Details>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Details {
+    /// Further spans in the same file, each with what it has to do with
+    /// the problem.
+    pub labels: Vec<(Span, String)>,
+    pub notes: Vec<String>,
+    /// A suggested fix.
+    pub help: Option<String>,
+    /// The imported module `span` is in; `None` for the source the
+    /// diagnostic is reported against.
+    pub file: Option<PathBuf>,
+    /// The `import` calls that led to `file`, innermost first: the module
+    /// each one is in (`None` as for `file`) and its span there.
+    pub imported_from: Vec<(Option<PathBuf>, Span)>,
+    /// The calls the evaluator was in when the error happened, innermost
+    /// first. `None` for diagnostics that are not runtime errors, and for
+    /// ones no evaluator has seen yet.
+    pub trace: Option<Vec<StackFrame>>,
+}
+
+/// A call in a runtime error's backtrace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    /// The callee as written at the call: `f`, `List.map`; `<function>` when
+    /// it is not a name.
+    pub name: String,
+    pub span: Span,
+    /// The module the call is in; `None` as for `Details::file`.
+    pub file: Option<PathBuf>,
+    /// How many tail calls were made between the next (outer) frame and
+    /// this one. Their frames were reused, so they are not in the trace.
+    pub elided: usize,
 }
 
 impl Diagnostic {
     pub fn new(span: Span, message: impl Into<String>, label: impl Into<String>) -> Self {
         Self {
+            severity: Severity::Error,
+            code: None,
             span,
             message: message.into(),
             label: label.into(),
+            details: Box::default(),
+        }
+    }
+
+    pub fn warning(mut self) -> Self {
+        self.severity = Severity::Warning;
+        self
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, label: impl Into<String>) -> Self {
+        self.details.labels.push((span, label.into()));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.details.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.details.help = Some(help.into());
+        self
+    }
+
+    /// `self`, with a help suggesting the one of `candidates` closest to
+    /// the misspelt `name`, if one is close enough.
+    pub fn did_you_mean<'a>(
+        self,
+        name: &str,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        match closest(name, candidates) {
+            Some(c) => self.with_help(format!("did you mean `{}`?", c)),
+            None => self,
+        }
+    }
+
+    /// `self`, raised while loading or running `module`, seen from the
+    /// `import` in `from` at `span` that loaded it.
+    pub fn imported(mut self, module: &Path, from: Option<&Path>, span: &Span) -> Self {
+        if self.details.file.is_none() {
+            self.details.file = Some(module.to_path_buf());
         }
+        self.details.imported_from.push((from.map(Path::to_path_buf), span.clone()));
+        self
     }
 }
 
+/// How `report` writes diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// ariadne's annotated source, for people.
+    #[default]
+    Text,
+    /// One `to_json` object per line.
+    Json,
+    /// A single SARIF 2.1.0 log of all of them, written by `finish`.
+    Sarif,
+}
+
+/// The one of `candidates` most likely meant by `name`: the closest by
+/// edit distance (ignoring case, a swap of adjacent letters counting as one
+/// edit), within a third of the length of `name`. Ties go to the first in
+/// alphabetical order, so the choice does not depend on map order.
+pub fn closest<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|c| *c != name)
+        .map(|c| (distance(name, c), c))
+        .filter(|(d, _)| *d <= max)
+        .min()
+        .map(|(_, c)| c)
+}
+
+/// Optimal string alignment distance between `a` and `b`, ignoring case.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    // d[i][j]: the distance between a[..i] and b[..j].
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+thread_local! {
+    /// Sources of the modules loaded on this thread, for rendering the
+    /// diagnostics inside them.
+    static SOURCES: RefCell<HashMap<PathBuf, Rc<str>>> = RefCell::new(HashMap::new());
+    static FORMAT: Cell<Format> = const { Cell::new(Format::Text) };
+    /// The SARIF results `report` collected for `finish`.
+    static RESULTS: RefCell<Vec<Value>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Make `report` write diagnostics on this thread as `format`.
+pub fn set_format(format: Format) {
+    FORMAT.with(|f| f.set(format));
+}
+
+/// Remember `source` as the text of `module`. `imports::load` calls this
+/// for every module it loads.
+pub fn add_source(module: &Path, source: &str) {
+    SOURCES.with(|s| s.borrow_mut().insert(module.to_path_buf(), source.into()));
+}
+
+/// The source `add_source` recorded for `module`.
+pub fn source(module: &Path) -> Option<Rc<str>> {
+    SOURCES.with(|s| s.borrow().get(module).cloned())
+}
+
+/// Write `diag`, found in `src` (named `filename`), to stderr in the
+/// format `set_format` chose. SARIF is held back until `finish`.
 pub fn report(filename: &str, src: &str, diag: &Diagnostic) {
-    use ariadne::{Color, Label, Report, ReportKind, Source};
+    match FORMAT.with(Cell::get) {
+        Format::Text => {
+            let (report, sources) = build(filename, src, diag, true);
+            let _ = report.eprint(ariadne::sources(sources));
+        }
+        Format::Json => eprintln!("{}", to_json(filename, src, diag)),
+        Format::Sarif => {
+            let result = sarif_result(filename, src, diag);
+            RESULTS.with(|r| r.borrow_mut().push(result));
+        }
+    }
+}
 
-    let _ = Report::build(ReportKind::Error, (filename, diag.span.clone()))
+/// Write what `report` held back: the SARIF log, even an empty one, so CI
+/// always has a file to upload. Call it once all diagnostics are reported.
+pub fn finish() {
+    if FORMAT.with(Cell::get) == Format::Sarif {
+        let results = RESULTS.with(|r| std::mem::take(&mut *r.borrow_mut()));
+        eprintln!("{:#}", sarif_log(results));
+    }
+}
+
+/// `diag` as `report` prints it, without colors.
+pub fn render(filename: &str, src: &str, diag: &Diagnostic) -> String {
+    let (report, sources) = build(filename, src, diag, false);
+    let mut out = Vec::new();
+    let _ = report.write(ariadne::sources(sources), &mut out);
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+type Report = ariadne::Report<'static, (String, Span)>;
+
+/// The report for `diag`, and the sources its labels point into: `src` as
+/// `filename`, and each module it mentions as its path. A module whose
+/// source was not recorded (the one being reported, named by its path) is
+/// taken to be `src`.
+fn build(
+    filename: &str,
+    src: &str,
+    diag: &Diagnostic,
+    color: bool,
+) -> (Report, Vec<(String, Rc<str>)>) {
+    use ariadne::{Color, Config, Label, ReportKind};
+
+    let mut sources: Vec<(String, Rc<str>)> = vec![(filename.to_string(), src.into())];
+    let mut id = |file: &Option<PathBuf>| {
+        let Some((path, text)) = file.as_deref().and_then(|path| Some((path, source(path)?))) else {
+            return filename.to_string();
+        };
+        let name = path.display().to_string();
+        if !sources.iter().any(|(n, _)| *n == name) {
+            sources.push((name.clone(), text));
+        }
+        name
+    };
+
+    let (kind, color_of_kind) = match diag.severity {
+        Severity::Error => (ReportKind::Error, Color::Red),
+        Severity::Warning => (ReportKind::Warning, Color::Yellow),
+        Severity::Note => (ReportKind::Advice, Color::Fixed(147)),
+    };
+    let file = id(&diag.details.file);
+    let at = (file.clone(), diag.span.clone());
+    let mut report = ariadne::Report::build(kind, at.clone())
+        .with_config(Config::default().with_color(color))
         .with_message(&diag.message)
-        .with_label(
-            Label::new((filename, diag.span.clone()))
-                .with_message(&diag.label)
-                .with_color(Color::Red),
-        )
-        .finish()
-        .eprint((filename, Source::from(src)));
+        .with_label(Label::new(at).with_message(&diag.label).with_color(color_of_kind));
+    if let Some(code) = diag.code {
+        report = report.with_code(code);
+    }
+    for (span, label) in &diag.details.labels {
+        report = report.with_label(
+            Label::new((file.clone(), span.clone()))
+                .with_message(label)
+                .with_color(Color::Blue),
+        );
+    }
+    for (order, (file, span)) in diag.details.imported_from.iter().enumerate() {
+        report = report.with_label(
+            Label::new((id(file), span.clone()))
+                .with_message("imported here")
+                .with_color(Color::Cyan)
+                .with_order(order as i32 + 1),
+        );
+    }
+    for note in &diag.details.notes {
+        report = report.with_note(note);
+    }
+    if let Some(trace) = diag.details.trace.as_ref().filter(|t| !t.is_empty()) {
+        report = report.with_note(backtrace(filename, src, trace));
+    }
+    if let Some(help) = &diag.details.help {
+        report = report.with_help(help);
+    }
+    (report.finish(), sources)
+}
+
+/// Frames shown at each end of a longer backtrace; a runaway recursion
+/// has as many as `--max-depth` allows.
+const BACKTRACE_ENDS: usize = 10;
+
+/// `trace` as a note: one `at NAME (FILE:LINE:COLUMN)` line per frame.
+fn backtrace(filename: &str, src: &str, trace: &[StackFrame]) -> String {
+    let mut out = "backtrace, most recent call first:".to_string();
+    for (i, frame) in trace.iter().enumerate() {
+        if trace.len() > 2 * BACKTRACE_ENDS && i >= BACKTRACE_ENDS {
+            if i == BACKTRACE_ENDS {
+                let omitted = trace.len() - 2 * BACKTRACE_ENDS;
+                out.push_str(&format!("\n  ... {} more frames", omitted));
+            }
+            if i < trace.len() - BACKTRACE_ENDS {
+                continue;
+            }
+        }
+        let (file, text) = file_source(filename, src, &frame.file);
+        let (line, column) = line_col(&text, frame.span.start);
+        out.push_str(&format!("\n  at {} ({}:{}:{})", frame.name, file, line, column));
+        match frame.elided {
+            0 => {}
+            1 => out.push_str("\n  ... 1 tail call elided"),
+            n => out.push_str(&format!("\n  ... {} tail calls elided", n)),
+        }
+    }
+    out
+}
+
+/// 1-based line and column (in characters) of byte `offset` in `text`.
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Where `span` is in `text`, the source of `file`: the byte span, and
+/// the `line_col` of both ends.
+fn location(file: &str, text: &str, span: &Span) -> Value {
+    let line_col = |offset: usize| {
+        let (line, column) = line_col(text, offset);
+        json!({"line": line, "column": column})
+    };
+    json!({
+        "file": file,
+        "span": {"start": span.start, "end": span.end},
+        "start": line_col(span.start),
+        "end": line_col(span.end),
+    })
+}
+
+/// The name and source of `file` as `build` would pick them.
+fn file_source(filename: &str, src: &str, file: &Option<PathBuf>) -> (String, Rc<str>) {
+    match file.as_deref().and_then(|path| Some((path, source(path)?))) {
+        Some((path, text)) => (path.display().to_string(), text),
+        None => (filename.to_string(), src.into()),
+    }
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    }
+}
+
+/// `diag` as a JSON object: its location (see `location`), `severity`,
+/// `code` (or `null`), `message`, `label`, the secondary `labels` with
+/// their locations, `notes`, `help`, the `imported_from` chain and the
+/// backtrace `trace` (or `null`), each frame a location with `name` and
+/// `elided`.
+pub fn to_json(filename: &str, src: &str, diag: &Diagnostic) -> Value {
+    let (file, text) = file_source(filename, src, &diag.details.file);
+    let mut out = location(&file, &text, &diag.span);
+    let labels: Vec<_> = diag
+        .details
+        .labels
+        .iter()
+        .map(|(span, label)| {
+            let mut at = location(&file, &text, span);
+            at["message"] = json!(label);
+            at
+        })
+        .collect();
+    let imported_from: Vec<_> = diag
+        .details
+        .imported_from
+        .iter()
+        .map(|(from, span)| {
+            let (file, text) = file_source(filename, src, from);
+            location(&file, &text, span)
+        })
+        .collect();
+    let trace = diag.details.trace.as_ref().map(|trace| {
+        trace
+            .iter()
+            .map(|frame| {
+                let (file, text) = file_source(filename, src, &frame.file);
+                let mut at = location(&file, &text, &frame.span);
+                at["name"] = json!(frame.name);
+                at["elided"] = json!(frame.elided);
+                at
+            })
+            .collect::<Vec<_>>()
+    });
+    let fields = json!({
+        "severity": severity_name(diag.severity),
+        "code": diag.code,
+        "message": diag.message,
+        "label": diag.label,
+        "labels": labels,
+        "notes": diag.details.notes,
+        "help": diag.details.help,
+        "imported_from": imported_from,
+        "trace": trace,
+    });
+    if let (Value::Object(out), Value::Object(fields)) = (&mut out, fields) {
+        out.extend(fields);
+    }
+    out
+}
+
+/// `diags`, all found in `src`, as a SARIF 2.1.0 log.
+pub fn to_sarif(filename: &str, src: &str, diags: &[Diagnostic]) -> Value {
+    sarif_log(diags.iter().map(|d| sarif_result(filename, src, d)).collect())
+}
+
+fn sarif_result(filename: &str, src: &str, diag: &Diagnostic) -> Value {
+    let diag = to_json(filename, src, diag);
+    let physical = |at: &Value| {
+        let offset = |end: &str| at["span"][end].as_u64().unwrap_or(0);
+        json!({
+            "artifactLocation": {"uri": at["file"]},
+            "region": {
+                "startLine": at["start"]["line"],
+                "startColumn": at["start"]["column"],
+                "endLine": at["end"]["line"],
+                "endColumn": at["end"]["column"],
+                "byteOffset": offset("start"),
+                "byteLength": offset("end").saturating_sub(offset("start")),
+            },
+        })
+    };
+    let str_of = |v: &Value| v.as_str().unwrap_or("").to_string();
+    let mut text = format!("{}: {}", str_of(&diag["message"]), str_of(&diag["label"]));
+    for note in diag["notes"].as_array().into_iter().flatten() {
+        text.push_str(&format!("\nnote: {}", str_of(note)));
+    }
+    if let Some(help) = diag["help"].as_str() {
+        text.push_str(&format!("\nhelp: {}", help));
+    }
+    let labels = diag["labels"].as_array().into_iter().flatten();
+    let imported = diag["imported_from"].as_array().into_iter().flatten();
+    let related: Vec<_> = labels
+        .map(|at| (at, at["message"].clone()))
+        .chain(imported.map(|at| (at, json!("imported here"))))
+        .enumerate()
+        .map(|(id, (at, message))| {
+            json!({"id": id, "physicalLocation": physical(at), "message": {"text": message}})
+        })
+        .collect();
+    let mut result = json!({
+        "level": diag["severity"],
+        "message": {"text": text},
+        "locations": [{"physicalLocation": physical(&diag)}],
+    });
+    if !related.is_empty() {
+        result["relatedLocations"] = json!(related);
+    }
+    if let Some(code) = diag["code"].as_str() {
+        result["ruleId"] = json!(code);
+    }
+    if let Some(trace) = diag["trace"].as_array().filter(|t| !t.is_empty()) {
+        let frames: Vec<_> = trace
+            .iter()
+            .map(|at| {
+                let message = json!({"text": at["name"]});
+                json!({"location": {"physicalLocation": physical(at), "message": message}})
+            })
+            .collect();
+        result["stacks"] = json!([{"message": {"text": "backtrace"}, "frames": frames}]);
+    }
+    result
+}
+
+/// The SARIF log of `results`, with a rule for each code they use.
+fn sarif_log(results: Vec<Value>) -> Value {
+    let mut codes: Vec<&str> = results.iter().filter_map(|r| r["ruleId"].as_str()).collect();
+    codes.sort_unstable();
+    codes.dedup();
+    let rules: Vec<_> = codes
+        .into_iter()
+        .filter_map(|code| Some((code, crate::codes::explain(code)?)))
+        .map(|(code, text)| {
+            let title = text.lines().next().unwrap_or("");
+            json!({
+                "id": code,
+                "shortDescription": {"text": title},
+                "fullDescription": {"text": text},
+                "help": {"text": format!("spctr --explain {}", code)},
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {"driver": {
+                "name": "spctr",
+                "version": env!("CARGO_PKG_VERSION"),
+                "rules": rules,
+            }},
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
 }
@@ -0,0 +1,222 @@
+//! Embedding API.
+//!
+//! An [`Engine`] bundles what a host configures once — the import resolver,
+//! the recursion limit and its own root bindings — and evaluates programs
+//! with it:
+//!
+//! ```
+//! use spctr::engine::{Engine, HostModule};
+//! use spctr::interp::Value;
+//! use spctr::types::{Scheme, Type};
+//!
+//! let mut engine = Engine::new();
+//! engine.register_module(
+//!     "Host",
+//!     HostModule::new().function(
+//!         "twice",
+//!         Scheme::mono(Type::Fn(vec![Type::Number], Box::new(Type::Number))),
+//!         |args, _span| match args[0] {
+//!             Value::Number(n) => Ok(Value::Number(n * 2.0)),
+//!             _ => unreachable!("typeck checked the argument"),
+//!         },
+//!     ),
+//! );
+//! let out = engine.eval_str("Host.twice(21)").unwrap();
+//! assert_eq!(out.value.to_string(), "42");
+//! ```
+//!
+//! Host bindings are appended to the builtin roots, so the resolver and
+//! typeck see them like `List` or `import`, imported modules included.
+//! Programs are type-checked before they run: a type error fails the
+//! evaluation, while warnings come back with the value.
+
+use crate::diag::Diagnostic;
+use crate::interp::{self, EvalResult, Frame, Function, HostRoot, Value};
+use crate::lexer::Span;
+use crate::stdlib::imports::{self, FsResolver, ImportResolver};
+use crate::symbol::{intern, Symbol};
+use crate::types::{Scheme, Type};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+pub struct Engine {
+    resolver: Rc<dyn ImportResolver>,
+    max_depth: usize,
+    host: Rc<Vec<HostRoot>>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    /// An engine importing from the working directory, with the default
+    /// recursion limit and no host bindings.
+    pub fn new() -> Self {
+        Self {
+            resolver: Rc::new(FsResolver::default()),
+            max_depth: interp::DEFAULT_MAX_DEPTH,
+            host: Rc::new(Vec::new()),
+        }
+    }
+
+    pub fn set_resolver(&mut self, resolver: impl ImportResolver + 'static) -> &mut Self {
+        self.resolver = Rc::new(resolver);
+        self
+    }
+
+    /// See `interp::set_max_depth`.
+    pub fn set_max_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Bind `name` at the root to a host function of type `scheme`.
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        scheme: Scheme,
+        f: impl Fn(Vec<Value>, &Span) -> EvalResult + 'static,
+    ) -> &mut Self {
+        let value = Value::Function(Function::Foreign(Rc::new(f)));
+        self.register(name, scheme, value)
+    }
+
+    /// Bind `name` at the root to a record of host functions.
+    pub fn register_module(&mut self, name: &str, module: HostModule) -> &mut Self {
+        let mut binds = Vec::with_capacity(module.fields.len());
+        let mut names = HashMap::with_capacity(module.fields.len());
+        let mut types = Vec::with_capacity(module.fields.len());
+        for (field, scheme, value) in module.fields {
+            names.insert(field, binds.len() as u32);
+            binds.push(Rc::new(RefCell::new(interp::BindState::Done(value))));
+            types.push((field, scheme));
+        }
+        let value = Value::Block(Rc::new(Frame {
+            binds,
+            names: Some(names),
+            parent: interp::Env::empty(),
+        }));
+        self.register(name, Scheme::mono(Type::Module(types)), value)
+    }
+
+    fn register(&mut self, name: &str, scheme: Scheme, value: Value) -> &mut Self {
+        let name = intern(name);
+        let host = Rc::make_mut(&mut self.host);
+        host.retain(|h| h.name != name);
+        host.push(HostRoot { name, scheme, value });
+        self
+    }
+
+    /// Parse, check and evaluate `source`. Relative imports are resolved
+    /// from the resolver's root.
+    pub fn eval_str(&self, source: &str) -> Result<Evaluated, Vec<Diagnostic>> {
+        let _installed = self.install();
+        eval(source, None)
+    }
+
+    /// Load `path` through the resolver, then evaluate it like `eval_str`.
+    /// Relative imports are resolved from the file itself.
+    pub fn eval_file(&self, path: impl AsRef<Path>) -> Result<Evaluated, Vec<Diagnostic>> {
+        let _installed = self.install();
+        let path = path.as_ref().to_string_lossy();
+        let module = self
+            .resolver
+            .resolve(&path, None)
+            .map_err(|msg| {
+                vec![Diagnostic::new(0..0, msg, "cannot load program").with_code("E0401")]
+            })?;
+        let source = self
+            .resolver
+            .load(&module)
+            .map_err(|msg| {
+                vec![Diagnostic::new(0..0, msg, "cannot load program").with_code("E0401")]
+            })?;
+        eval(&source, Some(&module))
+    }
+
+    /// Install this engine's resolver, limit and host roots on the current
+    /// thread until the returned guard drops, even by a panic in a host
+    /// function.
+    fn install(&self) -> Installed {
+        let max_depth = interp::max_depth();
+        interp::set_max_depth(self.max_depth);
+        Installed {
+            resolver: imports::set_resolver(self.resolver.clone()),
+            host: interp::set_host_roots(self.host.clone()),
+            max_depth,
+        }
+    }
+}
+
+/// What `Engine::install` replaced, put back on drop.
+struct Installed {
+    resolver: Rc<dyn ImportResolver>,
+    host: Rc<Vec<HostRoot>>,
+    max_depth: usize,
+}
+
+impl Drop for Installed {
+    fn drop(&mut self) {
+        interp::set_max_depth(self.max_depth);
+        interp::set_host_roots(self.host.clone());
+        imports::set_resolver(self.resolver.clone());
+    }
+}
+
+/// A program's value, with the warnings typeck had about it.
+#[derive(Debug)]
+pub struct Evaluated {
+    pub value: Value,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Errors fail the evaluation, reported with the warnings before them.
+fn eval(source: &str, module: Option<&Path>) -> Result<Evaluated, Vec<Diagnostic>> {
+    let ast = crate::parser::parse(source)?;
+    crate::resolver::resolve(&ast, &interp::root_names()).map_err(|d| vec![d])?;
+    let mut result = crate::typeck::check_in(&ast, &interp::root_schemes(), module);
+    if result.has_errors() {
+        return Err(result.warnings);
+    }
+    match interp::run_in(&ast, module.map(Path::to_path_buf)) {
+        Ok(value) => Ok(Evaluated {
+            value,
+            warnings: result.warnings,
+        }),
+        Err(d) => {
+            result.warnings.push(d);
+            Err(result.warnings)
+        }
+    }
+}
+
+/// A record of host functions for [`Engine::register_module`].
+#[derive(Default)]
+pub struct HostModule {
+    fields: Vec<(Symbol, Scheme, Value)>,
+}
+
+impl HostModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the field `name`, a host function of type `scheme`.
+    pub fn function(
+        mut self,
+        name: &str,
+        scheme: Scheme,
+        f: impl Fn(Vec<Value>, &Span) -> EvalResult + 'static,
+    ) -> Self {
+        let name = intern(name);
+        self.fields.retain(|(n, _, _)| *n != name);
+        let value = Value::Function(Function::Foreign(Rc::new(f)));
+        self.fields.push((name, scheme, value));
+        self
+    }
+}
@@ -0,0 +1,713 @@
+//! Source formatter (`spctr fmt`).
+//!
+//! The parser drops comments, so formatting works from two inputs: the AST,
+//! which fixes what is printed, and the comment spans the lexer kept
+//! (`lexer::lex_with_comments`), which are put back by position. A comment
+//! before an entry of a program, block, list, argument list or `match`
+//! stays on its own line before that entry; one on the same line after an
+//! entry stays after it. A comment anywhere else moves to the next such
+//! place, so none is ever lost. At most one blank line between entries is
+//! kept.
+//!
+//! The AST is turned into a [`Doc`] that is laid out Wadler-style: a group
+//! is printed on one line when it fits in [`WIDTH`] columns and contains no
+//! comment that ends a line, and otherwise one entry per line; a block
+//! whose first entry starts on a new line is always broken. Parentheses
+//! come from operator precedence, since the AST does not record them.
+//! Literal text — numbers, strings, string fragments — is copied from the
+//! source as written. Keys are written bare when they can be.
+//!
+//! As a guard, the result is parsed again and must give the same
+//! `Statement` (spans aside) and keep every comment; otherwise formatting
+//! fails rather than change the program.
+
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::lexer::{lex_with_comments, Span};
+use crate::symbol::display;
+
+/// The line width layout aims for.
+pub const WIDTH: usize = 80;
+
+/// Format `src`. Errors are the parser's, or the guard's if the output
+/// would not parse back to the same program.
+pub fn format(src: &str) -> Result<String, Vec<Diagnostic>> {
+    let stmt = crate::parser::parse(src)?;
+    let comments = match lex_with_comments(src) {
+        Ok((_, comments)) => comments,
+        Err(_) => unreachable!("parse succeeded, so lexing does"),
+    };
+    let mut printer = Printer {
+        src,
+        comments: &comments,
+        next: 0,
+    };
+    let doc = printer.program(&stmt);
+    let mut out = render(&doc, WIDTH);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+
+    let unchanged = match crate::parser::parse(&out) {
+        Ok(again) => same_stmt(&stmt, &again),
+        Err(_) => false,
+    };
+    let kept = matches!(lex_with_comments(&out), Ok((_, c)) if c.len() == comments.len());
+    if !unchanged || !kept {
+        let what = if unchanged { "drop a comment" } else { "change the program" };
+        return Err(vec![Diagnostic::new(
+            0..0,
+            format!("formatting would {}", what),
+            "this is a bug in spctr fmt",
+        )]);
+    }
+    Ok(out)
+}
+
+/// A layout document.
+#[derive(Clone)]
+enum Doc {
+    Text(String),
+    /// A space, or a newline when the enclosing group is broken.
+    Line,
+    /// Nothing, or a newline when the enclosing group is broken.
+    SoftLine,
+    /// Always a newline; breaks every enclosing group.
+    HardLine,
+    /// Nothing, but breaks every enclosing group (after a `//` comment).
+    BreakParent,
+    Concat(Vec<Doc>),
+    /// The contents, with newlines indented two more columns.
+    Nest(Box<Doc>),
+    /// One line if the contents fit, otherwise broken. The flag records
+    /// that the contents force a break.
+    Group(Box<Doc>, bool),
+}
+
+fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    let broken = forces_break(&doc);
+    Doc::Group(Box::new(doc), broken)
+}
+
+fn forces_break(doc: &Doc) -> bool {
+    match doc {
+        Doc::HardLine | Doc::BreakParent => true,
+        Doc::Text(s) => s.contains('\n'),
+        Doc::Line | Doc::SoftLine => false,
+        Doc::Concat(docs) => docs.iter().any(forces_break),
+        Doc::Nest(doc) => forces_break(doc),
+        Doc::Group(_, broken) => *broken,
+    }
+}
+
+fn columns(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    // (indent, flat, doc), next to print last.
+    let mut stack = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                out.push_str(s);
+                column = match s.rfind('\n') {
+                    Some(i) => columns(&s[i + 1..]),
+                    None => column + columns(s),
+                };
+            }
+            Doc::Line if flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                while out.ends_with(' ') {
+                    out.pop();
+                }
+                out.push('\n');
+                out.extend(std::iter::repeat_n(' ', indent));
+                column = indent;
+            }
+            Doc::BreakParent => {}
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|d| (indent, flat, d))),
+            Doc::Nest(doc) => stack.push((indent + 2, flat, doc)),
+            Doc::Group(doc, broken) => {
+                let flat = flat
+                    || (!broken && fits(width.saturating_sub(column), doc, &stack));
+                stack.push((indent, flat, doc));
+            }
+        }
+    }
+    out
+}
+
+/// Whether `doc`, printed flat, and whatever follows it up to the next
+/// possible line break fit in `room` columns.
+fn fits(room: usize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut room = room as isize;
+    let mut next = vec![(true, doc)];
+    let mut rest = rest.iter().rev();
+    loop {
+        let (flat, doc) = match next.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(&(_, flat, doc)) => (flat, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(s) => {
+                if s.contains('\n') {
+                    return true;
+                }
+                room -= columns(s) as isize;
+            }
+            Doc::Line if flat => room -= 1,
+            Doc::SoftLine if flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::BreakParent => {}
+            Doc::Concat(docs) => next.extend(docs.iter().rev().map(|d| (flat, d))),
+            Doc::Nest(doc) => next.push((flat, doc)),
+            Doc::Group(doc, broken) => next.push((flat && !broken, doc)),
+        }
+        if room < 0 {
+            return false;
+        }
+    }
+}
+
+/// An entry of a comma-separated sequence.
+enum Entry<'a> {
+    Bind(&'a Bind),
+    Expr(&'a Spanned<Expr>),
+    Arm(&'a MatchArm),
+}
+
+impl Entry<'_> {
+    fn span(&self) -> Span {
+        match self {
+            Entry::Bind(((_, key), (_, value))) => key.start..value.end,
+            Entry::Expr((_, span)) => span.clone(),
+            Entry::Arm(arm) => arm.pattern.1.start..arm.body.1.end,
+        }
+    }
+}
+
+struct Printer<'a> {
+    src: &'a str,
+    comments: &'a [Span],
+    /// The first comment not printed yet.
+    next: usize,
+}
+
+impl Printer<'_> {
+    fn program(&mut self, stmt: &Statement) -> Doc {
+        // A program without a body parses as `null` spanning everything.
+        let body = match &stmt.body {
+            (Expr::Null, span) if &self.src[span.clone()] != "null" => None,
+            body => Some(body),
+        };
+        let entries = statement_entries(&stmt.definitions, body);
+        self.entries(entries, self.src.len(), Doc::HardLine)
+    }
+
+    /// `entries` separated by commas and `sep`, with the comments before
+    /// `end` that fall among them.
+    fn entries(&mut self, entries: Vec<Entry>, end: usize, sep: Doc) -> Doc {
+        let spans: Vec<Span> = entries.iter().map(Entry::span).collect();
+        let mut parts = Vec::new();
+        for (i, entry) in entries.into_iter().enumerate() {
+            let span = &spans[i];
+            let last = i + 1 == spans.len();
+            while let Some(comment) = self.comment_before(span.start) {
+                if !parts.is_empty() && self.blank_before(comment.start) {
+                    parts.push(Doc::HardLine);
+                }
+                parts.push(text(&self.src[comment.clone()]));
+                // A block comment on the entry's line stays on it.
+                if self.is_block(&comment) && !self.src[comment.end..span.start].contains('\n') {
+                    parts.push(text(" "));
+                } else {
+                    parts.push(Doc::HardLine);
+                }
+            }
+            if !parts.is_empty() && self.blank_before(span.start) {
+                parts.push(Doc::HardLine);
+            }
+            parts.push(self.entry(entry));
+            if !last {
+                parts.push(text(","));
+            }
+            // Comments left inside the entry, then those after it on its
+            // line.
+            let until = spans.get(i + 1).map_or(end, |next| next.start);
+            while let Some(comment) = self.comment_before(until) {
+                if comment.start > span.end && self.src[span.end..comment.start].contains('\n') {
+                    self.next -= 1;
+                    break;
+                }
+                parts.push(text(" "));
+                parts.push(text(&self.src[comment.clone()]));
+                if !self.is_block(&comment) {
+                    parts.push(Doc::BreakParent);
+                }
+            }
+            if !last {
+                parts.push(sep.clone());
+            }
+        }
+        while let Some(comment) = self.comment_before(end) {
+            if !parts.is_empty() {
+                parts.push(Doc::HardLine);
+                if self.blank_before(comment.start) {
+                    parts.push(Doc::HardLine);
+                }
+            }
+            parts.push(text(&self.src[comment]));
+            parts.push(Doc::BreakParent);
+        }
+        concat(parts)
+    }
+
+    fn is_block(&self, comment: &Span) -> bool {
+        self.src[comment.clone()].starts_with("/*")
+    }
+
+    /// The next comment, if it starts before `pos`.
+    fn comment_before(&mut self, pos: usize) -> Option<Span> {
+        let comment = self.comments.get(self.next).filter(|c| c.start < pos)?.clone();
+        self.next += 1;
+        Some(comment)
+    }
+
+    /// Whether a blank line precedes `pos`.
+    fn blank_before(&self, pos: usize) -> bool {
+        let before = &self.src[..pos];
+        let gap = &before[before.trim_end().len()..];
+        gap.matches('\n').count() >= 2
+    }
+
+    fn entry(&mut self, entry: Entry) -> Doc {
+        match entry {
+            Entry::Bind(((name, key), value)) => {
+                concat(vec![self.key(*name, key), text(": "), self.expr(value)])
+            }
+            Entry::Expr(expr) => self.expr(expr),
+            Entry::Arm(arm) => {
+                let mut parts = vec![self.pattern(&arm.pattern)];
+                if let Some(guard) = &arm.guard {
+                    parts.push(text(" if "));
+                    parts.push(self.expr(guard));
+                }
+                parts.push(text(" => "));
+                parts.push(self.expr(&arm.body));
+                concat(parts)
+            }
+        }
+    }
+
+    /// `open entries close`, one entry per line when broken. `pad` puts a
+    /// space inside the delimiters when on one line.
+    fn delimited(&mut self, open: &str, entries: Vec<Entry>, end: usize, close: &str, pad: bool) -> Doc {
+        let inner = self.entries(entries, end, Doc::Line);
+        if matches!(&inner, Doc::Concat(parts) if parts.is_empty()) {
+            return text(format!("{}{}", open, close));
+        }
+        let line = if pad { Doc::Line } else { Doc::SoftLine };
+        group(concat(vec![
+            text(open),
+            nest(concat(vec![line.clone(), inner])),
+            line,
+            text(close),
+        ]))
+    }
+
+    /// A block. One written across lines stays that way, as records of
+    /// settings usually are.
+    fn record(&mut self, entries: Vec<Entry>, span: &Span) -> Doc {
+        let first = entries.first().map_or(span.end, |e| e.span().start);
+        let multiline = self.src[span.start..first].contains('\n');
+        let doc = self.delimited("{", entries, span.end, "}", false);
+        match doc {
+            Doc::Group(inner, _) if multiline => Doc::Group(inner, true),
+            doc => doc,
+        }
+    }
+
+    fn key(&self, name: crate::symbol::Symbol, span: &Span) -> Doc {
+        if is_ident(display(name)) {
+            text(display(name))
+        } else {
+            text(&self.src[span.clone()])
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Doc {
+        let (e, span) = expr;
+        match e {
+            Expr::Number(_) | Expr::String(_) => text(&self.src[span.clone()]),
+            Expr::Null => text("null"),
+            Expr::Bool(b) => text(b.to_string()),
+            Expr::Error => unreachable!("`parse` returns no error nodes"),
+            Expr::Variable(var) => text(display(var.name)),
+            Expr::Interpolation(parts) => {
+                let mut out = String::from("\"");
+                for part in parts {
+                    match part {
+                        InterpPart::Literal(_, span) => out.push_str(&self.src[span.clone()]),
+                        InterpPart::Expr(e) => {
+                            let doc = self.expr(e);
+                            out.push_str("${");
+                            out.push_str(&render(&doc, usize::MAX));
+                            out.push('}');
+                        }
+                    }
+                }
+                out.push('"');
+                text(out)
+            }
+            Expr::List(items) => {
+                let entries = items.iter().map(Entry::Expr).collect();
+                self.delimited("[", entries, span.end, "]", false)
+            }
+            Expr::Block(defs) => {
+                let entries: Vec<_> = defs.iter().map(Entry::Bind).collect();
+                self.record(entries, span)
+            }
+            Expr::ImmediateBlock(stmt) => {
+                let entries = statement_entries(&stmt.definitions, Some(&stmt.body));
+                self.record(entries, span)
+            }
+            Expr::Function(params, body) => {
+                let params: Vec<_> = params.iter().map(|(p, _)| display(*p)).collect();
+                let head = text(format!("({}) =>", params.join(", ")));
+                let body_doc = self.expr(body);
+                match body.0 {
+                    Expr::Block(_)
+                    | Expr::ImmediateBlock(_)
+                    | Expr::List(_)
+                    | Expr::Call(..)
+                    | Expr::Match { .. } => concat(vec![head, text(" "), body_doc]),
+                    _ => group(concat(vec![head, nest(concat(vec![Doc::Line, body_doc]))])),
+                }
+            }
+            Expr::If { .. } => self.if_chain(expr),
+            Expr::Binary(op, _, _) => {
+                // A left-nested run of the same precedence, as one group.
+                let mut rhs = Vec::new();
+                let mut lhs = expr;
+                while let (Expr::Binary(o, l, r), _) = lhs {
+                    if precedence_of_op(*o) != precedence_of_op(*op) {
+                        break;
+                    }
+                    rhs.push((*o, &**r));
+                    lhs = l;
+                }
+                let prec = precedence_of_op(*op);
+                let mut parts = vec![self.operand(lhs, prec)];
+                let mut tail = Vec::new();
+                for (o, r) in rhs.into_iter().rev() {
+                    tail.push(Doc::Line);
+                    tail.push(text(format!("{} ", op_str(o))));
+                    tail.push(self.operand(r, prec + 1));
+                }
+                parts.push(nest(concat(tail)));
+                group(concat(parts))
+            }
+            Expr::Unary(op, operand) => {
+                let op = match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "!",
+                };
+                concat(vec![text(op), self.operand(operand, UNARY)])
+            }
+            Expr::Call(callee, args) => {
+                let callee = self.operand(callee, POSTFIX);
+                let entries = args.iter().map(Entry::Expr).collect();
+                concat(vec![callee, self.delimited("(", entries, span.end, ")", false)])
+            }
+            Expr::Access(obj, (field, field_span)) => {
+                let name = display(*field);
+                if is_ident(name) {
+                    concat(vec![self.operand(obj, POSTFIX), text(format!(".{}", name))])
+                } else {
+                    let key = &self.src[field_span.clone()];
+                    concat(vec![self.operand(obj, POSTFIX), text(format!("[{}]", key))])
+                }
+            }
+            Expr::OptAccess(obj, (field, _)) => {
+                concat(vec![self.operand(obj, POSTFIX), text(format!("?.{}", display(*field)))])
+            }
+            Expr::Index(obj, index) => {
+                let obj = self.operand(obj, POSTFIX);
+                concat(vec![obj, text("["), self.expr(index), text("]")])
+            }
+            Expr::Match { scrutinee, arms } => {
+                let head = concat(vec![text("match "), self.expr(scrutinee), text(" ")]);
+                let entries = arms.iter().map(Entry::Arm).collect();
+                concat(vec![head, self.delimited("{", entries, span.end, "}", true)])
+            }
+        }
+    }
+
+    /// `expr` where an operand of precedence `min` is expected, in
+    /// parentheses if it binds more loosely.
+    fn operand(&mut self, expr: &Spanned<Expr>, min: u8) -> Doc {
+        let doc = self.expr(expr);
+        if precedence(&expr.0) < min {
+            concat(vec![text("("), doc, text(")")])
+        } else {
+            doc
+        }
+    }
+
+    /// `if a then b else if c then d else e`, with each `else` starting a
+    /// line when broken.
+    fn if_chain(&mut self, expr: &Spanned<Expr>) -> Doc {
+        let mut parts = Vec::new();
+        let mut expr = expr;
+        while let Expr::If { cond, cons, alt } = &expr.0 {
+            parts.push(text(if parts.is_empty() { "if " } else { "else if " }));
+            parts.push(self.expr(cond));
+            parts.push(text(" then"));
+            let cons = self.expr(cons);
+            parts.push(group(nest(concat(vec![Doc::Line, cons]))));
+            parts.push(Doc::Line);
+            expr = alt;
+        }
+        parts.push(text("else"));
+        let alt = self.expr(expr);
+        parts.push(group(nest(concat(vec![Doc::Line, alt]))));
+        group(concat(parts))
+    }
+
+    fn pattern(&self, pattern: &Spanned<Pattern>) -> Doc {
+        let (p, span) = pattern;
+        match p {
+            Pattern::Wildcard => text("_"),
+            Pattern::Bind((name, _)) => text(display(*name)),
+            Pattern::Number(_) | Pattern::String(_) => text(&self.src[span.clone()]),
+            Pattern::Bool(b) => text(b.to_string()),
+            Pattern::Null => text("null"),
+            Pattern::List { items, rest } => {
+                let mut parts: Vec<Doc> = items.iter().map(|item| self.pattern(item)).collect();
+                match rest {
+                    Some(Some((name, _))) => parts.push(text(format!("...{}", display(*name)))),
+                    Some(None) => parts.push(text("...")),
+                    None => {}
+                }
+                list_of("[", parts, "]")
+            }
+            Pattern::Record(fields) => {
+                let parts = fields
+                    .iter()
+                    .map(|((name, key), sub)| match sub {
+                        Some(sub) => concat(vec![self.key(*name, key), text(": "), self.pattern(sub)]),
+                        None => self.key(*name, key),
+                    })
+                    .collect();
+                list_of("{", parts, "}")
+            }
+        }
+    }
+}
+
+fn list_of(open: &str, parts: Vec<Doc>, close: &str) -> Doc {
+    let mut docs = vec![text(open)];
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            docs.push(text(", "));
+        }
+        docs.push(part);
+    }
+    docs.push(text(close));
+    concat(docs)
+}
+
+/// The definitions and body of a program or block, in source order.
+fn statement_entries<'a>(defs: &'a [Bind], body: Option<&'a Spanned<Expr>>) -> Vec<Entry<'a>> {
+    let mut entries: Vec<Entry> = defs.iter().map(Entry::Bind).collect();
+    if let Some(body) = body {
+        let at = defs.iter().take_while(|((_, key), _)| key.start < body.1.start).count();
+        entries.insert(at, Entry::Expr(body));
+    }
+    entries
+}
+
+const UNARY: u8 = 7;
+const POSTFIX: u8 = 8;
+
+fn precedence_of_op(op: BinOp) -> u8 {
+    match op {
+        BinOp::Coalesce => 1,
+        BinOp::Or => 2,
+        BinOp::And => 3,
+        BinOp::Eq | BinOp::Ne | BinOp::Gt | BinOp::Lt | BinOp::Ge | BinOp::Le => 4,
+        BinOp::Add | BinOp::Sub => 5,
+        BinOp::Mul | BinOp::Div | BinOp::Mod => 6,
+    }
+}
+
+/// How tightly `e` binds. `if` and functions extend as far right as they
+/// can, so they are parenthesized as any operand.
+fn precedence(e: &Expr) -> u8 {
+    match e {
+        Expr::If { .. } | Expr::Function(..) => 0,
+        Expr::Binary(op, _, _) => precedence_of_op(*op),
+        Expr::Unary(..) => UNARY,
+        Expr::Call(..) | Expr::Access(..) | Expr::OptAccess(..) | Expr::Index(..) => POSTFIX,
+        _ => POSTFIX + 1,
+    }
+}
+
+fn op_str(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Gt => ">",
+        BinOp::Lt => "<",
+        BinOp::Ge => ">=",
+        BinOp::Le => "<=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+        BinOp::Coalesce => "??",
+    }
+}
+
+/// Whether `s` can be written as a bare identifier.
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !matches!(s, "if" | "then" | "else" | "null" | "true" | "false" | "match")
+}
+
+// Structural equality, ignoring spans and resolution.
+
+fn same_stmt(a: &Statement, b: &Statement) -> bool {
+    same_binds(&a.definitions, &b.definitions) && same_expr(&a.body, &b.body)
+}
+
+fn same_binds(a: &[Bind], b: &[Bind]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(((x, _), v), ((y, _), w))| x == y && same_expr(v, w))
+}
+
+fn same_exprs(a: &[Spanned<Expr>], b: &[Spanned<Expr>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| same_expr(x, y))
+}
+
+fn same_expr((a, _): &Spanned<Expr>, (b, _): &Spanned<Expr>) -> bool {
+    match (a, b) {
+        (Expr::Number(x), Expr::Number(y)) => x == y,
+        (Expr::String(x), Expr::String(y)) => x == y,
+        (Expr::Interpolation(x), Expr::Interpolation(y)) => {
+            x.len() == y.len()
+                && x.iter().zip(y).all(|pair| match pair {
+                    (InterpPart::Literal(s, _), InterpPart::Literal(t, _)) => s == t,
+                    (InterpPart::Expr(e), InterpPart::Expr(f)) => same_expr(e, f),
+                    _ => false,
+                })
+        }
+        (Expr::Variable(x), Expr::Variable(y)) => x.name == y.name,
+        (Expr::Null, Expr::Null) | (Expr::Error, Expr::Error) => true,
+        (Expr::Bool(x), Expr::Bool(y)) => x == y,
+        (Expr::List(x), Expr::List(y)) => same_exprs(x, y),
+        (Expr::Function(p, x), Expr::Function(q, y)) => {
+            p.len() == q.len() && p.iter().zip(q).all(|(a, b)| a.0 == b.0) && same_expr(x, y)
+        }
+        (Expr::Block(x), Expr::Block(y)) => same_binds(x, y),
+        (Expr::ImmediateBlock(x), Expr::ImmediateBlock(y)) => same_stmt(x, y),
+        (
+            Expr::If { cond, cons, alt },
+            Expr::If {
+                cond: c,
+                cons: d,
+                alt: e,
+            },
+        ) => same_expr(cond, c) && same_expr(cons, d) && same_expr(alt, e),
+        (Expr::Binary(o, l, r), Expr::Binary(p, m, s)) => {
+            std::mem::discriminant(o) == std::mem::discriminant(p) && same_expr(l, m) && same_expr(r, s)
+        }
+        (Expr::Unary(o, x), Expr::Unary(p, y)) => {
+            std::mem::discriminant(o) == std::mem::discriminant(p) && same_expr(x, y)
+        }
+        (Expr::Call(f, x), Expr::Call(g, y)) => same_expr(f, g) && same_exprs(x, y),
+        (Expr::Access(x, (f, _)), Expr::Access(y, (g, _)))
+        | (Expr::OptAccess(x, (f, _)), Expr::OptAccess(y, (g, _))) => f == g && same_expr(x, y),
+        (Expr::Index(x, i), Expr::Index(y, j)) => same_expr(x, y) && same_expr(i, j),
+        (
+            Expr::Match { scrutinee, arms },
+            Expr::Match {
+                scrutinee: s,
+                arms: a,
+            },
+        ) => {
+            same_expr(scrutinee, s)
+                && arms.len() == a.len()
+                && arms.iter().zip(a).all(|(x, y)| {
+                    same_pattern(&x.pattern, &y.pattern)
+                        && match (&x.guard, &y.guard) {
+                            (Some(g), Some(h)) => same_expr(g, h),
+                            (None, None) => true,
+                            _ => false,
+                        }
+                        && same_expr(&x.body, &y.body)
+                })
+        }
+        _ => false,
+    }
+}
+
+fn same_pattern((a, _): &Spanned<Pattern>, (b, _): &Spanned<Pattern>) -> bool {
+    match (a, b) {
+        (Pattern::Wildcard, Pattern::Wildcard) | (Pattern::Null, Pattern::Null) => true,
+        (Pattern::Bind((x, _)), Pattern::Bind((y, _))) => x == y,
+        (Pattern::Number(x), Pattern::Number(y)) => x == y,
+        (Pattern::String(x), Pattern::String(y)) => x == y,
+        (Pattern::Bool(x), Pattern::Bool(y)) => x == y,
+        (Pattern::List { items: x, rest: r }, Pattern::List { items: y, rest: s }) => {
+            x.len() == y.len()
+                && x.iter().zip(y).all(|(p, q)| same_pattern(p, q))
+                && match (r, s) {
+                    (Some(Some((a, _))), Some(Some((b, _)))) => a == b,
+                    (Some(None), Some(None)) | (None, None) => true,
+                    _ => false,
+                }
+        }
+        (Pattern::Record(x), Pattern::Record(y)) => {
+            x.len() == y.len()
+                && x.iter().zip(y).all(|(((a, _), p), ((b, _), q))| {
+                    a == b
+                        && match (p, q) {
+                            (Some(p), Some(q)) => same_pattern(p, q),
+                            (None, None) => true,
+                            _ => false,
+                        }
+                })
+        }
+        _ => false,
+    }
+}
@@ -1,11 +1,12 @@
 use crate::ast::*;
-use crate::diag::Diagnostic;
+use crate::diag::{Diagnostic, StackFrame};
 use crate::lexer::Span;
 use crate::symbol::{display, intern, Symbol};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fmt::{self, Write as _};
-use std::rc::Rc;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
 
 pub type EvalResult = Result<Value, Diagnostic>;
 
@@ -23,13 +24,17 @@ pub enum Value {
 #[derive(Clone)]
 pub enum Function {
     Native {
-        params: Vec<Symbol>,
+        params: Rc<[Symbol]>,
         body: Rc<Spanned<Expr>>,
         env: Env,
     },
     Foreign(Rc<dyn Fn(Vec<Value>, &Span) -> EvalResult>),
+    /// A foreign function that calls spctr functions; see `Driver`.
+    Driven(Rc<StartDriver>),
 }
 
+pub type StartDriver = dyn Fn(Vec<Value>, &Span) -> Result<Box<dyn Driver>, Diagnostic>;
+
 #[derive(Clone, Default)]
 pub struct Env(Option<Rc<Frame>>);
 
@@ -39,34 +44,158 @@ pub struct Frame {
     pub parent: Env,
 }
 
+/// What a dropped frame (its binds and parent) or list (its items) leaves
+/// to drop; see `defer_drop`.
+type Orphans = (Vec<Rc<RefCell<BindState>>>, Env, Vec<Value>);
+
+thread_local! {
+    /// Children of the frames and lists being dropped; see `defer_drop`.
+    static DROP_QUEUE: RefCell<Option<Vec<Orphans>>> = const { RefCell::new(None) };
+}
+
+/// Drop what a frame or list held. Dropping a deep structure (nested records
+/// or lists, a long env chain, closures capturing one) would otherwise
+/// recurse once per level on the Rust stack. The outermost drop drains a
+/// queue; frames and lists freed meanwhile just hand it their children.
+fn defer_drop(orphans: Orphans) {
+    let owner = DROP_QUEUE.try_with(|q| {
+        let mut q = q.borrow_mut();
+        match q.as_mut() {
+            Some(pending) => {
+                pending.push(orphans);
+                None
+            }
+            None => {
+                *q = Some(Vec::new());
+                Some(orphans)
+            }
+        }
+    });
+    // Thread-local storage is gone during thread teardown: drop in place.
+    let Ok(Some(orphans)) = owner else { return };
+    drop(orphans);
+    while let Some(next) = DROP_QUEUE.with(|q| q.borrow_mut().as_mut().and_then(Vec::pop)) {
+        drop(next);
+    }
+    DROP_QUEUE.with(|q| *q.borrow_mut() = None);
+}
+
+/// Whether dropping `v` would free a frame or list, which `defer_drop` must
+/// then see to. Most values (numbers, a call's arguments shared with the
+/// caller) free neither and skip the queue.
+fn frees(v: &Value) -> bool {
+    match v {
+        Value::List(items) => Rc::strong_count(items) == 1 && !items.is_empty(),
+        Value::Block(frame) => Rc::strong_count(frame) == 1,
+        Value::Function(Function::Native { env, .. }) => env.0.as_ref().is_some_and(|f| Rc::strong_count(f) == 1),
+        _ => false,
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        let bind_frees = |b: &Rc<RefCell<BindState>>| {
+            Rc::strong_count(b) == 1 && matches!(&*b.borrow(), BindState::Done(v) if frees(v))
+        };
+        let parent_frees = self.parent.0.as_ref().is_some_and(|p| Rc::strong_count(p) == 1);
+        if !parent_frees && !self.binds.iter().any(bind_frees) {
+            return;
+        }
+        defer_drop((
+            std::mem::take(&mut self.binds),
+            std::mem::take(&mut self.parent),
+            Vec::new(),
+        ));
+    }
+}
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        // Only the last reference to a list frees its items.
+        if let Value::List(items) = self {
+            if let Some(items) = Rc::get_mut(items).filter(|items| items.iter().any(frees)) {
+                defer_drop((Vec::new(), Env::empty(), std::mem::take(items)));
+            }
+        }
+    }
+}
+
 pub enum BindState {
     Lazy(Rc<Spanned<Expr>>),
     InProgress,
     Done(Value),
 }
 
+impl Frame {
+    /// A record's field names with their slots, in declaration order, or
+    /// `None` for frames that are not records (e.g. a program's top level).
+    pub fn fields(&self) -> Option<Vec<(Symbol, u32)>> {
+        let mut fields: Vec<(Symbol, u32)> = self.names.as_ref()?.iter().map(|(k, v)| (*k, *v)).collect();
+        fields.sort_by_key(|(_, slot)| *slot);
+        Some(fields)
+    }
+
+    /// The value in `slot`, evaluating it first if it is still lazy.
+    pub fn get(self: &Rc<Self>, slot: u32) -> EvalResult {
+        force(&Env(Some(self.clone())), &self.binds[slot as usize], &(0..0))
+    }
+
+    /// Span of the expression in `slot`, while it has not been forced yet.
+    pub fn span(&self, slot: u32) -> Option<Span> {
+        match &*self.binds[slot as usize].borrow() {
+            BindState::Lazy(expr) => Some(expr.1.clone()),
+            _ => None,
+        }
+    }
+}
+
 impl Env {
     pub fn empty() -> Self {
         Env(None)
     }
 
     fn parent_at(&self, depth: u32) -> Env {
-        let mut cur = self.clone();
+        let mut cur = self;
         for _ in 0..depth {
-            let next = cur
-                .0
-                .as_ref()
-                .expect("env chain shorter than resolved depth")
-                .parent
-                .clone();
-            cur = next;
+            cur = &cur.0.as_ref().expect("env chain shorter than resolved depth").parent;
         }
-        cur
+        cur.clone()
     }
 }
 
 pub const ROOT_NAMES: [&str; 4] = ["List", "String", "Number", "import"];
 
+/// A root binding supplied by the embedder (see `engine::Engine`). Host
+/// roots come after the builtins, so builtin slots never move.
+#[derive(Clone)]
+pub struct HostRoot {
+    pub name: Symbol,
+    pub scheme: crate::types::Scheme,
+    pub value: Value,
+}
+
+thread_local! {
+    static HOST_ROOTS: RefCell<Rc<Vec<HostRoot>>> = RefCell::new(Rc::new(Vec::new()));
+}
+
+/// Install the host roots seen by every later run on this thread, and
+/// return the ones they replace.
+pub fn set_host_roots(roots: Rc<Vec<HostRoot>>) -> Rc<Vec<HostRoot>> {
+    HOST_ROOTS.with(|h| std::mem::replace(&mut *h.borrow_mut(), roots))
+}
+
+fn host_roots() -> Rc<Vec<HostRoot>> {
+    HOST_ROOTS.with(|h| h.borrow().clone())
+}
+
+/// `ROOT_NAMES` followed by the installed host roots' names.
+pub fn root_names() -> Vec<&'static str> {
+    let mut names = ROOT_NAMES.to_vec();
+    names.extend(host_roots().iter().map(|h| display(h.name)));
+    names
+}
+
+/// Types of the builtin roots, in `ROOT_NAMES` order.
 pub fn root_types() -> Vec<crate::types::Type> {
     vec![
         crate::stdlib::list::ty(),
@@ -76,17 +205,34 @@ pub fn root_types() -> Vec<crate::types::Type> {
     ]
 }
 
+/// Schemes of every root in `root_names` order, host roots included.
+pub fn root_schemes() -> Vec<crate::types::Scheme> {
+    let mut schemes: Vec<_> = root_types()
+        .into_iter()
+        .map(crate::types::Scheme::mono)
+        .collect();
+    schemes.extend(host_roots().iter().map(|h| h.scheme.clone()));
+    schemes
+}
+
 pub fn run(ast: &Statement) -> EvalResult {
-    let env = build_root_env();
+    run_in(ast, None)
+}
+
+/// Like `run`, for a program that is itself the module `module`: relative
+/// imports are resolved from it.
+pub fn run_in(ast: &Statement, module: Option<PathBuf>) -> EvalResult {
+    crate::stdlib::imports::clear_cache(module.clone());
+    let env = build_root_env(module);
     interpret_statement(ast, &env)
 }
 
-/// Like `run`, but eagerly forces all top-level definitions before evaluating
-/// the body. Used by `import` so that side-effecting computations dependent on
-/// the current source directory (such as nested imports) execute while the
-/// imported file's directory is still set.
-pub fn run_eager(ast: &Statement) -> EvalResult {
-    let env = build_root_env();
+/// Evaluate the imported module `module`, eagerly forcing all top-level
+/// definitions before the body so their errors surface at the import.
+/// Relative imports inside it are resolved from `module`.
+pub fn run_eager(ast: &Statement, module: PathBuf) -> EvalResult {
+    let env = build_root_env(Some(module.clone()));
+    register_module(&env, module);
     let frame = make_frame(&ast.definitions, &env, false);
     let new_env = Env(Some(Rc::new(frame)));
     for (i, (_, body)) in ast.definitions.iter().enumerate() {
@@ -96,7 +242,39 @@ pub fn run_eager(ast: &Statement) -> EvalResult {
     interpret(&ast.body, &new_env)
 }
 
-fn build_root_env() -> Env {
+thread_local! {
+    /// The root environments of the modules imported on this thread, with
+    /// their paths; see `module_of`.
+    static MODULE_ROOTS: RefCell<Vec<(Weak<Frame>, PathBuf)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Remember that `env` is the root environment of `module`.
+fn register_module(env: &Env, module: PathBuf) {
+    let Some(root) = &env.0 else { return };
+    MODULE_ROOTS.with(|roots| {
+        let mut roots = roots.borrow_mut();
+        roots.retain(|(frame, _)| frame.strong_count() > 0);
+        roots.push((Rc::downgrade(root), module));
+    });
+}
+
+/// The imported module whose code runs in `env`, or `None` for the
+/// program itself.
+fn module_of(env: &Env) -> Option<PathBuf> {
+    let mut frame = env.0.as_ref()?;
+    while let Some(parent) = &frame.parent.0 {
+        frame = parent;
+    }
+    MODULE_ROOTS.with(|roots| {
+        let roots = roots.borrow();
+        let (_, module) = roots
+            .iter()
+            .find(|(root, _)| std::ptr::eq(root.as_ptr(), Rc::as_ptr(frame)))?;
+        Some(module.clone())
+    })
+}
+
+fn build_root_env(module: Option<PathBuf>) -> Env {
     let mut binds: Vec<Rc<RefCell<BindState>>> = Vec::with_capacity(ROOT_NAMES.len());
 
     binds.push(Rc::new(RefCell::new(BindState::Done(
@@ -108,9 +286,12 @@ fn build_root_env() -> Env {
     binds.push(Rc::new(RefCell::new(BindState::Done(
         crate::stdlib::number::module(),
     ))));
-    binds.push(Rc::new(RefCell::new(BindState::Done(Value::Function(
-        Function::Foreign(Rc::new(crate::stdlib::imports::import)),
-    )))));
+    binds.push(Rc::new(RefCell::new(BindState::Done(
+        crate::stdlib::imports::importer(module),
+    ))));
+    for host in host_roots().iter() {
+        binds.push(Rc::new(RefCell::new(BindState::Done(host.value.clone()))));
+    }
 
     Env(Some(Rc::new(Frame {
         binds,
@@ -119,37 +300,49 @@ fn build_root_env() -> Env {
     })))
 }
 
-fn force(env: &Env, bind: &Rc<RefCell<BindState>>, span: &Span) -> EvalResult {
-    {
-        let state = bind.borrow();
-        match &*state {
-            BindState::Done(v) => return Ok(v.clone()),
-            BindState::InProgress => {
-                return Err(Diagnostic::new(
-                    span.clone(),
-                    "cyclic binding",
-                    "this binding refers to itself during evaluation",
-                ))
-            }
-            BindState::Lazy(_) => {}
-        }
-    }
+/// Default for `set_max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 1_000_000;
 
-    let expr = match std::mem::replace(&mut *bind.borrow_mut(), BindState::InProgress) {
-        BindState::Lazy(e) => e,
-        _ => unreachable!(),
-    };
+thread_local! {
+    static MAX_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_DEPTH) };
+    /// Pending function calls and forced bindings across every `interpret`
+    /// running on this thread (imports and `force` nest one machine inside
+    /// another, and they share one budget).
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
 
-    match interpret(&expr, env) {
-        Ok(v) => {
-            *bind.borrow_mut() = BindState::Done(v.clone());
-            Ok(v)
-        }
-        Err(err) => {
-            *bind.borrow_mut() = BindState::Lazy(expr);
-            Err(err)
-        }
-    }
+/// Limit the number of nested (non-tail) function calls and lazy binding
+/// evaluations on this thread. Exceeding it is a runtime `Diagnostic`, not a
+/// crash: the interpreter keeps its continuations on the heap, so without
+/// the limit a runaway recursion would only stop when memory runs out.
+pub fn set_max_depth(depth: usize) {
+    MAX_DEPTH.with(|m| m.set(depth));
+}
+
+/// The limit `set_max_depth` installed on this thread.
+pub fn max_depth() -> usize {
+    MAX_DEPTH.with(|m| m.get())
+}
+
+/// A foreign function that calls back into spctr code (`List.map` and
+/// friends). Rather than recursing into `interpret` itself, it hands the
+/// interpreter one call at a time, so callbacks don't grow the Rust stack
+/// either.
+pub trait Driver {
+    /// Called first with `None`, then with the result of each call it asked
+    /// for, until it returns `Resume::Done`.
+    fn resume(&mut self, last: Option<Value>, span: &Span) -> Result<Resume, Diagnostic>;
+}
+
+pub enum Resume {
+    Call(Value, Vec<Value>),
+    Done(Value),
+}
+
+fn force(env: &Env, bind: &Rc<RefCell<BindState>>, span: &Span) -> EvalResult {
+    let mut m = Machine::default();
+    let step = m.force(env.clone(), bind.clone(), span);
+    m.finish(step)
 }
 
 pub fn interpret_statement(stmt: &Statement, env: &Env) -> EvalResult {
@@ -178,241 +371,1000 @@ fn make_frame(defs: &[Bind], parent: &Env, with_names: bool) -> Frame {
     }
 }
 
-// Writes to `current_function` below are load-bearing — they keep the
-// `Rc<Spanned<Expr>>` body alive for the `cur` raw pointer — even though no
-// Rust code subsequently reads the Option directly.
-#[allow(unused_assignments, unused_variables)]
 pub fn interpret(expr: &Spanned<Expr>, env: &Env) -> EvalResult {
-    // Tail-call-optimizing loop. Function calls, `if` branches, and
-    // `ImmediateBlock` bodies are all tail-position transitions: instead of
-    // recursing back into `interpret` (which would grow the Rust stack), we
-    // update `cur` to point at the next expression to evaluate and loop.
-    //
-    // Non-tail sub-expressions (Call arguments, Binary operands, List items,
-    // …) are handled by `interpret_value` below, which still recurses
-    // through `interpret` for its children — but Rust stack growth there
-    // is bounded by *expression* depth, not by *call-chain* depth.
-    //
-    // `cur` is a raw pointer so the loop can rebind it across iterations
-    // without fighting the borrow checker. The pointer is always valid:
-    //
-    // - On entry, it points at the caller's `expr`, which is borrowed for
-    //   the entire `interpret` call.
-    // - On a tail `Call`, we move the callee `Value` (which owns the
-    //   `Rc<Spanned<Expr>>` body) into `current_function`, then point `cur`
-    //   at the body inside that Rc. The previous `current_function`'s body
-    //   is dropped only after `cur` has moved off it.
-    // - On a tail `If` / `ImmediateBlock`, `cur` is redirected at a
-    //   sub-expression of the current node, which lives inside the same
-    //   anchor.
-    let mut current_function: Option<Value> = None;
-    let mut current_env: Env = env.clone();
-    let mut cur: *const Spanned<Expr> = expr;
-    loop {
-        // SAFETY: see the comment above — `cur` is anchored either by the
-        // caller's `expr` borrow or by `current_function`'s held Rc.
-        let e: &Spanned<Expr> = unsafe { &*cur };
-        match &e.0 {
-            Expr::Call(callee, args) => {
-                let callee_val = interpret(callee, &current_env)?;
-                let mut arg_vals: Vec<Value> = Vec::with_capacity(args.len());
-                for arg in args {
-                    arg_vals.push(interpret(arg, &current_env)?);
+    let mut m = Machine {
+        base: Some(env.clone()),
+        ..Machine::default()
+    };
+    m.finish(Ok(Step::Eval(expr, env.clone())))
+}
+
+// The evaluator is a loop over an explicit continuation stack: evaluating a
+// node either produces its value right away or pushes a `Kont` saying what
+// to do with a sub-expression's value and moves on to that sub-expression.
+// Recursion in spctr code therefore grows `Machine::stack` on the heap, never
+// the Rust stack.
+//
+// Tail calls keep the stack flat: a call whose caller has nothing left to do
+// (the top of the stack is the caller's own `Kont::Body`) replaces that entry
+// instead of pushing a new one. `if` branches, `match` arms, `ImmediateBlock`
+// bodies and the right side of `&&` / `||` / `??` are evaluated without a
+// continuation of their own, so calls there are tail calls too.
+//
+// Nodes are raw pointers so continuations can refer into function bodies
+// without a lifetime. Every pointer stays valid while it is on the stack:
+//
+// - A node of the root expression is borrowed by `interpret`'s caller.
+// - A node inside a function body lies above that call's `Kont::Body`, which
+//   holds the body's `Rc`, and a tail call only replaces the `Body` once
+//   nothing above it refers into the old body.
+// - A node inside a lazy binding lies above the `Kont::Force` holding its
+//   `Rc`.
+type Node = *const Spanned<Expr>;
+
+fn at<'a>(node: Node) -> &'a Spanned<Expr> {
+    // SAFETY: see above — the node is anchored while it is in use.
+    unsafe { &*node }
+}
+
+enum Step {
+    Eval(Node, Env),
+    Return(Value),
+}
+
+enum Kont {
+    /// The body of a running (non-tail) call.
+    Body(Rc<Spanned<Expr>>),
+    /// A lazy binding being evaluated in the environment it is defined in;
+    /// it becomes `Done` on return and is reset to `Lazy` if evaluation
+    /// fails.
+    Force(Rc<RefCell<BindState>>, Rc<Spanned<Expr>>, Env),
+    /// `Call` node: the callee was evaluated.
+    Callee(Node, Env),
+    /// `Call` node with an `obj?.method` callee: `obj` was evaluated.
+    OptCallee(Node, Env),
+    /// `Call` node: `done.len() - 1` was the last argument evaluated.
+    Args {
+        node: Node,
+        env: Env,
+        callee: Value,
+        done: Vec<Value>,
+    },
+    IfCond(Node, Env),
+    Scrutinee(Node, Env),
+    /// `Match` node: the guard of arm `arm` was evaluated.
+    Guard {
+        node: Node,
+        env: Env,
+        value: Value,
+        arm: usize,
+        arm_env: Env,
+    },
+    /// `Interpolation` node: part `part` was evaluated.
+    Interp {
+        node: Node,
+        env: Env,
+        part: usize,
+        out: String,
+    },
+    ListItem {
+        node: Node,
+        env: Env,
+        items: Vec<Value>,
+    },
+    BinLeft(Node, Env),
+    BinRight(Node, Value),
+    Unary(Node),
+    Access(Node),
+    OptAccess(Node),
+    IndexArr(Node, Env),
+    IndexIdx(Node, Value),
+    /// A `Driver` waiting for the result of the call it asked for, made by
+    /// the builtin `Callee` called at `Span`.
+    Driven(Box<dyn Driver>, Span, Callee),
+}
+
+/// The callee of a call as written, for backtraces.
+#[derive(Clone, Copy)]
+enum Callee {
+    /// `f(...)`
+    Name(Symbol),
+    /// `m.f(...)` or `m?.f(...)`
+    Member(Symbol, Symbol),
+    /// `(...).f(...)`
+    Field(Symbol),
+    /// A function a builtin called, like the one passed to `List.map`.
+    Callback(Option<Symbol>, Symbol),
+    Other,
+}
+
+impl Callee {
+    fn of(expr: &Spanned<Expr>) -> Self {
+        match &expr.0 {
+            Expr::Variable(var) => Callee::Name(var.name),
+            Expr::Access(obj, (name, _)) | Expr::OptAccess(obj, (name, _)) => match &obj.0 {
+                Expr::Variable(var) => Callee::Member(var.name, *name),
+                _ => Callee::Field(*name),
+            },
+            _ => Callee::Other,
+        }
+    }
+
+    /// A function that the builtin `self` calls.
+    fn callback(self) -> Self {
+        match self {
+            Callee::Name(f) | Callee::Field(f) => Callee::Callback(None, f),
+            Callee::Member(m, f) => Callee::Callback(Some(m), f),
+            Callee::Callback(..) | Callee::Other => Callee::Other,
+        }
+    }
+
+    fn name(self) -> String {
+        match self {
+            Callee::Name(f) => display(f).to_string(),
+            Callee::Member(m, f) => format!("{}.{}", display(m), display(f)),
+            Callee::Field(f) => format!("(...).{}", display(f)),
+            Callee::Callback(None, f) => format!("<function passed to {}>", display(f)),
+            Callee::Callback(Some(m), f) => {
+                format!("<function passed to {}.{}>", display(m), display(f))
+            }
+            Callee::Other => "<function>".to_string(),
+        }
+    }
+}
+
+/// The call a `Kont::Body` is running, for backtraces.
+struct Call {
+    callee: Callee,
+    span: Span,
+    /// The environment of the function called: the calls in its body are
+    /// in its module.
+    env: Env,
+    /// The last of the tail calls that replaced this frame's function.
+    tail: Option<TailCall>,
+}
+
+struct TailCall {
+    callee: Callee,
+    span: Span,
+    /// The environment of the function that made the call.
+    from: Env,
+    /// Tail calls between the frame's own call and this one.
+    elided: usize,
+}
+
+#[derive(Default)]
+struct Machine {
+    stack: Vec<Kont>,
+    /// The call of each `Kont::Body` on `stack`, in the same order.
+    calls: Vec<Call>,
+    /// The environment the code the machine started with runs in, if the
+    /// machine knows it.
+    base: Option<Env>,
+}
+
+impl Machine {
+    fn finish(&mut self, step: Result<Step, Diagnostic>) -> EvalResult {
+        let result = self.run(step).map_err(|d| self.trace(d));
+        if result.is_err() {
+            self.unwind();
+        }
+        result
+    }
+
+    /// Add the calls on the stack to `diag`'s backtrace. Machines nest, so
+    /// the innermost one to see an error adds its calls first; that one,
+    /// knowing which code failed, also points `diag` into its module.
+    fn trace(&self, mut diag: Diagnostic) -> Diagnostic {
+        // Where the code running at each point of the stack comes from.
+        let mut env = self.base.clone();
+        let mut calls = self.calls.iter();
+        let mut frames = Vec::new();
+        for k in &self.stack {
+            match k {
+                Kont::Body(_) => {
+                    let call = calls.next().expect("a call per body");
+                    frames.push(StackFrame {
+                        name: call.callee.name(),
+                        span: call.span.clone(),
+                        file: env.as_ref().and_then(module_of),
+                        elided: 0,
+                    });
+                    if let Some(tail) = &call.tail {
+                        frames.push(StackFrame {
+                            name: tail.callee.name(),
+                            span: tail.span.clone(),
+                            file: module_of(&tail.from),
+                            elided: tail.elided,
+                        });
+                    }
+                    env = Some(call.env.clone());
+                }
+                Kont::Force(_, _, at) => env = Some(at.clone()),
+                _ => {}
+            }
+        }
+        frames.reverse();
+        match &mut diag.details.trace {
+            Some(trace) => trace.extend(frames),
+            None if env.is_some() || !frames.is_empty() => {
+                if diag.details.file.is_none() {
+                    diag.details.file = env.as_ref().and_then(module_of);
                 }
-                let (next_ptr, next_env) = match &callee_val {
-                    Value::Function(Function::Native {
-                        params,
-                        body,
-                        env: func_env,
-                    }) => {
-                        if params.len() != arg_vals.len() {
-                            return Err(Diagnostic::new(
-                                e.1.clone(),
-                                format!(
-                                    "expected {} arguments, got {}",
-                                    params.len(),
-                                    arg_vals.len()
-                                ),
-                                "argument count mismatch",
-                            ));
+                diag.details.trace = Some(frames);
+            }
+            None => {}
+        }
+        diag
+    }
+
+    fn run(&mut self, step: Result<Step, Diagnostic>) -> EvalResult {
+        let mut step = step?;
+        loop {
+            step = match step {
+                Step::Eval(node, env) => self.eval(node, env)?,
+                Step::Return(v) => match self.stack.pop() {
+                    None => return Ok(v),
+                    Some(k) => self.resume(k, v)?,
+                },
+            };
+        }
+    }
+
+    /// Drop every pending continuation after an error, putting bindings that
+    /// were being forced back to `Lazy` so a later access retries them.
+    fn unwind(&mut self) {
+        self.calls.clear();
+        while let Some(k) = self.stack.pop() {
+            match k {
+                Kont::Body(_) => leave(),
+                Kont::Force(bind, expr, _) => {
+                    *bind.borrow_mut() = BindState::Lazy(expr);
+                    leave();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn eval(&mut self, node: Node, env: Env) -> Result<Step, Diagnostic> {
+        let expr = at(node);
+        let span = &expr.1;
+        Ok(match &expr.0 {
+            Expr::Number(n) => Step::Return(Value::Number(*n)),
+            Expr::String(s) => Step::Return(Value::String(s.clone())),
+            Expr::Null => Step::Return(Value::Null),
+            Expr::Bool(b) => Step::Return(Value::Bool(*b)),
+            Expr::Error => {
+                return Err(Diagnostic::new(
+                    span.clone(),
+                    "cannot evaluate a syntax error",
+                    "skipped by the parser",
+                )
+                .with_code("E0002"))
+            }
+            Expr::Interpolation(_) => return self.interp_parts(node, env, 0, String::new()),
+            Expr::Variable(var) => {
+                let bref = var.resolved.get().ok_or_else(|| {
+                    Diagnostic::new(
+                        span.clone(),
+                        format!("unresolved variable: {}", display(var.name)),
+                        "resolver did not run",
+                    )
+                })?;
+                let env_at_def = env.parent_at(bref.depth);
+                let bind = env_at_def
+                    .0
+                    .as_ref()
+                    .expect("frame missing")
+                    .binds
+                    .get(bref.slot as usize)
+                    .expect("slot out of range")
+                    .clone();
+                return self.force(env_at_def, bind, span);
+            }
+            Expr::List(items) => match items.first() {
+                None => Step::Return(Value::List(Rc::new(Vec::new()))),
+                Some(first) => {
+                    self.stack.push(Kont::ListItem {
+                        node,
+                        env: env.clone(),
+                        items: Vec::with_capacity(items.len()),
+                    });
+                    Step::Eval(first, env)
+                }
+            },
+            Expr::Function(params, body) => {
+                let param_names = params.iter().map(|(n, _)| *n).collect();
+                Step::Return(Value::Function(Function::Native {
+                    params: param_names,
+                    body: Rc::new(body.as_ref().clone()),
+                    env,
+                }))
+            }
+            Expr::Block(defs) => {
+                let frame = make_frame(defs, &env, true);
+                Step::Return(Value::Block(Rc::new(frame)))
+            }
+            Expr::ImmediateBlock(stmt) => {
+                let frame = make_frame(&stmt.definitions, &env, false);
+                Step::Eval(&stmt.body, Env(Some(Rc::new(frame))))
+            }
+            Expr::If { cond, cons, alt } => match quick(cond, &env) {
+                Some(c) => Step::Eval(if is_truthy(&c) { cons.as_ref() } else { alt.as_ref() }, env),
+                None => {
+                    self.stack.push(Kont::IfCond(node, env.clone()));
+                    Step::Eval(cond.as_ref(), env)
+                }
+            },
+            Expr::Match { scrutinee, .. } => match quick(scrutinee, &env) {
+                Some(v) => return self.select_arm(node, env, v, 0),
+                None => {
+                    self.stack.push(Kont::Scrutinee(node, env.clone()));
+                    Step::Eval(scrutinee.as_ref(), env)
+                }
+            },
+            Expr::Call(callee, args) => match &callee.0 {
+                // `obj?.method(args)`: a null `obj` skips the whole call.
+                Expr::OptAccess(obj, _) => {
+                    self.stack.push(Kont::OptCallee(node, env.clone()));
+                    Step::Eval(obj.as_ref(), env)
+                }
+                _ => match quick(callee, &env) {
+                    Some(callee_val) => {
+                        let mut done = Vec::with_capacity(args.len());
+                        for arg in args {
+                            match quick(arg, &env) {
+                                Some(v) => done.push(v),
+                                None => break,
+                            }
                         }
-                        let binds: Vec<Rc<RefCell<BindState>>> = arg_vals
-                            .into_iter()
-                            .map(|v| Rc::new(RefCell::new(BindState::Done(v))))
-                            .collect();
-                        let frame = Frame {
-                            binds,
-                            names: None,
-                            parent: func_env.clone(),
-                        };
-                        let next_env = Env(Some(Rc::new(frame)));
-                        let next_ptr = body.as_ref() as *const Spanned<Expr>;
-                        (next_ptr, next_env)
+                        match args.get(done.len()) {
+                            Some(next) => {
+                                self.stack.push(Kont::Args {
+                                    node,
+                                    env: env.clone(),
+                                    callee: callee_val,
+                                    done,
+                                });
+                                Step::Eval(next, env)
+                            }
+                            None => {
+                                let span = span.clone();
+                                return self.apply(callee_val, done, &span, Callee::of(callee));
+                            }
+                        }
+                    }
+                    None => {
+                        self.stack.push(Kont::Callee(node, env.clone()));
+                        Step::Eval(callee.as_ref(), env)
+                    }
+                },
+            },
+            Expr::Binary(_, l, _) => match quick(l, &env) {
+                Some(lv) => return self.resume(Kont::BinLeft(node, env), lv),
+                None => {
+                    self.stack.push(Kont::BinLeft(node, env.clone()));
+                    Step::Eval(l.as_ref(), env)
+                }
+            },
+            Expr::Unary(op, e) => match quick(e, &env) {
+                Some(v) => Step::Return(apply_unaryop(*op, v, span)?),
+                None => {
+                    self.stack.push(Kont::Unary(node));
+                    Step::Eval(e.as_ref(), env)
+                }
+            },
+            Expr::Access(obj, _) => {
+                self.stack.push(Kont::Access(node));
+                Step::Eval(obj.as_ref(), env)
+            }
+            Expr::OptAccess(obj, _) => {
+                self.stack.push(Kont::OptAccess(node));
+                Step::Eval(obj.as_ref(), env)
+            }
+            Expr::Index(arr, _) => {
+                self.stack.push(Kont::IndexArr(node, env.clone()));
+                Step::Eval(arr.as_ref(), env)
+            }
+        })
+    }
+
+    fn resume(&mut self, k: Kont, v: Value) -> Result<Step, Diagnostic> {
+        Ok(match k {
+            Kont::Body(_) => {
+                leave();
+                self.calls.pop();
+                Step::Return(v)
+            }
+            Kont::Force(bind, _, _) => {
+                leave();
+                *bind.borrow_mut() = BindState::Done(v.clone());
+                Step::Return(v)
+            }
+            Kont::Callee(node, env) => {
+                let Expr::Call(callee, args) = &at(node).0 else {
+                    unreachable!()
+                };
+                match args.first() {
+                    None => {
+                        let span = at(node).1.clone();
+                        return self.apply(v, Vec::new(), &span, Callee::of(callee));
+                    }
+                    Some(first) => {
+                        self.stack.push(Kont::Args {
+                            node,
+                            env: env.clone(),
+                            callee: v,
+                            done: Vec::with_capacity(args.len()),
+                        });
+                        Step::Eval(first, env)
+                    }
+                }
+            }
+            Kont::OptCallee(node, env) => {
+                let Expr::Call(callee, _) = &at(node).0 else {
+                    unreachable!()
+                };
+                let Expr::OptAccess(_, (name, name_span)) = &callee.0 else {
+                    unreachable!()
+                };
+                if is_absent(&v, *name) {
+                    return Ok(Step::Return(Value::Null));
+                }
+                self.stack.push(Kont::Callee(node, env));
+                return self.access(v, *name, name_span, &callee.1);
+            }
+            Kont::Args {
+                node,
+                env,
+                callee,
+                mut done,
+            } => {
+                let (Expr::Call(callee_expr, args), span) = at(node) else {
+                    unreachable!()
+                };
+                done.push(v);
+                match args.get(done.len()) {
+                    Some(next) => {
+                        self.stack.push(Kont::Args {
+                            node,
+                            env: env.clone(),
+                            callee,
+                            done,
+                        });
+                        Step::Eval(next, env)
+                    }
+                    None => {
+                        let span = span.clone();
+                        return self.apply(callee, done, &span, Callee::of(callee_expr));
                     }
-                    Value::Function(Function::Foreign(f)) => {
-                        return f(arg_vals, &e.1);
+                }
+            }
+            Kont::IfCond(node, env) => {
+                let Expr::If { cons, alt, .. } = &at(node).0 else {
+                    unreachable!()
+                };
+                Step::Eval(if is_truthy(&v) { cons.as_ref() } else { alt.as_ref() }, env)
+            }
+            Kont::Scrutinee(node, env) => return self.select_arm(node, env, v, 0),
+            Kont::Guard {
+                node,
+                env,
+                value,
+                arm,
+                arm_env,
+            } => {
+                if !is_truthy(&v) {
+                    return self.select_arm(node, env, value, arm + 1);
+                }
+                let Expr::Match { arms, .. } = &at(node).0 else {
+                    unreachable!()
+                };
+                Step::Eval(&arms[arm].body, arm_env)
+            }
+            Kont::Interp {
+                node,
+                env,
+                part,
+                mut out,
+            } => {
+                let Expr::Interpolation(parts) = &at(node).0 else {
+                    unreachable!()
+                };
+                match &v {
+                    Value::String(s) => out.push_str(s),
+                    // Same format as `Value::Display` for numbers.
+                    Value::Number(n) => {
+                        let _ = write!(out, "{}", n);
                     }
+                    Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+                    Value::Null => out.push_str("null"),
                     other => {
+                        let InterpPart::Expr(e) = &parts[part] else {
+                            unreachable!()
+                        };
                         return Err(Diagnostic::new(
                             e.1.clone(),
-                            format!("cannot call {}", other.type_name()),
-                            "not a function",
-                        ));
+                            format!("cannot interpolate {} into a string", other.type_name()),
+                            "interpolation supports number, string, bool, and null",
+                        )
+                        .with_code("E0206"));
                     }
+                }
+                return self.interp_parts(node, env, part + 1, out);
+            }
+            Kont::ListItem {
+                node,
+                env,
+                mut items,
+            } => {
+                let Expr::List(exprs) = &at(node).0 else {
+                    unreachable!()
                 };
-                cur = next_ptr;
-                current_env = next_env;
-                current_function = Some(callee_val);
-            }
-            Expr::If { cond, cons, alt } => {
-                let c = interpret(cond, &current_env)?;
-                cur = if is_truthy(&c) {
-                    cons.as_ref() as *const _
-                } else {
-                    alt.as_ref() as *const _
-                };
+                items.push(v);
+                match exprs.get(items.len()) {
+                    Some(next) => {
+                        self.stack.push(Kont::ListItem {
+                            node,
+                            env: env.clone(),
+                            items,
+                        });
+                        Step::Eval(next, env)
+                    }
+                    None => Step::Return(Value::List(Rc::new(items))),
+                }
             }
-            Expr::ImmediateBlock(stmt) => {
-                let frame = make_frame(&stmt.definitions, &current_env, false);
-                current_env = Env(Some(Rc::new(frame)));
-                cur = &stmt.body as *const _;
-            }
-            _ => return interpret_value(e, &current_env),
-        }
-    }
-}
-
-/// Evaluate every `Expr` variant *except* the three tail-positionable ones
-/// (`Call`, `If`, `ImmediateBlock`) which are handled directly by the loop
-/// in `interpret`. Child sub-expressions still recurse through `interpret`,
-/// which means they get TCO too — only the *non*-tail sub-evaluations land
-/// here.
-fn interpret_value(expr: &Spanned<Expr>, env: &Env) -> EvalResult {
-    let (e, span) = (&expr.0, &expr.1);
-    match e {
-        Expr::Number(n) => Ok(Value::Number(*n)),
-        Expr::String(s) => Ok(Value::String(s.clone())),
-        Expr::Interpolation(parts) => {
-            let mut out = String::new();
-            for p in parts {
-                match p {
-                    crate::ast::InterpPart::Literal(s, _) => out.push_str(s),
-                    crate::ast::InterpPart::Expr(e) => match interpret(e, env)? {
-                        Value::String(s) => out.push_str(&s),
-                        Value::Number(n) => {
-                            use std::fmt::Write;
-                            // Same format as `Value::Display` for numbers.
-                            let _ = write!(out, "{}", n);
-                        }
-                        Value::Bool(b) => out.push_str(if b { "true" } else { "false" }),
-                        Value::Null => out.push_str("null"),
-                        other => {
-                            return Err(Diagnostic::new(
-                                e.1.clone(),
-                                format!(
-                                    "cannot interpolate {} into a string",
-                                    other.type_name()
-                                ),
-                                "interpolation supports number, string, bool, and null",
-                            ));
+            Kont::BinLeft(node, env) => {
+                let (Expr::Binary(op, _, r), span) = at(node) else {
+                    unreachable!()
+                };
+                let short_circuit = match op {
+                    BinOp::And => !is_truthy(&v),
+                    BinOp::Or => is_truthy(&v),
+                    BinOp::Coalesce => !matches!(v, Value::Null),
+                    _ => match quick(r, &env) {
+                        Some(rv) => return Ok(Step::Return(apply_binop(*op, v, rv, span)?)),
+                        None => {
+                            self.stack.push(Kont::BinRight(node, v));
+                            return Ok(Step::Eval(r.as_ref(), env));
                         }
                     },
+                };
+                if short_circuit {
+                    Step::Return(v)
+                } else {
+                    Step::Eval(r.as_ref(), env)
                 }
             }
-            Ok(Value::String(Rc::new(out)))
-        }
-        Expr::Null => Ok(Value::Null),
-        Expr::Bool(b) => Ok(Value::Bool(*b)),
-        Expr::Variable(var) => {
-            let bref = var.resolved.get().ok_or_else(|| {
-                Diagnostic::new(
+            Kont::BinRight(node, lv) => {
+                let (Expr::Binary(op, _, _), span) = at(node) else {
+                    unreachable!()
+                };
+                Step::Return(apply_binop(*op, lv, v, span)?)
+            }
+            Kont::Unary(node) => {
+                let (Expr::Unary(op, _), span) = at(node) else {
+                    unreachable!()
+                };
+                Step::Return(apply_unaryop(*op, v, span)?)
+            }
+            Kont::Access(node) => {
+                let (Expr::Access(_, (name, name_span)), span) = at(node) else {
+                    unreachable!()
+                };
+                return self.access(v, *name, name_span, span);
+            }
+            Kont::OptAccess(node) => {
+                let (Expr::OptAccess(_, (name, name_span)), span) = at(node) else {
+                    unreachable!()
+                };
+                if is_absent(&v, *name) {
+                    return Ok(Step::Return(Value::Null));
+                }
+                return self.access(v, *name, name_span, span);
+            }
+            Kont::IndexArr(node, env) => {
+                let Expr::Index(_, idx) = &at(node).0 else {
+                    unreachable!()
+                };
+                self.stack.push(Kont::IndexIdx(node, v));
+                Step::Eval(idx.as_ref(), env)
+            }
+            Kont::IndexIdx(node, arr) => {
+                let span = &at(node).1;
+                match (&arr, &v) {
+                    (Value::List(l), Value::Number(i)) => {
+                        let n = *i as usize;
+                        Step::Return(l.get(n).cloned().ok_or_else(|| {
+                            Diagnostic::new(
+                                span.clone(),
+                                format!("index out of bounds: {}", n),
+                                "list access",
+                            )
+                            .with_code("E0301")
+                        })?)
+                    }
+                    (Value::Block(frame), Value::String(s)) => {
+                        let (env_at_def, bind) = field_bind(frame, intern(s), span)?;
+                        return self.force(env_at_def, bind, span);
+                    }
+                    (a, i) => {
+                        return Err(Diagnostic::new(
+                            span.clone(),
+                            format!("cannot index {} by {}", a.type_name(), i.type_name()),
+                            "invalid index",
+                        )
+                        .with_code("E0203"))
+                    }
+                }
+            }
+            Kont::Driven(driver, span, callee) => {
+                return self.drive(driver, Some(v), &span, callee)
+            }
+        })
+    }
+
+    /// Return `bind`'s value, evaluating it first if it is still lazy.
+    fn force(
+        &mut self,
+        env: Env,
+        bind: Rc<RefCell<BindState>>,
+        span: &Span,
+    ) -> Result<Step, Diagnostic> {
+        match &*bind.borrow() {
+            BindState::Done(v) => return Ok(Step::Return(v.clone())),
+            BindState::InProgress => {
+                return Err(Diagnostic::new(
                     span.clone(),
-                    format!("unresolved variable: {}", display(var.name)),
-                    "resolver did not run",
+                    "cyclic binding",
+                    "this binding refers to itself during evaluation",
                 )
-            })?;
-            let env_at_def = env.parent_at(bref.depth);
-            let bind = env_at_def
-                .0
-                .as_ref()
-                .expect("frame missing")
-                .binds
-                .get(bref.slot as usize)
-                .expect("slot out of range")
-                .clone();
-            force(&env_at_def, &bind, span)
-        }
-        Expr::List(items) => {
-            let mut vs = Vec::with_capacity(items.len());
-            for item in items {
-                vs.push(interpret(item, env)?);
-            }
-            Ok(Value::List(Rc::new(vs)))
-        }
-        Expr::Function(params, body) => {
-            let param_names = params.iter().map(|(n, _)| *n).collect();
-            Ok(Value::Function(Function::Native {
-                params: param_names,
-                body: Rc::new(body.as_ref().clone()),
-                env: env.clone(),
-            }))
-        }
-        Expr::Block(defs) => {
-            let frame = make_frame(defs, env, true);
-            Ok(Value::Block(Rc::new(frame)))
+                .with_code("E0302"))
+            }
+            BindState::Lazy(_) => {}
         }
-        Expr::Binary(op, l, r) => {
-            let lv = interpret(l, env)?;
-            match op {
-                BinOp::And => {
-                    if !is_truthy(&lv) {
-                        return Ok(lv);
-                    }
-                    interpret(r, env)
+        enter(span)?;
+        let expr = match std::mem::replace(&mut *bind.borrow_mut(), BindState::InProgress) {
+            BindState::Lazy(e) => e,
+            _ => unreachable!(),
+        };
+        let node: Node = expr.as_ref();
+        self.stack.push(Kont::Force(bind, expr, env.clone()));
+        Ok(Step::Eval(node, env))
+    }
+
+    fn access(
+        &mut self,
+        ov: Value,
+        name: Symbol,
+        name_span: &Span,
+        span: &Span,
+    ) -> Result<Step, Diagnostic> {
+        let frame = match &ov {
+            Value::Block(f) => f,
+            other => {
+                return Err(Diagnostic::new(
+                    span.clone(),
+                    format!("field access on {}", other.type_name()),
+                    "not a block",
+                )
+                .with_code("E0203"))
+            }
+        };
+        let (env_at_def, bind) = field_bind(frame, name, name_span)?;
+        self.force(env_at_def, bind, name_span)
+    }
+
+    /// Call `callee`. When the caller has nothing left to do with the result
+    /// (its `Body` is on top of the stack) the callee's body takes its place.
+    fn apply(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        span: &Span,
+        name: Callee,
+    ) -> Result<Step, Diagnostic> {
+        match &callee {
+            Value::Function(Function::Native { params, body, env }) => {
+                if params.len() != args.len() {
+                    return Err(Diagnostic::new(
+                        span.clone(),
+                        format!("expected {} arguments, got {}", params.len(), args.len()),
+                        "argument count mismatch",
+                    )
+                    .with_code("E0207"));
                 }
-                BinOp::Or => {
-                    if is_truthy(&lv) {
-                        return Ok(lv);
+                let call_env = env.clone();
+                let frame = Frame {
+                    binds: args
+                        .into_iter()
+                        .map(|v| Rc::new(RefCell::new(BindState::Done(v))))
+                        .collect(),
+                    names: None,
+                    parent: env.clone(),
+                };
+                let node: Node = body.as_ref();
+                match self.stack.last_mut() {
+                    Some(Kont::Body(caller)) => {
+                        *caller = body.clone();
+                        let call = self.calls.last_mut().expect("a call per body");
+                        let from = std::mem::replace(&mut call.env, call_env);
+                        call.tail = Some(TailCall {
+                            callee: name,
+                            span: span.clone(),
+                            from,
+                            elided: call.tail.as_ref().map_or(0, |t| t.elided + 1),
+                        });
+                    }
+                    _ => {
+                        enter(span)?;
+                        self.stack.push(Kont::Body(body.clone()));
+                        self.calls.push(Call {
+                            callee: name,
+                            span: span.clone(),
+                            env: call_env,
+                            tail: None,
+                        });
                     }
-                    interpret(r, env)
-                }
-                _ => {
-                    let rv = interpret(r, env)?;
-                    apply_binop(*op, lv, rv, span)
                 }
+                Ok(Step::Eval(node, Env(Some(Rc::new(frame)))))
+            }
+            Value::Function(Function::Foreign(f)) => Ok(Step::Return(f(args, span)?)),
+            Value::Function(Function::Driven(start)) => {
+                let driver = start(args, span)?;
+                self.drive(driver, None, span, name)
             }
+            other => Err(Diagnostic::new(
+                span.clone(),
+                format!("cannot call {}", other.type_name()),
+                "not a function",
+            )
+            .with_code("E0203")),
         }
-        Expr::Unary(op, e) => {
-            let v = interpret(e, env)?;
-            apply_unaryop(*op, v, span)
+    }
+
+    fn drive(
+        &mut self,
+        mut driver: Box<dyn Driver>,
+        last: Option<Value>,
+        span: &Span,
+        callee: Callee,
+    ) -> Result<Step, Diagnostic> {
+        match driver.resume(last, span)? {
+            Resume::Done(v) => Ok(Step::Return(v)),
+            Resume::Call(f, args) => {
+                self.stack.push(Kont::Driven(driver, span.clone(), callee));
+                self.apply(f, args, span, callee.callback())
+            }
         }
-        Expr::Access(obj, (name, name_span)) => {
-            let ov = interpret(obj, env)?;
-            let frame = match ov {
-                Value::Block(f) => f,
-                other => {
-                    return Err(Diagnostic::new(
-                        span.clone(),
-                        format!("field access on {}", other.type_name()),
-                        "not a block",
-                    ))
+    }
+
+    /// Append the literal parts of an interpolation from `from` on to `out`,
+    /// stopping at the next embedded expression.
+    fn interp_parts(
+        &mut self,
+        node: Node,
+        env: Env,
+        from: usize,
+        mut out: String,
+    ) -> Result<Step, Diagnostic> {
+        let Expr::Interpolation(parts) = &at(node).0 else {
+            unreachable!()
+        };
+        for (part, p) in parts.iter().enumerate().skip(from) {
+            match p {
+                InterpPart::Literal(s, _) => out.push_str(s),
+                InterpPart::Expr(e) => {
+                    self.stack.push(Kont::Interp {
+                        node,
+                        env: env.clone(),
+                        part,
+                        out,
+                    });
+                    return Ok(Step::Eval(e, env));
                 }
+            }
+        }
+        Ok(Step::Return(Value::String(Rc::new(out))))
+    }
+
+    /// Try the arms of `Match` node `node` from `from` on: the first whose
+    /// pattern matches `v` and whose guard (if any) is truthy runs with a
+    /// frame holding its binders, in the slot order the resolver assigned via
+    /// `Pattern::binders`.
+    fn select_arm(
+        &mut self,
+        node: Node,
+        env: Env,
+        v: Value,
+        from: usize,
+    ) -> Result<Step, Diagnostic> {
+        let (Expr::Match { arms, .. }, span) = at(node) else {
+            unreachable!()
+        };
+        for (i, arm) in arms.iter().enumerate().skip(from) {
+            let mut bound = Vec::new();
+            if !match_pattern(&arm.pattern.0, &v, &mut bound)? {
+                continue;
+            }
+            let frame = Frame {
+                binds: bound
+                    .into_iter()
+                    .map(|v| Rc::new(RefCell::new(BindState::Done(v))))
+                    .collect(),
+                names: None,
+                parent: env.clone(),
             };
-            access_field(&frame, *name, name_span)
+            let arm_env = Env(Some(Rc::new(frame)));
+            return Ok(match &arm.guard {
+                Some(guard) => {
+                    self.stack.push(Kont::Guard {
+                        node,
+                        env,
+                        value: v,
+                        arm: i,
+                        arm_env: arm_env.clone(),
+                    });
+                    Step::Eval(guard, arm_env)
+                }
+                None => Step::Eval(&arm.body, arm_env),
+            });
         }
-        Expr::Index(arr, idx) => {
-            let av = interpret(arr, env)?;
-            let iv = interpret(idx, env)?;
-            apply_index(av, iv, span)
+        Err(Diagnostic::new(
+            span.clone(),
+            format!("no match arm matched {}", v.type_name()),
+            "non-exhaustive match",
+        )
+        .with_code("E0205"))
+    }
+}
+
+/// Evaluate `expr` on the spot when that needs no continuation: literals,
+/// variables and record fields that already hold a value, and operators
+/// over those. `None`
+/// sends it down the general path, which also reports any error; nothing
+/// observable happened meanwhile, since none of these have effects.
+fn quick(expr: &Spanned<Expr>, env: &Env) -> Option<Value> {
+    match &expr.0 {
+        Expr::Number(n) => Some(Value::Number(*n)),
+        Expr::String(s) => Some(Value::String(s.clone())),
+        Expr::Bool(b) => Some(Value::Bool(*b)),
+        Expr::Null => Some(Value::Null),
+        Expr::Variable(var) => {
+            let bref = var.resolved.get()?;
+            let mut cur = env;
+            for _ in 0..bref.depth {
+                cur = &cur.0.as_ref()?.parent;
+            }
+            let bind = cur.0.as_ref()?.binds.get(bref.slot as usize)?;
+            match &*bind.borrow() {
+                BindState::Done(v) => Some(v.clone()),
+                _ => None,
+            }
+        }
+        Expr::Binary(op, l, r) => {
+            let lv = quick(l, env)?;
+            let short_circuit = match op {
+                BinOp::And => !is_truthy(&lv),
+                BinOp::Or => is_truthy(&lv),
+                BinOp::Coalesce => !matches!(lv, Value::Null),
+                _ => return apply_binop(*op, lv, quick(r, env)?, &expr.1).ok(),
+            };
+            if short_circuit {
+                Some(lv)
+            } else {
+                quick(r, env)
+            }
+        }
+        Expr::Unary(op, e) => apply_unaryop(*op, quick(e, env)?, &expr.1).ok(),
+        Expr::Access(obj, (name, _)) => {
+            let Value::Block(frame) = &quick(obj, env)? else {
+                return None;
+            };
+            let slot = *frame.names.as_ref()?.get(name)?;
+            let field = match &*frame.binds[slot as usize].borrow() {
+                BindState::Done(v) => Some(v.clone()),
+                _ => None,
+            };
+            field
         }
-        // The TCO loop in `interpret` handles these directly.
-        Expr::Call(_, _) | Expr::If { .. } | Expr::ImmediateBlock(_) => unreachable!(
-            "interpret_value should never see tail-positionable Expr variants"
-        ),
+        _ => None,
     }
 }
 
-fn access_field(frame: &Rc<Frame>, name: Symbol, span: &Span) -> EvalResult {
+fn enter(span: &Span) -> Result<(), Diagnostic> {
+    let max = MAX_DEPTH.with(Cell::get);
+    DEPTH.with(|d| {
+        if d.get() >= max {
+            return Err(Diagnostic::new(
+                span.clone(),
+                format!("maximum recursion depth of {} exceeded", max),
+                "raise the limit with --max-depth",
+            )
+            .with_code("E0303"));
+        }
+        d.set(d.get() + 1);
+        Ok(())
+    })
+}
+
+fn leave() {
+    DEPTH.with(|d| d.set(d.get() - 1));
+}
+
+fn match_pattern(pat: &Pattern, v: &Value, out: &mut Vec<Value>) -> Result<bool, Diagnostic> {
+    match (pat, v) {
+        (Pattern::Wildcard, _) => Ok(true),
+        (Pattern::Bind(_), v) => {
+            out.push(v.clone());
+            Ok(true)
+        }
+        (Pattern::Number(n), Value::Number(m)) => {
+            Ok(value_eq(&Value::Number(*n), &Value::Number(*m)))
+        }
+        (Pattern::String(s), Value::String(t)) => Ok(s == t),
+        (Pattern::Bool(b), Value::Bool(c)) => Ok(b == c),
+        (Pattern::Null, Value::Null) => Ok(true),
+        (Pattern::List { items, rest }, Value::List(l)) => {
+            let fits = match rest {
+                Some(_) => l.len() >= items.len(),
+                None => l.len() == items.len(),
+            };
+            if !fits {
+                return Ok(false);
+            }
+            for ((p, _), x) in items.iter().zip(l.iter()) {
+                if !match_pattern(p, x, out)? {
+                    return Ok(false);
+                }
+            }
+            if let Some(Some(_)) = rest {
+                out.push(Value::List(Rc::new(l[items.len()..].to_vec())));
+            }
+            Ok(true)
+        }
+        (Pattern::Record(fields), Value::Block(frame)) => {
+            let Some(names) = frame.names.as_ref() else {
+                return Ok(false);
+            };
+            for ((name, name_span), sub) in fields {
+                let Some(slot) = names.get(name) else {
+                    return Ok(false);
+                };
+                let bind = frame.binds[*slot as usize].clone();
+                let fv = force(&Env(Some(frame.clone())), &bind, name_span)?;
+                match sub {
+                    Some((p, _)) => {
+                        if !match_pattern(p, &fv, out)? {
+                            return Ok(false);
+                        }
+                    }
+                    None => out.push(fv),
+                }
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Whether `obj?.name` is null: `obj` is, or is a record without `name` (a
+/// field only some of the records in a list have, say).
+fn is_absent(obj: &Value, name: Symbol) -> bool {
+    match obj {
+        Value::Null => true,
+        Value::Block(f) => f.names.as_ref().is_some_and(|names| !names.contains_key(&name)),
+        _ => false,
+    }
+}
+
+/// The frame and cell holding field `name` of a record.
+fn field_bind(
+    frame: &Rc<Frame>,
+    name: Symbol,
+    span: &Span,
+) -> Result<(Env, Rc<RefCell<BindState>>), Diagnostic> {
     let names = frame.names.as_ref().ok_or_else(|| {
         Diagnostic::new(
             span.clone(),
@@ -426,61 +1378,16 @@ fn access_field(frame: &Rc<Frame>, name: Symbol, span: &Span) -> EvalResult {
             format!("no such field: {}", display(name)),
             "field not found",
         )
+        .with_code("E0202")
+        .did_you_mean(display(name), names.keys().map(|n| display(*n)))
     })?;
-    let bind = frame.binds[*slot as usize].clone();
-    let env_at_def = Env(Some(frame.clone()));
-    force(&env_at_def, &bind, span)
-}
-
-fn apply_index(arr: Value, idx: Value, span: &Span) -> EvalResult {
-    match (arr, idx) {
-        (Value::List(l), Value::Number(i)) => {
-            let n = i as usize;
-            l.get(n).cloned().ok_or_else(|| {
-                Diagnostic::new(
-                    span.clone(),
-                    format!("index out of bounds: {}", n),
-                    "list access",
-                )
-            })
-        }
-        (Value::Block(frame), Value::String(s)) => access_field(&frame, intern(&s), span),
-        (a, i) => Err(Diagnostic::new(
-            span.clone(),
-            format!("cannot index {} by {}", a.type_name(), i.type_name()),
-            "invalid index",
-        )),
-    }
+    Ok((Env(Some(frame.clone())), frame.binds[*slot as usize].clone()))
 }
 
 pub fn call_value(callee: Value, args: Vec<Value>, span: &Span) -> EvalResult {
-    match callee {
-        Value::Function(Function::Native { params, body, env }) => {
-            if params.len() != args.len() {
-                return Err(Diagnostic::new(
-                    span.clone(),
-                    format!("expected {} arguments, got {}", params.len(), args.len()),
-                    "argument count mismatch",
-                ));
-            }
-            let mut binds = Vec::with_capacity(args.len());
-            for (_p, a) in params.iter().zip(args.into_iter()) {
-                binds.push(Rc::new(RefCell::new(BindState::Done(a))));
-            }
-            let frame = Frame {
-                binds,
-                names: None,
-                parent: env,
-            };
-            interpret(&body, &Env(Some(Rc::new(frame))))
-        }
-        Value::Function(Function::Foreign(f)) => f(args, span),
-        other => Err(Diagnostic::new(
-            span.clone(),
-            format!("cannot call {}", other.type_name()),
-            "not a function",
-        )),
-    }
+    let mut m = Machine::default();
+    let step = m.apply(callee, args, span, Callee::Other);
+    m.finish(step)
 }
 
 fn is_truthy(v: &Value) -> bool {
@@ -505,7 +1412,9 @@ fn apply_binop(op: BinOp, l: Value, r: Value, span: &Span) -> EvalResult {
         BinOp::Lt => num_cmp(l, r, span, |a, b| a < b),
         BinOp::Ge => num_cmp(l, r, span, |a, b| a >= b),
         BinOp::Le => num_cmp(l, r, span, |a, b| a <= b),
-        BinOp::And | BinOp::Or => unreachable!("handled in interpret() for short-circuit"),
+        BinOp::And | BinOp::Or | BinOp::Coalesce => {
+            unreachable!("handled in interpret() for short-circuit")
+        }
     }
 }
 
@@ -524,23 +1433,36 @@ fn into_number(v: Value, span: &Span) -> Result<f64, Diagnostic> {
             span.clone(),
             format!("expected number, got {}", other.type_name()),
             "type mismatch",
-        )),
+        )
+        .with_code("E0201")),
     }
 }
 
 fn value_eq(a: &Value, b: &Value) -> bool {
     use Value::*;
-    match (a, b) {
-        (Number(a), Number(b)) => (a - b).abs() < f64::EPSILON,
-        (Null, Null) => true,
-        (Bool(a), Bool(b)) => a == b,
-        (String(a), String(b)) => a == b,
-        (List(a), List(b)) => {
-            Rc::ptr_eq(a, b)
-                || (a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| value_eq(x, y)))
+    // Pairs still to compare, so deep lists don't recurse.
+    let mut todo = Vec::new();
+    let mut pair = Some((a, b));
+    while let Some((a, b)) = pair.take().or_else(|| todo.pop()) {
+        let same = match (a, b) {
+            (Number(a), Number(b)) => (a - b).abs() < f64::EPSILON,
+            (Null, Null) => true,
+            (Bool(a), Bool(b)) => a == b,
+            (String(a), String(b)) => a == b,
+            (List(a), List(b)) => {
+                let same = Rc::ptr_eq(a, b) || a.len() == b.len();
+                if same && !Rc::ptr_eq(a, b) {
+                    todo.extend(a.iter().zip(b.iter()));
+                }
+                same
+            }
+            _ => false,
+        };
+        if !same {
+            return false;
         }
-        _ => false,
     }
+    true
 }
 
 fn apply_unaryop(op: UnaryOp, v: Value, span: &Span) -> EvalResult {
@@ -552,12 +1474,33 @@ fn apply_unaryop(op: UnaryOp, v: Value, span: &Span) -> EvalResult {
                 span.clone(),
                 format!("cannot apply ! to {}", other.type_name()),
                 "type mismatch",
-            )),
+            )
+            .with_code("E0201")),
         },
     }
 }
 
 impl Value {
+    /// A record holding `fields`, already evaluated, in the given order.
+    pub fn record(fields: Vec<(Symbol, Value)>) -> Value {
+        let mut binds = Vec::with_capacity(fields.len());
+        let mut names = HashMap::with_capacity(fields.len());
+        for (name, value) in fields {
+            match names.get(&name) {
+                Some(&slot) => binds[slot as usize] = Rc::new(RefCell::new(BindState::Done(value))),
+                None => {
+                    names.insert(name, binds.len() as u32);
+                    binds.push(Rc::new(RefCell::new(BindState::Done(value))));
+                }
+            }
+        }
+        Value::Block(Rc::new(Frame {
+            binds,
+            names: Some(names),
+            parent: Env::empty(),
+        }))
+    }
+
     pub fn type_name(&self) -> &'static str {
         match self {
             Value::Number(_) => "number",
@@ -571,53 +1514,108 @@ impl Value {
     }
 }
 
-impl fmt::Display for Value {
+/// The value's structure, for embedders: unlike `Display`, it shows
+/// functions and leaves record fields that were never needed unevaluated.
+impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Value::Number(n) => write!(f, "{}", n),
-            Value::String(s) => {
-                f.write_str("\"")?;
-                for c in s.chars() {
-                    match c {
-                        '"' => f.write_str("\\\"")?,
-                        '\\' => f.write_str("\\\\")?,
-                        '\n' => f.write_str("\\n")?,
-                        '\t' => f.write_str("\\t")?,
-                        '\r' => f.write_str("\\r")?,
-                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
-                        c => f.write_char(c)?,
+            Value::Number(n) => f.debug_tuple("Number").field(n).finish(),
+            Value::String(s) => f.debug_tuple("String").field(s).finish(),
+            Value::Bool(b) => f.debug_tuple("Bool").field(b).finish(),
+            Value::Null => f.write_str("Null"),
+            Value::List(items) => f.debug_tuple("List").field(&**items).finish(),
+            Value::Function(Function::Native { params, .. }) => {
+                let params: Vec<_> = params.iter().map(|p| display(*p)).collect();
+                f.debug_struct("Function").field("params", &params).finish_non_exhaustive()
+            }
+            Value::Function(Function::Foreign(_) | Function::Driven(_)) => f.write_str("Function(<host>)"),
+            Value::Block(frame) => match frame.fields() {
+                Some(fields) => {
+                    let mut record = f.debug_map();
+                    for (name, slot) in fields {
+                        match &*frame.binds[slot as usize].borrow() {
+                            BindState::Done(v) => record.entry(&display(name), v),
+                            _ => record.entry(&display(name), &format_args!("<unevaluated>")),
+                        };
                     }
+                    record.finish()
                 }
-                f.write_str("\"")
-            }
-            Value::Bool(b) => write!(f, "{}", b),
-            Value::Null => write!(f, "null"),
-            Value::List(v) => {
-                let inner: Vec<String> = v.iter().map(|x| format!("{}", x)).collect();
-                write!(f, "[{}]", inner.join(", "))
-            }
-            Value::Function(_) => write!(f, "[function]"),
-            Value::Block(b) => {
-                let Some(names) = b.names.as_ref() else {
-                    return write!(f, "{{...}}");
-                };
-                let mut sorted: Vec<(Symbol, u32)> =
-                    names.iter().map(|(k, v)| (*k, *v)).collect();
-                sorted.sort_by_key(|(s, _)| display(*s));
-                let env = Env(Some(b.clone()));
-                let parts: Vec<String> = sorted
-                    .into_iter()
-                    .map(|(name, slot)| {
+                None => f.write_str("Block(<scope>)"),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    // Iterative, with the parts still to write on a stack, so printing a
+    // deeply nested value doesn't recurse once per level.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        enum Part {
+            Value(Value),
+            Text(String),
+        }
+        let mut todo = vec![Part::Value(self.clone())];
+        while let Some(part) = todo.pop() {
+            let v = match part {
+                Part::Text(text) => {
+                    f.write_str(&text)?;
+                    continue;
+                }
+                Part::Value(v) => v,
+            };
+            match &v {
+                Value::Number(n) => write!(f, "{}", n)?,
+                Value::String(s) => {
+                    f.write_str("\"")?;
+                    for c in s.chars() {
+                        match c {
+                            '"' => f.write_str("\\\"")?,
+                            '\\' => f.write_str("\\\\")?,
+                            '\n' => f.write_str("\\n")?,
+                            '\t' => f.write_str("\\t")?,
+                            '\r' => f.write_str("\\r")?,
+                            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                            c => f.write_char(c)?,
+                        }
+                    }
+                    f.write_str("\"")?;
+                }
+                Value::Bool(b) => write!(f, "{}", b)?,
+                Value::Null => write!(f, "null")?,
+                Value::List(items) => {
+                    f.write_str("[")?;
+                    todo.push(Part::Text("]".into()));
+                    for (i, item) in items.iter().enumerate().rev() {
+                        todo.push(Part::Value(item.clone()));
+                        if i > 0 {
+                            todo.push(Part::Text(", ".into()));
+                        }
+                    }
+                }
+                Value::Function(_) => write!(f, "[function]")?,
+                Value::Block(b) => {
+                    let Some(names) = b.names.as_ref() else {
+                        write!(f, "{{...}}")?;
+                        continue;
+                    };
+                    let mut sorted: Vec<(Symbol, u32)> =
+                        names.iter().map(|(k, v)| (*k, *v)).collect();
+                    sorted.sort_by_key(|(s, _)| display(*s));
+                    let env = Env(Some(b.clone()));
+                    f.write_str("{")?;
+                    todo.push(Part::Text("}".into()));
+                    for (i, (name, slot)) in sorted.into_iter().enumerate().rev() {
                         let bind = b.binds[slot as usize].clone();
-                        let val_str = match force(&env, &bind, &(0..0)) {
-                            Ok(v) => v.to_string(),
-                            Err(_) => "<error>".to_string(),
-                        };
-                        format!("\"{}\": {}", display(name), val_str)
-                    })
-                    .collect();
-                write!(f, "{{{}}}", parts.join(", "))
+                        todo.push(match force(&env, &bind, &(0..0)) {
+                            Ok(v) => Part::Value(v),
+                            Err(_) => Part::Text("<error>".into()),
+                        });
+                        let sep = if i > 0 { ", " } else { "" };
+                        todo.push(Part::Text(format!("{}\"{}\": ", sep, display(name))));
+                    }
+                }
             }
         }
+        Ok(())
     }
 }
@@ -11,15 +11,16 @@
 //! results from `typeck`. `Number → F64`, `Bool → I8`, `Fn(...) → I64`. Anything
 //! else is rejected with an ariadne-friendly Diagnostic.
 use crate::ast::*;
-use crate::diag::Diagnostic;
+use crate::diag::{Diagnostic, Severity};
 use crate::interp;
 use crate::lexer::Span;
-use crate::types::{Subst, Type};
+use crate::link;
 use crate::typeck;
+use crate::types::{Subst, Type};
 
 use cranelift_codegen::ir::{
-    types as ir_types, AbiParam, InstBuilder, MemFlags, Signature, SigRef, Type as IrType,
-    Value as IrValue,
+    types as ir_types, AbiParam, InstBuilder, MemFlags, SigRef, Signature, TrapCode,
+    Type as IrType, Value as IrValue,
 };
 use cranelift_codegen::isa::CallConv;
 use cranelift_codegen::settings::{self, Configurable};
@@ -27,7 +28,9 @@ use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable as CV
 use cranelift_jit::{JITBuilder, JITModule};
 use cranelift_module::{FuncId, Linkage, Module};
 
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
 
 // === Runtime helper ==========================================================
 
@@ -258,6 +261,44 @@ pub extern "C" fn spctr_print(s: *const u8) {
     }
 }
 
+/// Where diagnostics go while a program runs; see `run_with_display`.
+pub type Report = Rc<dyn Fn(&Diagnostic)>;
+
+thread_local! {
+    /// `TypeCheckResult::null_checks` of the program being compiled.
+    static NULL_CHECKS: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+    /// Where `spctr_fail` reports, while `run_with_display` runs a program.
+    static REPORT: RefCell<Option<Report>> = const { RefCell::new(None) };
+}
+
+/// `spctr_fail` kinds.
+const FAIL_NULL: i64 = 0;
+const FAIL_NO_MATCH: i64 = 1;
+
+/// Called by generated code where the program cannot go on: a value typeck
+/// warned may be null is null, or no arm of a `match` matches. Generated
+/// frames have no unwind information to return through, so this reports
+/// the failure at `start..end` and exits the process.
+#[no_mangle]
+pub extern "C" fn spctr_fail(kind: u32, start: u64, end: u64) {
+    let span = start as usize..end as usize;
+    let d = if i64::from(kind) == FAIL_NULL {
+        Diagnostic::new(span, "unexpected null", "is null").with_code("E0204")
+    } else {
+        Diagnostic::new(span, "no match arm matched the value", "non-exhaustive match").with_code("E0205")
+    };
+    match REPORT.with(|r| r.borrow().clone()) {
+        Some(report) => {
+            report(&d);
+            crate::diag::finish();
+        }
+        None => eprintln!("{}: {}", d.message, d.label),
+    }
+    use std::io::Write;
+    let _ = std::io::stdout().flush();
+    std::process::exit(1);
+}
+
 // === Entry ===================================================================
 
 /// Compile and execute an AST through the JIT, returning the f64 produced by
@@ -272,8 +313,21 @@ pub fn run(ast: &Statement) -> Result<f64, Diagnostic> {
 /// via the `spctr_print` runtime helper. Used by `main.rs` so JIT execution
 /// matches tree-walker output for non-numeric programs (records, strings,
 /// lists).
-pub fn run_with_display(ast: &Statement) -> Result<(), Diagnostic> {
-    run_inner(ast, true).map(|_| ())
+///
+/// Typeck's warnings do not stop compilation; they go to `report` before the
+/// program runs. A failure at run time also goes to `report`, and then the
+/// process exits (see `spctr_fail`).
+pub fn run_with_display(ast: &Statement, report: Report) -> Result<(), Diagnostic> {
+    let compiled = compile_inner(ast, true)?;
+    for w in &compiled.warnings {
+        report(w);
+    }
+    let sources = compiled.sources.clone();
+    let report: Report = Rc::new(move |d| report(&sources.map(d.clone())));
+    let prev = REPORT.with(|r| r.replace(Some(report)));
+    run_compiled(compiled);
+    REPORT.with(|r| *r.borrow_mut() = prev);
+    Ok(())
 }
 
 /// A handle to a compiled spctr program ready to invoke. The module owning
@@ -284,6 +338,9 @@ pub fn run_with_display(ast: &Statement) -> Result<(), Diagnostic> {
 /// where the program will be called repeatedly without re-parsing.
 pub struct Compiled {
     main_fn: extern "C" fn() -> f64,
+    warnings: Vec<Diagnostic>,
+    /// Maps run-time failures inside imported modules back to their file.
+    sources: Rc<link::Sources>,
     // SAFETY anchor: `main_fn` is a raw function pointer into the executable
     // pages owned by `_module`. The module must outlive every call to
     // `main_fn`, so we hold it here.
@@ -296,6 +353,12 @@ impl Compiled {
     pub fn run(&self) -> f64 {
         (self.main_fn)()
     }
+
+    /// What typeck warned about: possibly null values and non-exhaustive
+    /// matches. Errors stop compilation instead.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
 }
 
 /// Compile an AST into a `Compiled` handle without executing it. The
@@ -306,14 +369,23 @@ pub fn compile(ast: &Statement) -> Result<Compiled, Diagnostic> {
 }
 
 fn compile_inner(ast: &Statement, display: bool) -> Result<Compiled, Diagnostic> {
+    // Literal imports are linked into one program up front, so imported
+    // functions are monomorphized and compiled into this same module.
+    // Imported code keeps spans into its own file; `sources` maps them back.
+    let (linked, sources) = link::link(ast)?;
+    let ast = &linked;
     let tres = typeck::check(ast, &interp::root_types());
-    if let Some(w) = tres.warnings.into_iter().next() {
-        return Err(w);
+    if tres.has_errors() {
+        let first = tres.warnings.into_iter().find(|d| d.severity == Severity::Error);
+        return Err(sources.map(first.expect("has_errors")));
     }
 
-    let mut compiler = Compiler::new(tres.node_types)?;
+    let mut compiler = Compiler::new(tres.node_types).map_err(unsupported)?;
     compiler.display = display;
-    compiler.compile_program(ast)?;
+    let prev = NULL_CHECKS.with(|c| c.replace(tres.null_checks));
+    let compiled = compiler.compile_program(ast).map_err(unsupported);
+    NULL_CHECKS.with(|c| *c.borrow_mut() = prev);
+    compiled.map_err(|d| sources.map(d))?;
     let main_id = compiler.main_id;
     let mut module = compiler.module;
     module
@@ -323,12 +395,17 @@ fn compile_inner(ast: &Statement, display: bool) -> Result<Compiled, Diagnostic>
     let main_fn: extern "C" fn() -> f64 = unsafe { std::mem::transmute(main_ptr) };
     Ok(Compiled {
         main_fn,
+        warnings: tres.warnings.into_iter().map(|w| sources.map(w)).collect(),
+        sources: Rc::new(sources),
         _module: module,
     })
 }
 
 fn run_inner(ast: &Statement, display: bool) -> Result<f64, Diagnostic> {
-    let compiled = compile_inner(ast, display)?;
+    Ok(run_compiled(compile_inner(ast, display)?))
+}
+
+fn run_compiled(compiled: Compiled) -> f64 {
     let result = compiled.run();
     // Forget the module: spctr programs leak top-level closure allocations
     // via `Box::leak` and these point into the module's executable pages;
@@ -336,13 +413,22 @@ fn run_inner(ast: &Statement, display: bool) -> Result<f64, Diagnostic> {
     // reclaim memory should use `compile` + `Compiled` and drop the handle
     // themselves.
     std::mem::forget(compiled);
-    Ok(result)
+    result
 }
 
 fn internal(msg: impl Into<String>) -> Diagnostic {
     Diagnostic::new(0..0, msg, "JIT internal error")
 }
 
+/// Compile errors are about what the JIT cannot handle, so the ones that
+/// do not already say what is wrong with the program get E0501.
+fn unsupported(diag: Diagnostic) -> Diagnostic {
+    match diag.code {
+        Some(_) => diag,
+        None => diag.with_code("E0501"),
+    }
+}
+
 fn declare_stdlib(module: &mut JITModule) -> Result<(), Diagnostic> {
     let mk = |module: &mut JITModule, name: &str, params: &[IrType], ret: Option<IrType>| {
         let mut sig = module.make_signature();
@@ -369,6 +455,7 @@ fn declare_stdlib(module: &mut JITModule) -> Result<(), Diagnostic> {
     mk(module, "spctr_list_concat", &[ir_types::I64, ir_types::I64], Some(ir_types::I64))?;
     mk(module, "spctr_list_slice", &[ir_types::I64, ir_types::I32, ir_types::I32], Some(ir_types::I64))?;
     mk(module, "spctr_print", &[ir_types::I64], None)?;
+    mk(module, "spctr_fail", &[ir_types::I32, ir_types::I64, ir_types::I64], None)?;
     Ok(())
 }
 
@@ -379,6 +466,8 @@ fn ir_type_for(ty: &Type, span: &Span) -> Result<IrType, Diagnostic> {
         Type::Number => Ok(ir_types::F64),
         Type::Bool | Type::Null => Ok(ir_types::I8),
         Type::Fn(_, _) | Type::Record(_) | Type::List(_) | Type::String => Ok(ir_types::I64),
+        // Null is marked in-band (see `emit_null`).
+        Type::Nullable(t) => ir_type_for(t, span),
         _ => Err(Diagnostic::new(
             span.clone(),
             format!("JIT cannot represent type {ty}"),
@@ -391,8 +480,9 @@ fn contains_var(ty: &Type) -> bool {
     match ty {
         Type::Var(_) => true,
         Type::Fn(args, ret) => args.iter().any(contains_var) || contains_var(ret),
-        Type::List(t) => contains_var(t),
+        Type::List(t) | Type::Nullable(t) => contains_var(t),
         Type::Record(fields) => fields.iter().any(|(_, t)| contains_var(t)),
+        Type::Row(_, _) => true,
         Type::Module(fields) => fields.iter().any(|(_, sch)| contains_var(&sch.ty)),
         _ => false,
     }
@@ -415,6 +505,27 @@ fn unify_subst(general: &Type, specific: &Type, subst: &mut Subst) {
             unify_subst(r1, r2, subst);
         }
         (Type::List(t1), Type::List(t2)) => unify_subst(t1, t2, subst),
+        (Type::Nullable(t1), Type::Nullable(t2)) => unify_subst(t1, t2, subst),
+        (Type::Nullable(_), Type::Null) => {}
+        // A plain `T` passed where `T?` is expected.
+        (Type::Nullable(t1), t2) => unify_subst(t1, t2, subst),
+        (Type::Record(f1), Type::Record(f2)) => {
+            for (n, t1) in f1 {
+                if let Some((_, t2)) = f2.iter().find(|(m, _)| m == n) {
+                    unify_subst(t1, t2, subst);
+                }
+            }
+        }
+        // The row var stands for the whole concrete record, so field
+        // offsets inside the instance follow the caller's layout.
+        (Type::Row(f1, rho), Type::Record(f2)) => {
+            subst.entry(*rho).or_insert_with(|| specific.clone());
+            for (n, t1) in f1 {
+                if let Some((_, t2)) = f2.iter().find(|(m, _)| m == n) {
+                    unify_subst(t1, t2, subst);
+                }
+            }
+        }
         _ => {}
     }
 }
@@ -545,6 +656,7 @@ impl Compiler {
         builder.symbol("spctr_list_concat", spctr_list_concat as *const u8);
         builder.symbol("spctr_list_slice", spctr_list_slice as *const u8);
         builder.symbol("spctr_print", spctr_print as *const u8);
+        builder.symbol("spctr_fail", spctr_fail as *const u8);
         let mut module = JITModule::new(builder);
 
         let mut alloc_sig = module.make_signature();
@@ -691,6 +803,12 @@ fn stringify_value(
             Ok(bcx.ins().select(j.val, true_lit, false_lit))
         }
         Some(Type::Null) => Ok(emit_string_literal(bcx, "null").val),
+        Some(Type::Nullable(inner)) => {
+            let is_null = emit_is_null(bcx, j);
+            let text = stringify_value(bcx, j, Some(inner), module, span)?;
+            let null_lit = emit_string_literal(bcx, "null").val;
+            Ok(bcx.ins().select(is_null, null_lit, text))
+        }
         Some(Type::String) => {
             if j.irty != ir_types::I64 {
                 return Err(internal(format!(
@@ -765,6 +883,28 @@ fn emit_display(
             Ok(())
         }
         Type::Fn(_, _) => emit_print_static(bcx, module, "[function]"),
+        Type::Nullable(inner) => {
+            let irty = ir_type_for(inner, span)?;
+            let is_null = emit_is_null(bcx, JVal { val, irty });
+            let null_blk = bcx.create_block();
+            let some_blk = bcx.create_block();
+            let merge = bcx.create_block();
+            bcx.ins().brif(is_null, null_blk, &[], some_blk, &[]);
+
+            bcx.switch_to_block(null_blk);
+            bcx.seal_block(null_blk);
+            emit_print_static(bcx, module, "null")?;
+            bcx.ins().jump(merge, &[]);
+
+            bcx.switch_to_block(some_blk);
+            bcx.seal_block(some_blk);
+            emit_display(bcx, val, inner, module, node_types, span)?;
+            bcx.ins().jump(merge, &[]);
+
+            bcx.switch_to_block(merge);
+            bcx.seal_block(merge);
+            Ok(())
+        }
         Type::Record(fields) => {
             // Match the tree-walker: fields are printed alphabetically by name
             // even though the heap layout uses declaration order.
@@ -1177,11 +1317,23 @@ impl Compiler {
                     self.collect_uses_in(a, depth_to_tl, subst, out);
                 }
             }
-            Expr::Access(o, _) => self.collect_uses_in(o, depth_to_tl, subst, out),
+            Expr::Access(o, _) | Expr::OptAccess(o, _) => {
+                self.collect_uses_in(o, depth_to_tl, subst, out);
+            }
             Expr::Index(a, i) => {
                 self.collect_uses_in(a, depth_to_tl, subst, out);
                 self.collect_uses_in(i, depth_to_tl, subst, out);
             }
+            // Each arm adds a resolver scope for its pattern binders.
+            Expr::Match { scrutinee, arms } => {
+                self.collect_uses_in(scrutinee, depth_to_tl, subst, out);
+                for arm in arms {
+                    if let Some(g) = &arm.guard {
+                        self.collect_uses_in(g, depth_to_tl + 1, subst, out);
+                    }
+                    self.collect_uses_in(&arm.body, depth_to_tl + 1, subst, out);
+                }
+            }
             _ => {}
         }
     }
@@ -1283,11 +1435,22 @@ impl Compiler {
                     self.discover(a, nesting, parent_subst, ast)?;
                 }
             }
-            Expr::Access(o, _) => self.discover(o, nesting, parent_subst, ast)?,
+            Expr::Access(o, _) | Expr::OptAccess(o, _) => {
+                self.discover(o, nesting, parent_subst, ast)?;
+            }
             Expr::Index(a, i) => {
                 self.discover(a, nesting, parent_subst, ast)?;
                 self.discover(i, nesting, parent_subst, ast)?;
             }
+            Expr::Match { scrutinee, arms } => {
+                self.discover(scrutinee, nesting, parent_subst, ast)?;
+                for arm in arms {
+                    if let Some(g) = &arm.guard {
+                        self.discover(g, nesting, parent_subst, ast)?;
+                    }
+                    self.discover(&arm.body, nesting, parent_subst, ast)?;
+                }
+            }
             _ => {}
         }
         Ok(())
@@ -1386,11 +1549,21 @@ impl Compiler {
                     self.collect_captures(a, layers, caps);
                 }
             }
-            Expr::Access(o, _) => self.collect_captures(o, layers, caps),
+            Expr::Access(o, _) | Expr::OptAccess(o, _) => self.collect_captures(o, layers, caps),
             Expr::Index(a, i) => {
                 self.collect_captures(a, layers, caps);
                 self.collect_captures(i, layers, caps);
             }
+            // Match arms bind their pattern variables in a scope of their own.
+            Expr::Match { scrutinee, arms } => {
+                self.collect_captures(scrutinee, layers, caps);
+                for arm in arms {
+                    if let Some(g) = &arm.guard {
+                        self.collect_captures(g, layers + 1, caps);
+                    }
+                    self.collect_captures(&arm.body, layers + 1, caps);
+                }
+            }
             _ => {}
         }
     }
@@ -1809,6 +1982,8 @@ struct BlockFrame {
     populated: Vec<bool>,
 }
 
+/// Compile `expr`, failing at run time if it is null where typeck warned it
+/// may be (see `TypeCheckResult::null_checks`).
 #[allow(clippy::too_many_arguments)]
 fn compile_expr(
     bcx: &mut FunctionBuilder,
@@ -1820,6 +1995,48 @@ fn compile_expr(
     node_types: &HashMap<usize, Type>,
     alloc_id: FuncId,
     cc: CallConv,
+) -> Result<JVal, Diagnostic> {
+    let v = compile_node(bcx, expr, env, module, funcs, top_level, node_types, alloc_id, cc)?;
+    if NULL_CHECKS.with(|c| c.borrow().contains(&(expr as *const _ as usize))) {
+        let is_null = emit_is_null(bcx, v);
+        let null_blk = bcx.create_block();
+        let ok_blk = bcx.create_block();
+        bcx.ins().brif(is_null, null_blk, &[], ok_blk, &[]);
+        bcx.switch_to_block(null_blk);
+        bcx.seal_block(null_blk);
+        emit_fail(bcx, module, FAIL_NULL, &expr.1)?;
+        bcx.switch_to_block(ok_blk);
+        bcx.seal_block(ok_blk);
+    }
+    Ok(v)
+}
+
+/// End the current block with a call to `spctr_fail`.
+fn emit_fail(bcx: &mut FunctionBuilder, module: &mut JITModule, kind: i64, span: &Span) -> Result<(), Diagnostic> {
+    let id = match module.declarations().get_name("spctr_fail") {
+        Some(cranelift_module::FuncOrDataId::Func(id)) => id,
+        _ => return Err(internal("spctr_fail not declared")),
+    };
+    let r = module.declare_func_in_func(id, bcx.func);
+    let kind = bcx.ins().iconst(ir_types::I32, kind);
+    let start = bcx.ins().iconst(ir_types::I64, span.start as i64);
+    let end = bcx.ins().iconst(ir_types::I64, span.end as i64);
+    bcx.ins().call(r, &[kind, start, end]);
+    bcx.ins().trap(TrapCode::unwrap_user(1));
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn compile_node(
+    bcx: &mut FunctionBuilder,
+    expr: &Spanned<Expr>,
+    env: &CompileEnv,
+    module: &mut JITModule,
+    funcs: &HashMap<FuncKey, FuncInfo>,
+    top_level: &[TopInstance],
+    node_types: &HashMap<usize, Type>,
+    alloc_id: FuncId,
+    cc: CallConv,
 ) -> Result<JVal, Diagnostic> {
     use cranelift_codegen::ir::condcodes::FloatCC;
     let span = &expr.1;
@@ -1912,14 +2129,16 @@ fn compile_expr(
 
             bcx.switch_to_block(then_blk);
             bcx.seal_block(then_blk);
-            let tv = compile_expr(bcx, cons, env, module, funcs, top_level, node_types, alloc_id, cc)?;
-            let tv = coerce_to(bcx, tv, result_irty, &cons.1)?;
+            let tv = compile_into(
+                bcx, cons, &result_ty, result_irty, env, module, funcs, top_level, node_types, alloc_id, cc,
+            )?;
             bcx.ins().jump(merge_blk, &[tv.into()]);
 
             bcx.switch_to_block(else_blk);
             bcx.seal_block(else_blk);
-            let ev = compile_expr(bcx, alt, env, module, funcs, top_level, node_types, alloc_id, cc)?;
-            let ev = coerce_to(bcx, ev, result_irty, &alt.1)?;
+            let ev = compile_into(
+                bcx, alt, &result_ty, result_irty, env, module, funcs, top_level, node_types, alloc_id, cc,
+            )?;
             bcx.ins().jump(merge_blk, &[ev.into()]);
 
             bcx.switch_to_block(merge_blk);
@@ -1929,6 +2148,47 @@ fn compile_expr(
                 irty: result_irty,
             })
         }
+        Expr::Binary(BinOp::Coalesce, l, r) => {
+            // `??` only needs a runtime test when typeck says the left side
+            // may be null; otherwise exactly one side is ever evaluated.
+            let lt = node_types
+                .get(&(l.as_ref() as *const _ as usize))
+                .cloned()
+                .unwrap_or(Type::Any)
+                .apply(env.subst);
+            match lt {
+                Type::Null => compile_expr(bcx, r, env, module, funcs, top_level, node_types, alloc_id, cc),
+                Type::Nullable(_) => {
+                    let result_ty = node_types
+                        .get(&(expr as *const _ as usize))
+                        .cloned()
+                        .unwrap_or(Type::Any)
+                        .apply(env.subst);
+                    let result_irty = ir_type_for(&result_ty, span)?;
+                    let lv = compile_expr(bcx, l, env, module, funcs, top_level, node_types, alloc_id, cc)?;
+                    let lval = coerce_to(bcx, lv, result_irty, &l.1)?;
+                    let is_null = emit_is_null(bcx, lv);
+                    let rhs_blk = bcx.create_block();
+                    let merge_blk = bcx.create_block();
+                    bcx.append_block_param(merge_blk, result_irty);
+                    bcx.ins().brif(is_null, rhs_blk, &[], merge_blk, &[lval.into()]);
+
+                    bcx.switch_to_block(rhs_blk);
+                    bcx.seal_block(rhs_blk);
+                    let rv = compile_expr(bcx, r, env, module, funcs, top_level, node_types, alloc_id, cc)?;
+                    let rv = coerce_to(bcx, rv, result_irty, &r.1)?;
+                    bcx.ins().jump(merge_blk, &[rv.into()]);
+
+                    bcx.switch_to_block(merge_blk);
+                    bcx.seal_block(merge_blk);
+                    Ok(JVal {
+                        val: bcx.block_params(merge_blk)[0],
+                        irty: result_irty,
+                    })
+                }
+                _ => compile_expr(bcx, l, env, module, funcs, top_level, node_types, alloc_id, cc),
+            }
+        }
         Expr::Binary(op, l, r) => {
             // Short-circuit for `&&` / `||` — typeck has unified both sides to
             // Bool, so we know the operand IR type is I8 and the result is I8.
@@ -1982,6 +2242,31 @@ fn compile_expr(
             // numbers/bool/null, and constant `false` for records/closures
             // (matching `interp::value_eq`).
             if matches!(op, BinOp::Eq | BinOp::Ne) {
+                let static_ty = |e: &Spanned<Expr>| {
+                    node_types
+                        .get(&(e as *const _ as usize))
+                        .cloned()
+                        .map(|t| t.apply(env.subst))
+                };
+                // Comparing against a possibly-null value: a bare `null` side
+                // has its own IR type, so these go by the null tests instead.
+                let nullable_eq = match (static_ty(l), static_ty(r)) {
+                    (Some(Type::Null), Some(Type::Nullable(_))) => Some(emit_is_null(bcx, rv)),
+                    (Some(Type::Nullable(_)), Some(Type::Null)) => Some(emit_is_null(bcx, lv)),
+                    (Some(Type::Nullable(inner)), _) | (_, Some(Type::Nullable(inner))) => {
+                        Some(emit_nullable_eq(bcx, lv, rv, &inner, module, span)?)
+                    }
+                    _ => None,
+                };
+                if let Some(eq) = nullable_eq {
+                    let raw = if matches!(op, BinOp::Eq) {
+                        eq
+                    } else {
+                        let one = bcx.ins().iconst(ir_types::I8, 1);
+                        bcx.ins().bxor(eq, one)
+                    };
+                    return Ok(JVal { val: raw, irty: ir_types::I8 });
+                }
                 if lv.irty != rv.irty {
                     return Err(Diagnostic::new(
                         span.clone(),
@@ -2028,7 +2313,9 @@ fn compile_expr(
                 BinOp::Le => JVal { val: bcx.ins().fcmp(FloatCC::LessThanOrEqual, ln, rn), irty: ir_types::I8 },
                 BinOp::Gt => JVal { val: bcx.ins().fcmp(FloatCC::GreaterThan, ln, rn), irty: ir_types::I8 },
                 BinOp::Ge => JVal { val: bcx.ins().fcmp(FloatCC::GreaterThanOrEqual, ln, rn), irty: ir_types::I8 },
-                BinOp::Eq | BinOp::Ne | BinOp::And | BinOp::Or => unreachable!(),
+                BinOp::Eq | BinOp::Ne | BinOp::And | BinOp::Or | BinOp::Coalesce => {
+                    unreachable!()
+                }
             };
             Ok(v)
         }
@@ -2060,48 +2347,41 @@ fn compile_expr(
         ),
         Expr::Access(obj, (name, name_span)) => {
             let obj_v = compile_expr(bcx, obj, env, module, funcs, top_level, node_types, alloc_id, cc)?;
-            if obj_v.irty != ir_types::I64 {
-                return Err(Diagnostic::new(
-                    obj.1.clone(),
-                    "JIT: field access requires a record",
-                    "expected record",
-                ));
-            }
             let obj_ty = node_types
                 .get(&(obj.as_ref() as *const _ as usize))
                 .cloned()
                 .ok_or_else(|| Diagnostic::new(obj.1.clone(), "JIT: missing type for record obj", ""))?
                 .apply(env.subst);
-            let fields = match &obj_ty {
-                Type::Record(f) => f,
+            emit_field_load(bcx, obj_v, &obj_ty, *name, name_span, &obj.1)
+        }
+        Expr::OptAccess(obj, (name, name_span)) => {
+            let obj_ty = node_types
+                .get(&(obj.as_ref() as *const _ as usize))
+                .cloned()
+                .ok_or_else(|| Diagnostic::new(obj.1.clone(), "JIT: missing type for record obj", ""))?
+                .apply(env.subst);
+            match &obj_ty {
+                Type::Null => Ok(JVal {
+                    val: bcx.ins().iconst(ir_types::I8, 0),
+                    irty: ir_types::I8,
+                }),
+                Type::Nullable(inner) => {
+                    let obj_v = compile_expr(bcx, obj, env, module, funcs, top_level, node_types, alloc_id, cc)?;
+                    let result_ty = node_types
+                        .get(&(expr as *const _ as usize))
+                        .cloned()
+                        .unwrap_or(Type::Any)
+                        .apply(env.subst);
+                    let result_irty = ir_type_for(&result_ty, span)?;
+                    emit_unless_null(bcx, obj_v, result_irty, span, |bcx| {
+                        emit_field_load(bcx, obj_v, inner, *name, name_span, &obj.1)
+                    })
+                }
                 _ => {
-                    return Err(Diagnostic::new(
-                        obj.1.clone(),
-                        format!("JIT: field access on non-record type {obj_ty}"),
-                        "expected record",
-                    ))
+                    let obj_v = compile_expr(bcx, obj, env, module, funcs, top_level, node_types, alloc_id, cc)?;
+                    emit_field_load(bcx, obj_v, &obj_ty, *name, name_span, &obj.1)
                 }
-            };
-            let (idx, field_ty) = fields
-                .iter()
-                .enumerate()
-                .find(|(_, (n, _))| n == name)
-                .map(|(i, (_, t))| (i, t.clone()))
-                .ok_or_else(|| {
-                    Diagnostic::new(
-                        name_span.clone(),
-                        format!(
-                            "JIT: no field '{}' on {}",
-                            crate::symbol::display(*name),
-                            obj_ty
-                        ),
-                        "",
-                    )
-                })?;
-            let irty = ir_type_for(&field_ty, name_span)?;
-            let offset = 8 * idx as i32;
-            let v = bcx.ins().load(irty, MemFlags::trusted(), obj_v.val, offset);
-            Ok(JVal { val: v, irty })
+            }
         }
         Expr::List(items) => {
             // Allocate `[length: u32][_pad: u32][slot * n]`.
@@ -2135,8 +2415,9 @@ fn compile_expr(
             let elem_irty = ir_type_for(&elem_ty, span)?;
 
             for (i, item) in items.iter().enumerate() {
-                let v = compile_expr(bcx, item, env, module, funcs, top_level, node_types, alloc_id, cc)?;
-                let v = coerce_to(bcx, v, elem_irty, &item.1)?;
+                let v = compile_into(
+                    bcx, item, &elem_ty, elem_irty, env, module, funcs, top_level, node_types, alloc_id, cc,
+                )?;
                 let offset = 8 + 8 * i as i32;
                 bcx.ins().store(MemFlags::trusted(), v, ptr, offset);
             }
@@ -2180,13 +2461,297 @@ fn compile_expr(
             let v = bcx.ins().load(elem_irty, MemFlags::trusted(), addr, 0);
             Ok(JVal { val: v, irty: elem_irty })
         }
-        Expr::Null => Ok(JVal {
-            val: bcx.ins().iconst(ir_types::I8, 0),
-            irty: ir_types::I8,
-        }),
+        Expr::Error => Err(Diagnostic::new(
+            span.clone(),
+            "JIT: cannot compile a syntax error",
+            "skipped by the parser",
+        )),
+        Expr::Null => match node_types
+            .get(&(expr as *const _ as usize))
+            .map(|t| t.apply(env.subst))
+        {
+            // Widened by typeck into a `T?` slot (list item, branch, ...).
+            Some(Type::Nullable(inner)) => {
+                let irty = ir_type_for(&inner, span)?;
+                Ok(JVal { val: emit_null(bcx, irty), irty })
+            }
+            _ => Ok(JVal {
+                val: bcx.ins().iconst(ir_types::I8, 0),
+                irty: ir_types::I8,
+            }),
+        },
         Expr::ImmediateBlock(stmt) => compile_immediate_block(
             bcx, expr, stmt, env, module, funcs, top_level, node_types, alloc_id, cc,
         ),
+        Expr::Match { scrutinee, arms } => compile_match(
+            bcx, expr, scrutinee, arms, env, module, funcs, top_level, node_types, alloc_id, cc,
+        ),
+    }
+}
+
+/// Lower `match` to a chain of pattern tests. Each arm tests its pattern
+/// (branching to the next arm's test block on failure), stores its binders
+/// into a freshly allocated record and pushes that as a `BlockFrame` — the
+/// resolver gave every arm a scope of its own, so binder lookups are plain
+/// `bref.depth=0` loads, exactly like Block locals. Falling off the last arm
+/// fails through `spctr_fail`, as typeck's exhaustiveness warning said it
+/// might.
+#[allow(clippy::too_many_arguments)]
+fn compile_match(
+    bcx: &mut FunctionBuilder,
+    match_expr: &Spanned<Expr>,
+    scrutinee: &Spanned<Expr>,
+    arms: &[MatchArm],
+    env: &CompileEnv,
+    module: &mut JITModule,
+    funcs: &HashMap<FuncKey, FuncInfo>,
+    top_level: &[TopInstance],
+    node_types: &HashMap<usize, Type>,
+    alloc_id: FuncId,
+    cc: CallConv,
+) -> Result<JVal, Diagnostic> {
+    let span = &match_expr.1;
+    let sv = compile_expr(
+        bcx, scrutinee, env, module, funcs, top_level, node_types, alloc_id, cc,
+    )?;
+    let scrutinee_ty = node_types
+        .get(&(scrutinee as *const _ as usize))
+        .cloned()
+        .ok_or_else(|| Diagnostic::new(scrutinee.1.clone(), "JIT: missing type for scrutinee", ""))?
+        .apply(env.subst);
+    let result_ty = node_types
+        .get(&(match_expr as *const _ as usize))
+        .cloned()
+        .ok_or_else(|| Diagnostic::new(span.clone(), "JIT: missing type for match", ""))?
+        .apply(env.subst);
+    let result_irty = ir_type_for(&result_ty, span)?;
+    let merge_blk = bcx.create_block();
+    bcx.append_block_param(merge_blk, result_irty);
+
+    for arm in arms {
+        let next_arm = bcx.create_block();
+        let mut bound: Vec<JVal> = Vec::new();
+        emit_pattern_test(
+            bcx,
+            &arm.pattern,
+            sv.val,
+            &scrutinee_ty,
+            next_arm,
+            &mut bound,
+            module,
+        )?;
+
+        let record_ptr = if bound.is_empty() {
+            bcx.ins().iconst(ir_types::I64, 0)
+        } else {
+            let record_alloc_id = match module.declarations().get_name("spctr_alloc_record") {
+                Some(cranelift_module::FuncOrDataId::Func(id)) => id,
+                _ => return Err(internal("spctr_alloc_record not declared")),
+            };
+            let alloc_ref = module.declare_func_in_func(record_alloc_id, bcx.func);
+            let n_slots = bcx.ins().iconst(ir_types::I32, bound.len() as i64);
+            let inst = bcx.ins().call(alloc_ref, &[n_slots]);
+            let ptr = bcx.inst_results(inst)[0];
+            for (i, b) in bound.iter().enumerate() {
+                bcx.ins()
+                    .store(MemFlags::trusted(), b.val, ptr, 8 * i as i32);
+            }
+            ptr
+        };
+        let mut frames = env.block_frames.clone();
+        frames.push(BlockFrame {
+            record_ptr,
+            slot_irtys: bound.iter().map(|b| b.irty).collect(),
+            populated: vec![true; bound.len()],
+        });
+        let arm_env = CompileEnv {
+            kind: env.kind.clone(),
+            block_frames: frames,
+            subst: env.subst,
+        };
+
+        if let Some(guard) = &arm.guard {
+            let g = compile_expr(
+                bcx, guard, &arm_env, module, funcs, top_level, node_types, alloc_id, cc,
+            )?;
+            if g.irty != ir_types::I8 {
+                return Err(Diagnostic::new(
+                    guard.1.clone(),
+                    "JIT: match guard must be bool",
+                    "expected bool",
+                ));
+            }
+            let body_blk = bcx.create_block();
+            bcx.ins().brif(g.val, body_blk, &[], next_arm, &[]);
+            bcx.switch_to_block(body_blk);
+            bcx.seal_block(body_blk);
+        }
+
+        let v = compile_into(
+            bcx, &arm.body, &result_ty, result_irty, &arm_env, module, funcs, top_level, node_types,
+            alloc_id, cc,
+        )?;
+        bcx.ins().jump(merge_blk, &[v.into()]);
+
+        bcx.switch_to_block(next_arm);
+        bcx.seal_block(next_arm);
+    }
+    emit_fail(bcx, module, FAIL_NO_MATCH, &match_expr.1)?;
+
+    bcx.switch_to_block(merge_blk);
+    bcx.seal_block(merge_blk);
+    Ok(JVal {
+        val: bcx.block_params(merge_blk)[0],
+        irty: result_irty,
+    })
+}
+
+/// Emit the checks for `pat` against `val` (of static type `ty`). On failure
+/// control jumps to `fail`; on success the builder is left in a fresh block
+/// with every binder's value appended to `bound` in `Pattern::binders` order.
+fn emit_pattern_test(
+    bcx: &mut FunctionBuilder,
+    pat: &Spanned<Pattern>,
+    val: IrValue,
+    ty: &Type,
+    fail: cranelift_codegen::ir::Block,
+    bound: &mut Vec<JVal>,
+    module: &mut JITModule,
+) -> Result<(), Diagnostic> {
+    use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+    let span = &pat.1;
+    let branch = |bcx: &mut FunctionBuilder, ok: IrValue| {
+        let cont = bcx.create_block();
+        bcx.ins().brif(ok, cont, &[], fail, &[]);
+        bcx.switch_to_block(cont);
+        bcx.seal_block(cont);
+    };
+    if let Type::Nullable(inner) = ty {
+        let is_null = emit_is_null(bcx, JVal { val, irty: ir_type_for(inner, span)? });
+        match &pat.0 {
+            Pattern::Null => {
+                branch(bcx, is_null);
+                return Ok(());
+            }
+            Pattern::Wildcard | Pattern::Bind(_) => {}
+            // The sentinels never equal a number or bool literal; pointer
+            // patterns must not look behind a null.
+            Pattern::Number(_) | Pattern::Bool(_) => {
+                return emit_pattern_test(bcx, pat, val, inner, fail, bound, module);
+            }
+            Pattern::String(_) | Pattern::List { .. } | Pattern::Record(_) => {
+                let not_null = bcx.ins().icmp_imm(IntCC::Equal, is_null, 0);
+                branch(bcx, not_null);
+                return emit_pattern_test(bcx, pat, val, inner, fail, bound, module);
+            }
+        }
+    }
+    match &pat.0 {
+        Pattern::Wildcard | Pattern::Null => Ok(()),
+        Pattern::Bind(_) => {
+            let irty = ir_type_for(ty, span)?;
+            bound.push(JVal { val, irty });
+            Ok(())
+        }
+        Pattern::Number(n) => {
+            let k = bcx.ins().f64const(*n);
+            let ok = bcx.ins().fcmp(FloatCC::Equal, val, k);
+            branch(bcx, ok);
+            Ok(())
+        }
+        Pattern::Bool(b) => {
+            let ok = bcx.ins().icmp_imm(IntCC::Equal, val, i64::from(*b));
+            branch(bcx, ok);
+            Ok(())
+        }
+        Pattern::String(s) => {
+            let lit = emit_string_literal(bcx, s);
+            let ok = emit_value_eq(bcx, val, lit.val, &Type::String, module, span)?;
+            branch(bcx, ok);
+            Ok(())
+        }
+        Pattern::List { items, rest } => {
+            let elem_ty = match ty {
+                Type::List(t) => (**t).clone(),
+                other => {
+                    return Err(Diagnostic::new(
+                        span.clone(),
+                        format!("JIT: list pattern on non-list type {other}"),
+                        "internal",
+                    ))
+                }
+            };
+            let elem_irty = ir_type_for(&elem_ty, span)?;
+            let len = bcx.ins().load(ir_types::I32, MemFlags::trusted(), val, 0);
+            let n = items.len() as i64;
+            let ok = match rest {
+                Some(_) => bcx
+                    .ins()
+                    .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, len, n),
+                None => bcx.ins().icmp_imm(IntCC::Equal, len, n),
+            };
+            branch(bcx, ok);
+            for (i, item) in items.iter().enumerate() {
+                let elem = bcx
+                    .ins()
+                    .load(elem_irty, MemFlags::trusted(), val, 8 + 8 * i as i32);
+                emit_pattern_test(bcx, item, elem, &elem_ty, fail, bound, module)?;
+            }
+            if let Some(Some(_)) = rest {
+                let id = match module.declarations().get_name("spctr_list_slice") {
+                    Some(cranelift_module::FuncOrDataId::Func(id)) => id,
+                    _ => return Err(internal("spctr_list_slice not declared")),
+                };
+                let r = module.declare_func_in_func(id, bcx.func);
+                let start = bcx.ins().iconst(ir_types::I32, n);
+                let tail_len = bcx.ins().isub(len, start);
+                let inst = bcx.ins().call(r, &[val, start, tail_len]);
+                bound.push(JVal {
+                    val: bcx.inst_results(inst)[0],
+                    irty: ir_types::I64,
+                });
+            }
+            Ok(())
+        }
+        Pattern::Record(fields) => {
+            let known = match ty {
+                Type::Record(f) => f,
+                other => {
+                    return Err(Diagnostic::new(
+                        span.clone(),
+                        format!("JIT: record pattern on non-record type {other}"),
+                        "internal",
+                    ))
+                }
+            };
+            for ((name, name_span), sub) in fields {
+                let (idx, field_ty) = known
+                    .iter()
+                    .enumerate()
+                    .find(|(_, (n, _))| n == name)
+                    .map(|(i, (_, t))| (i, t.clone()))
+                    .ok_or_else(|| {
+                        Diagnostic::new(
+                            name_span.clone(),
+                            format!(
+                                "JIT: no field '{}' on {}",
+                                crate::symbol::display(*name),
+                                ty
+                            ),
+                            "",
+                        )
+                    })?;
+                let irty = ir_type_for(&field_ty, name_span)?;
+                let fv = bcx
+                    .ins()
+                    .load(irty, MemFlags::trusted(), val, 8 * idx as i32);
+                match sub {
+                    Some(p) => emit_pattern_test(bcx, p, fv, &field_ty, fail, bound, module)?,
+                    None => bound.push(JVal { val: fv, irty }),
+                }
+            }
+            Ok(())
+        }
     }
 }
 
@@ -2266,10 +2831,22 @@ fn compile_immediate_block(
         block_frames: frames,
         subst: env.subst,
     };
-    let _ = span;
-    compile_expr(
-        bcx, &stmt.body, &body_env, module, funcs, top_level, node_types, alloc_id, cc,
-    )
+    // The body may be a literal typeck widened the block to: build it there.
+    let ty = node_types
+        .get(&(block_expr as *const _ as usize))
+        .map(|t| t.apply(env.subst));
+    match ty.as_ref().map(|t| (t, ir_type_for(t, span))) {
+        Some((ty, Ok(irty))) => {
+            let val = compile_into(
+                bcx, &stmt.body, ty, irty, &body_env, module, funcs, top_level, node_types, alloc_id,
+                cc,
+            )?;
+            Ok(JVal { val, irty })
+        }
+        _ => compile_expr(
+            bcx, &stmt.body, &body_env, module, funcs, top_level, node_types, alloc_id, cc,
+        ),
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -2622,7 +3199,7 @@ fn collect_sibling_refs(expr: &Spanned<Expr>, depth: u32, out: &mut HashSet<u32>
                 collect_sibling_refs(a, depth, out);
             }
         }
-        Expr::Access(o, _) => collect_sibling_refs(o, depth, out),
+        Expr::Access(o, _) | Expr::OptAccess(o, _) => collect_sibling_refs(o, depth, out),
         Expr::Index(a, i) => {
             collect_sibling_refs(a, depth, out);
             collect_sibling_refs(i, depth, out);
@@ -2634,7 +3211,16 @@ fn collect_sibling_refs(expr: &Spanned<Expr>, depth: u32, out: &mut HashSet<u32>
                 }
             }
         }
-        Expr::Number(_) | Expr::String(_) | Expr::Null | Expr::Bool(_) => {}
+        Expr::Match { scrutinee, arms } => {
+            collect_sibling_refs(scrutinee, depth, out);
+            for arm in arms {
+                if let Some(g) = &arm.guard {
+                    collect_sibling_refs(g, depth + 1, out);
+                }
+                collect_sibling_refs(&arm.body, depth + 1, out);
+            }
+        }
+        Expr::Number(_) | Expr::String(_) | Expr::Null | Expr::Bool(_) | Expr::Error => {}
     }
 }
 
@@ -2668,6 +3254,267 @@ fn coerce_to(
     }
 }
 
+/// Load field `name` out of a record value of static type `obj_ty`.
+fn emit_field_load(
+    bcx: &mut FunctionBuilder,
+    obj_v: JVal,
+    obj_ty: &Type,
+    name: crate::symbol::Symbol,
+    name_span: &Span,
+    obj_span: &Span,
+) -> Result<JVal, Diagnostic> {
+    if obj_v.irty != ir_types::I64 {
+        return Err(Diagnostic::new(
+            obj_span.clone(),
+            "JIT: field access requires a record",
+            "expected record",
+        ));
+    }
+    // A possibly null record has been checked by `compile_expr` already:
+    // typeck lists it in `null_checks`.
+    let record = match obj_ty {
+        Type::Nullable(t) => t.as_ref(),
+        t => t,
+    };
+    let fields = match record {
+        Type::Record(f) => f,
+        _ => {
+            return Err(Diagnostic::new(
+                obj_span.clone(),
+                format!("JIT: field access on non-record type {obj_ty}"),
+                "expected record",
+            ))
+        }
+    };
+    let (idx, field_ty) = fields
+        .iter()
+        .enumerate()
+        .find(|(_, (n, _))| *n == name)
+        .map(|(i, (_, t))| (i, t.clone()))
+        .ok_or_else(|| {
+            Diagnostic::new(
+                name_span.clone(),
+                format!(
+                    "JIT: no field '{}' on {}",
+                    crate::symbol::display(name),
+                    obj_ty
+                ),
+                "",
+            )
+        })?;
+    let irty = ir_type_for(&field_ty, name_span)?;
+    let offset = 8 * idx as i32;
+    let v = bcx.ins().load(irty, MemFlags::trusted(), obj_v.val, offset);
+    Ok(JVal { val: v, irty })
+}
+
+/// Bit pattern marking null in an `F64` slot of a nullable number: a quiet
+/// NaN with a payload ("null") that arithmetic never produces.
+const NULL_F64_BITS: i64 = 0x7ff8_0000_6e75_6c6c;
+
+/// Compile `e` into a slot of static type `target` (IR type `irty`). An
+/// expression typed plain `null` becomes `target`'s null sentinel there.
+#[allow(clippy::too_many_arguments)]
+fn compile_into(
+    bcx: &mut FunctionBuilder,
+    e: &Spanned<Expr>,
+    target: &Type,
+    irty: IrType,
+    env: &CompileEnv,
+    module: &mut JITModule,
+    funcs: &HashMap<FuncKey, FuncInfo>,
+    top_level: &[TopInstance],
+    node_types: &HashMap<usize, Type>,
+    alloc_id: FuncId,
+    cc: CallConv,
+) -> Result<IrValue, Diagnostic> {
+    let v = compile_expr(bcx, e, env, module, funcs, top_level, node_types, alloc_id, cc)?;
+    let from = node_types.get(&(e as *const _ as usize)).map(|t| t.apply(env.subst));
+    match from {
+        Some(from) if !same_layout(&from, target) => {
+            emit_relayout(bcx, module, v.val, &from, target, &e.1)
+        }
+        _ => coerce_to(bcx, v, irty, &e.1),
+    }
+}
+
+/// Whether a value built at static type `from` can be used as is where one
+/// of type `to` is expected.
+fn same_layout(from: &Type, to: &Type) -> bool {
+    match (from, to) {
+        (Type::Null, Type::Nullable(_)) => false,
+        (Type::Nullable(a), Type::Nullable(b)) => same_layout(a, b),
+        (Type::Nullable(a), b) => same_layout(a, b),
+        (a, Type::Nullable(b)) => same_layout(a, b),
+        (Type::List(a), Type::List(b)) => same_layout(a, b),
+        (Type::Record(fa), Type::Record(fb)) => {
+            fa.len() == fb.len()
+                && fa
+                    .iter()
+                    .zip(fb)
+                    .all(|((n, a), (m, b))| n == m && same_layout(a, b))
+        }
+        _ => true,
+    }
+}
+
+/// Convert `v`, built at static type `from`, to the layout of `to`: a plain
+/// `null` becomes `to`'s null sentinel, and a record is copied into a new
+/// one with its fields in `to`'s order. Record literals keep their own
+/// layout, so this is what lets `[{a: 1, b: 2}, {b: 3, a: 4}]` be a list.
+fn emit_relayout(
+    bcx: &mut FunctionBuilder,
+    module: &mut JITModule,
+    v: IrValue,
+    from: &Type,
+    to: &Type,
+    span: &Span,
+) -> Result<IrValue, Diagnostic> {
+    if same_layout(from, to) {
+        return Ok(v);
+    }
+    let (from_fields, to_fields) = match (from, to) {
+        (Type::Null, Type::Nullable(_)) => return Ok(emit_null(bcx, ir_type_for(to, span)?)),
+        (Type::Record(ff), Type::Record(tf)) => (ff, tf),
+        (Type::Record(ff), Type::Nullable(t)) => match t.as_ref() {
+            Type::Record(tf) => (ff, tf),
+            _ => return Err(relayout_error(from, to, span)),
+        },
+        _ => return Err(relayout_error(from, to, span)),
+    };
+    let record_alloc_id = match module.declarations().get_name("spctr_alloc_record") {
+        Some(cranelift_module::FuncOrDataId::Func(id)) => id,
+        _ => return Err(internal("spctr_alloc_record not declared")),
+    };
+    let alloc_ref = module.declare_func_in_func(record_alloc_id, bcx.func);
+    let n_slots = bcx.ins().iconst(ir_types::I32, to_fields.len() as i64);
+    let inst = bcx.ins().call(alloc_ref, &[n_slots]);
+    let record_ptr = bcx.inst_results(inst)[0];
+    for (i, (name, to_ty)) in to_fields.iter().enumerate() {
+        let fv = match from_fields.iter().position(|(n, _)| n == name) {
+            Some(j) => {
+                let from_ty = &from_fields[j].1;
+                let irty = ir_type_for(from_ty, span)?;
+                let raw = bcx.ins().load(irty, MemFlags::trusted(), v, 8 * j as i32);
+                emit_relayout(bcx, module, raw, from_ty, to_ty, span)?
+            }
+            // Filling it in with `null` would print a field the record
+            // doesn't have.
+            None => {
+                return Err(Diagnostic::new(
+                    span.clone(),
+                    format!(
+                        "JIT: record has no field '{}' of {}",
+                        crate::symbol::display(*name),
+                        to
+                    ),
+                    "optional fields are not supported",
+                ))
+            }
+        };
+        bcx.ins().store(MemFlags::trusted(), fv, record_ptr, 8 * i as i32);
+    }
+    Ok(record_ptr)
+}
+
+fn relayout_error(from: &Type, to: &Type, span: &Span) -> Diagnostic {
+    Diagnostic::new(
+        span.clone(),
+        format!("JIT: cannot convert {from} to {to}"),
+        "laid out differently",
+    )
+}
+
+/// `==` where either side may be null: equal when both are null, unequal
+/// when only one is, and by value (at type `inner`) otherwise.
+fn emit_nullable_eq(
+    bcx: &mut FunctionBuilder,
+    lv: JVal,
+    rv: JVal,
+    inner: &Type,
+    module: &mut JITModule,
+    span: &Span,
+) -> Result<IrValue, Diagnostic> {
+    let l_null = emit_is_null(bcx, lv);
+    let r_null = emit_is_null(bcx, rv);
+    let any_null = bcx.ins().bor(l_null, r_null);
+    let values_blk = bcx.create_block();
+    let merge_blk = bcx.create_block();
+    bcx.append_block_param(merge_blk, ir_types::I8);
+    let both_null = bcx.ins().band(l_null, r_null);
+    bcx.ins().brif(any_null, merge_blk, &[both_null.into()], values_blk, &[]);
+
+    bcx.switch_to_block(values_blk);
+    bcx.seal_block(values_blk);
+    let eq = emit_value_eq(bcx, lv.val, rv.val, inner, module, span)?;
+    bcx.ins().jump(merge_blk, &[eq.into()]);
+
+    bcx.switch_to_block(merge_blk);
+    bcx.seal_block(merge_blk);
+    Ok(bcx.block_params(merge_blk)[0])
+}
+
+/// Nullable values share their payload's IR type and mark null in-band: `0`
+/// for pointers (`I64`), `NULL_F64_BITS` for numbers and `2` for bools
+/// (`I8`, next to `0`/`1`).
+fn emit_null(bcx: &mut FunctionBuilder, irty: IrType) -> IrValue {
+    if irty == ir_types::F64 {
+        bcx.ins().f64const(f64::from_bits(NULL_F64_BITS as u64))
+    } else if irty == ir_types::I8 {
+        bcx.ins().iconst(ir_types::I8, 2)
+    } else {
+        bcx.ins().iconst(irty, 0)
+    }
+}
+
+/// `I8` 1 when the nullable slot `v` holds null (see `emit_null`).
+fn emit_is_null(bcx: &mut FunctionBuilder, v: JVal) -> IrValue {
+    use cranelift_codegen::ir::condcodes::IntCC;
+    if v.irty == ir_types::F64 {
+        let bits = bcx.ins().bitcast(ir_types::I64, MemFlags::new(), v.val);
+        bcx.ins().icmp_imm(IntCC::Equal, bits, NULL_F64_BITS)
+    } else if v.irty == ir_types::I8 {
+        bcx.ins().icmp_imm(IntCC::Equal, v.val, 2)
+    } else {
+        bcx.ins().icmp_imm(IntCC::Equal, v.val, 0)
+    }
+}
+
+/// Run `f` only when the nullable `v` is not null; otherwise produce null.
+/// The two paths merge into one `result_irty` value.
+fn emit_unless_null(
+    bcx: &mut FunctionBuilder,
+    v: JVal,
+    result_irty: IrType,
+    span: &Span,
+    f: impl FnOnce(&mut FunctionBuilder) -> Result<JVal, Diagnostic>,
+) -> Result<JVal, Diagnostic> {
+    let is_null = emit_is_null(bcx, v);
+    let some_blk = bcx.create_block();
+    let null_blk = bcx.create_block();
+    let merge_blk = bcx.create_block();
+    bcx.append_block_param(merge_blk, result_irty);
+    bcx.ins().brif(is_null, null_blk, &[], some_blk, &[]);
+
+    bcx.switch_to_block(some_blk);
+    bcx.seal_block(some_blk);
+    let r = f(bcx)?;
+    let r = coerce_to(bcx, r, result_irty, span)?;
+    bcx.ins().jump(merge_blk, &[r.into()]);
+
+    bcx.switch_to_block(null_blk);
+    bcx.seal_block(null_blk);
+    let n = emit_null(bcx, result_irty);
+    bcx.ins().jump(merge_blk, &[n.into()]);
+
+    bcx.switch_to_block(merge_blk);
+    bcx.seal_block(merge_blk);
+    Ok(JVal {
+        val: bcx.block_params(merge_blk)[0],
+        irty: result_irty,
+    })
+}
+
 /// `mono_hint`: when known, the canonical mono_ty_str of the value being loaded.
 /// In the `Main` arm this disambiguates which `TopInstance` to read from.
 fn load_variable(
@@ -2906,6 +3753,41 @@ fn compile_call(
     cc: CallConv,
     span: &Span,
 ) -> Result<JVal, Diagnostic> {
+    // `obj?.method(args)` on a possibly-null `obj`: test first, and only
+    // load the method and evaluate the arguments when it is present.
+    if let Expr::OptAccess(obj, (name, name_span)) = &callee.0 {
+        let obj_ty = node_types
+            .get(&(obj.as_ref() as *const _ as usize))
+            .cloned()
+            .ok_or_else(|| Diagnostic::new(obj.1.clone(), "JIT: missing type for record obj", ""))?
+            .apply(env.subst);
+        match &obj_ty {
+            Type::Null => {
+                return Ok(JVal {
+                    val: bcx.ins().iconst(ir_types::I8, 0),
+                    irty: ir_types::I8,
+                })
+            }
+            Type::Nullable(inner) => {
+                let obj_v = compile_expr(bcx, obj, env, module, funcs, top_level, node_types, alloc_id, cc)?;
+                let callee_ty = node_types
+                    .get(&(callee as *const _ as usize))
+                    .cloned()
+                    .ok_or_else(|| Diagnostic::new(callee.1.clone(), "JIT: missing type for callee", ""))?
+                    .apply(env.subst);
+                let (_, ret_irty) = fn_type_parts(&callee_ty, &callee.1)?;
+                return emit_unless_null(bcx, obj_v, ret_irty, span, |bcx| {
+                    let f = emit_field_load(bcx, obj_v, inner, *name, name_span, &obj.1)?;
+                    emit_indirect_call(
+                        bcx, f, &callee_ty, callee, args, env, module, funcs, top_level, node_types,
+                        alloc_id, cc, span,
+                    )
+                });
+            }
+            // Never null: an ordinary indirect call below.
+            _ => {}
+        }
+    }
     // First try the stdlib dispatcher — `Module.field(args)` on List/String/Number.
     if let Some(result) = try_compile_stdlib_call(
         bcx, callee, args, env, module, funcs, top_level, node_types, alloc_id, cc, span,
@@ -2960,6 +3842,35 @@ fn compile_call(
 
     // Indirect call. Compile the callee to a closure pointer; derive signature from typeck.
     let callee_v = compile_expr(bcx, callee, env, module, funcs, top_level, node_types, alloc_id, cc)?;
+    let callee_ty = node_types
+        .get(&(callee as *const _ as usize))
+        .cloned()
+        .ok_or_else(|| Diagnostic::new(callee.1.clone(), "JIT: missing type for callee", ""))?
+        .apply(env.subst);
+    emit_indirect_call(
+        bcx, callee_v, &callee_ty, callee, args, env, module, funcs, top_level, node_types, alloc_id,
+        cc, span,
+    )
+}
+
+/// Call the closure `callee_v` (of static type `callee_ty`) through its
+/// function pointer.
+#[allow(clippy::too_many_arguments)]
+fn emit_indirect_call(
+    bcx: &mut FunctionBuilder,
+    callee_v: JVal,
+    callee_ty: &Type,
+    callee: &Spanned<Expr>,
+    args: &[Spanned<Expr>],
+    env: &CompileEnv,
+    module: &mut JITModule,
+    funcs: &HashMap<FuncKey, FuncInfo>,
+    top_level: &[TopInstance],
+    node_types: &HashMap<usize, Type>,
+    alloc_id: FuncId,
+    cc: CallConv,
+    span: &Span,
+) -> Result<JVal, Diagnostic> {
     if callee_v.irty != ir_types::I64 {
         return Err(Diagnostic::new(
             callee.1.clone(),
@@ -2967,12 +3878,7 @@ fn compile_call(
             "expected closure",
         ));
     }
-    let callee_ty = node_types
-        .get(&(callee as *const _ as usize))
-        .cloned()
-        .ok_or_else(|| Diagnostic::new(callee.1.clone(), "JIT: missing type for callee", ""))?
-        .apply(env.subst);
-    let (param_irtys, ret_irty) = fn_type_parts(&callee_ty, &callee.1)?;
+    let (param_irtys, ret_irty) = fn_type_parts(callee_ty, &callee.1)?;
 
     if args.len() != param_irtys.len() {
         return Err(Diagnostic::new(
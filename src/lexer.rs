@@ -21,6 +21,8 @@ pub enum Token {
     True,
     #[token("false")]
     False,
+    #[token("match")]
+    Match,
 
     #[token("=>")]
     FatArrow,
@@ -43,6 +45,10 @@ pub enum Token {
     Colon,
     #[token(".")]
     Dot,
+    #[token("...")]
+    Ellipsis,
+    #[token("?.")]
+    QuestionDot,
 
     #[token("+")]
     Plus,
@@ -73,6 +79,8 @@ pub enum Token {
     AndAnd,
     #[token("||")]
     OrOr,
+    #[token("??")]
+    QuestionQuestion,
 
     #[regex(r"[0-9]+(\.[0-9]+)?([eE][+-]?[0-9]+)?", |lex| lex.slice().parse::<f64>().ok())]
     Num(f64),
@@ -108,6 +116,7 @@ impl std::fmt::Display for Token {
             Token::Null => write!(f, "null"),
             Token::True => write!(f, "true"),
             Token::False => write!(f, "false"),
+            Token::Match => write!(f, "match"),
             Token::FatArrow => write!(f, "=>"),
             Token::LParen => write!(f, "("),
             Token::RParen => write!(f, ")"),
@@ -118,6 +127,8 @@ impl std::fmt::Display for Token {
             Token::Comma => write!(f, ","),
             Token::Colon => write!(f, ":"),
             Token::Dot => write!(f, "."),
+            Token::Ellipsis => write!(f, "..."),
+            Token::QuestionDot => write!(f, "?."),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Star => write!(f, "*"),
@@ -132,6 +143,7 @@ impl std::fmt::Display for Token {
             Token::Bang => write!(f, "!"),
             Token::AndAnd => write!(f, "&&"),
             Token::OrOr => write!(f, "||"),
+            Token::QuestionQuestion => write!(f, "??"),
             Token::Num(n) => write!(f, "{}", n),
             Token::Str(s) => write!(f, "\"{}\"", s),
             Token::StrBegin => write!(f, "\""),
@@ -169,17 +181,45 @@ impl std::fmt::Display for LexError {
 /// single `Token::Str(s)` so existing call sites (e.g. the bracket-string
 /// field access `r["foo"]`) keep their fast path.
 pub fn lex(src: &str) -> Result<Vec<(Token, Span)>, Vec<LexError>> {
+    lex_with_comments(src).map(|(tokens, _)| tokens)
+}
+
+pub type Tokens = Vec<(Token, Span)>;
+
+/// Like [`lex`], but also returns the span of every `//` and `/* */`
+/// comment, in source order, for tools that must keep them (`spctr fmt`).
+pub fn lex_with_comments(src: &str) -> Result<(Tokens, Vec<Span>), Vec<LexError>> {
+    let lexed = lex_recovering(src);
+    if lexed.errors.is_empty() {
+        Ok((lexed.tokens, lexed.comments))
+    } else {
+        Err(lexed.errors)
+    }
+}
+
+/// Everything the lexer found in a source, errors included.
+pub struct Lexed {
+    pub tokens: Tokens,
+    pub comments: Vec<Span>,
+    pub errors: Vec<LexError>,
+}
+
+/// Lexes all of `src`, going on after errors: an unexpected character is
+/// left out, and a bad escape is left out of its string, so the parser can
+/// still report what follows.
+pub fn lex_recovering(src: &str) -> Lexed {
     let mut state = LexState {
         src,
         pos: 0,
         tokens: Vec::new(),
+        comments: Vec::new(),
         errors: Vec::new(),
     };
     state.lex_top(false);
-    if state.errors.is_empty() {
-        Ok(state.tokens)
-    } else {
-        Err(state.errors)
+    Lexed {
+        tokens: state.tokens,
+        comments: state.comments,
+        errors: state.errors,
     }
 }
 
@@ -187,6 +227,7 @@ struct LexState<'a> {
     src: &'a str,
     pos: usize,
     tokens: Vec<(Token, Span)>,
+    comments: Vec<Span>,
     errors: Vec<LexError>,
 }
 
@@ -206,12 +247,14 @@ impl<'a> LexState<'a> {
                 continue;
             }
             if remaining.starts_with("//") {
-                let nl = remaining.find('\n').map(|n| n + 1).unwrap_or(remaining.len());
-                self.pos += nl;
+                let end = remaining.find('\n').unwrap_or(remaining.len());
+                self.comments.push(self.pos..self.pos + end);
+                self.pos += end;
                 continue;
             }
             if remaining.starts_with("/*") {
                 if let Some(end) = remaining[2..].find("*/") {
+                    self.comments.push(self.pos..self.pos + end + 4);
                     self.pos += end + 4;
                 } else {
                     self.errors.push(LexError {
@@ -332,28 +375,28 @@ impl<'a> LexState<'a> {
                             });
                             return;
                         }
-                        let hex = &self.src[hex_start..hex_start + 4];
-                        let Ok(code) = u32::from_str_radix(hex, 16) else {
-                            self.errors.push(LexError {
-                                span: escape_start..hex_start + 4,
-                            });
-                            return;
-                        };
-                        let Some(ch) = char::from_u32(code) else {
+                        let code = self
+                            .src
+                            .get(hex_start..hex_start + 4)
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+                        let Some(ch) = code.and_then(char::from_u32) else {
                             self.errors.push(LexError {
-                                span: escape_start..hex_start + 4,
+                                span: escape_start..hex_start,
                             });
-                            return;
+                            self.pos = hex_start;
+                            continue;
                         };
                         current_lit.push(ch);
                         self.pos = hex_start + 4;
                         continue;
                     }
                     _ => {
+                        let end = escape_start + 1 + next_c.len_utf8();
                         self.errors.push(LexError {
-                            span: escape_start..escape_start + 2,
+                            span: escape_start..end,
                         });
-                        return;
+                        self.pos = end;
+                        continue;
                     }
                 };
                 current_lit.push(escaped);
@@ -1,11 +1,20 @@
 pub mod ast;
+pub mod codes;
+pub mod convert;
 pub mod diag;
+pub mod engine;
+pub mod fmt;
 pub mod interp;
 pub mod jit;
 pub mod lexer;
+pub mod link;
+pub mod lsp;
+pub mod manifest;
 pub mod parser;
 pub mod resolver;
 pub mod stdlib;
 pub mod symbol;
 pub mod typeck;
 pub mod types;
+
+pub use convert::{from_value, to_value};
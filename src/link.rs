@@ -0,0 +1,491 @@
+//! Compile-time import linking for the JIT.
+//!
+//! The tree-walker evaluates `import("./path")` when the call is reached.
+//! The JIT has to see the whole program before it emits any code, so
+//! [`link`] folds every imported file into the importing program first:
+//!
+//! - an imported file's top-level bindings become top-level bindings of the
+//!   linked program, renamed to `<path>#<name>` (no identifier can spell
+//!   that, so they never collide with the importer's names);
+//! - when the imported body is a block literal — the usual
+//!   `{square: ..., cube: ...}` module shape — each field is hoisted the same
+//!   way as `<path>.<field>`. Exported functions are then ordinary top-level
+//!   functions: typeck generalizes them and the JIT monomorphizes them per
+//!   use, across file boundaries;
+//! - `import("lit").field`, and `m.field` for a top-level `m: import("lit")`,
+//!   become direct references to the hoisted field. Any other use of the
+//!   module is replaced by a block literal that re-assembles it.
+//!
+//! Each module is linked once (keyed by its resolved path) and lands before the
+//! files importing it. Only string-literal paths can be linked; any other
+//! use of `import` is an error.
+//!
+//! The spans of a hoisted module are moved past those of every other file,
+//! into a range of their own, so later passes can report on them:
+//! [`Sources::map`] moves a diagnostic back into the module it came from.
+
+use crate::ast::*;
+use crate::diag::Diagnostic;
+use crate::interp::root_names;
+use crate::lexer::Span;
+use crate::stdlib::imports;
+use crate::symbol::{display, intern, Symbol};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Link `ast` (already resolved) and every module it imports, directly or
+/// not, into one resolved program. Modules are found through the installed
+/// `ImportResolver`. Diagnostics about the linked program go through the
+/// returned `Sources` to point into the right file.
+pub fn link(ast: &Statement) -> Result<(Statement, Sources), Diagnostic> {
+    let mut linker = Linker {
+        defs: Vec::new(),
+        modules: Vec::new(),
+        by_path: HashMap::new(),
+        chain: Vec::new(),
+        sources: Sources::default(),
+    };
+    let (defs, body) = linker.file(ast, None, None)?;
+    let Linked::Expr(body) = body else {
+        unreachable!("the main program is never hoisted")
+    };
+    let mut definitions = linker.defs;
+    definitions.extend(defs);
+    let linked = Statement { definitions, body };
+    let sources = linker.sources;
+    crate::resolver::resolve(&linked, &root_names()).map_err(|d| sources.map(d))?;
+    Ok((linked, sources))
+}
+
+/// How many bytes of span space each linked module gets.
+const MODULE_SPANS: usize = 1 << 32;
+
+/// Where the spans of each linked module came from; see [`Sources::map`].
+#[derive(Default)]
+pub struct Sources {
+    /// By module id: the module's path, the file importing it (`None` for
+    /// the main program) and the span of the `import` call there.
+    modules: Vec<(PathBuf, Option<PathBuf>, Span)>,
+    by_path: HashMap<PathBuf, usize>,
+}
+
+impl Sources {
+    /// Move `d` back into the imported module its span is in, with the
+    /// `import` calls leading there. Diagnostics about the main program
+    /// are returned as they are.
+    pub fn map(&self, mut d: Diagnostic) -> Diagnostic {
+        let id = d.span.start / MODULE_SPANS;
+        if id == 0 || id > self.modules.len() {
+            return d;
+        }
+        let base = id * MODULE_SPANS;
+        let unshift = |span: &Span| span.start - base..span.end - base;
+        d.span = unshift(&d.span);
+        for (span, _) in &mut d.details.labels {
+            if span.start / MODULE_SPANS == id {
+                *span = unshift(span);
+            }
+        }
+        let mut at = Some(id - 1);
+        while let Some(id) = at {
+            let (path, from, span) = &self.modules[id];
+            d = d.imported(path, from.as_deref(), span);
+            at = from.as_ref().and_then(|from| self.by_path.get(from).copied());
+        }
+        d
+    }
+}
+
+/// What a linked file evaluates to.
+enum Module {
+    /// The body was a block literal; each field is hoisted on its own.
+    Record(Vec<(Spanned<Symbol>, Symbol)>),
+    /// Any other body, hoisted whole.
+    Value(Symbol),
+}
+
+enum Linked {
+    Expr(Spanned<Expr>),
+    Module(Module),
+}
+
+/// Where a resolved reference points after linking.
+#[derive(Clone, Copy)]
+enum Target {
+    Keep,
+    Rename(Symbol),
+    Module(usize),
+    Import,
+}
+
+struct Linker {
+    /// Hoisted bindings of every imported file, dependencies first.
+    defs: Vec<Bind>,
+    modules: Vec<Module>,
+    by_path: HashMap<PathBuf, usize>,
+    /// Files being linked, outermost first, for reporting cycles.
+    chain: Vec<PathBuf>,
+    sources: Sources,
+}
+
+struct Cx<'a> {
+    /// The module being rewritten; `None` for the main program.
+    from: Option<&'a Path>,
+    /// One entry per resolver scope, outermost (the root) first. Scopes
+    /// whose bindings stay where they are hold `None`.
+    scopes: Vec<Option<Vec<Target>>>,
+}
+
+impl Cx<'_> {
+    fn lookup(&self, var: &VarRef) -> Target {
+        let Some(bref) = var.resolved.get() else {
+            return Target::Keep;
+        };
+        let idx = self.scopes.len() - 1 - bref.depth as usize;
+        match &self.scopes[idx] {
+            Some(targets) => targets.get(bref.slot as usize).copied().unwrap_or(Target::Keep),
+            None => Target::Keep,
+        }
+    }
+}
+
+impl Linker {
+    /// Rewrite one file. With a `prefix` (an imported file) the top-level
+    /// bindings are renamed and a block body is hoisted field by field.
+    fn file(
+        &mut self,
+        ast: &Statement,
+        from: Option<&Path>,
+        prefix: Option<&str>,
+    ) -> Result<(Vec<Bind>, Linked), Diagnostic> {
+        let root = root_names()
+            .iter()
+            .map(|n| if *n == "import" { Target::Import } else { Target::Keep })
+            .collect();
+        let mut cx = Cx {
+            from,
+            scopes: vec![Some(root), None],
+        };
+
+        // A top-level `m: import("lit")` makes `m` an alias of the module
+        // rather than a binding of its own.
+        let mut top = Vec::with_capacity(ast.definitions.len());
+        for ((name, _), body) in &ast.definitions {
+            top.push(match self.module_ref(body, &cx)? {
+                Some(id) => Target::Module(id),
+                None => match prefix {
+                    Some(p) => Target::Rename(intern(&format!("{p}#{}", display(*name)))),
+                    None => Target::Keep,
+                },
+            });
+        }
+        cx.scopes[1] = Some(top.clone());
+
+        let mut defs = Vec::with_capacity(ast.definitions.len());
+        for (((name, span), body), target) in ast.definitions.iter().zip(&top) {
+            let name = match target {
+                Target::Module(_) => continue,
+                Target::Rename(sym) => *sym,
+                _ => *name,
+            };
+            defs.push(((name, span.clone()), self.expr(body, &mut cx)?));
+        }
+
+        let body = match (&ast.body.0, prefix) {
+            (Expr::Block(fields), Some(p)) => {
+                let hoisted: Vec<(Spanned<Symbol>, Symbol)> = fields
+                    .iter()
+                    .map(|(name, _)| (name.clone(), intern(&format!("{p}.{}", display(name.0)))))
+                    .collect();
+                cx.scopes
+                    .push(Some(hoisted.iter().map(|(_, sym)| Target::Rename(*sym)).collect()));
+                for ((_, body), ((_, span), sym)) in fields.iter().zip(&hoisted) {
+                    defs.push(((*sym, span.clone()), self.expr(body, &mut cx)?));
+                }
+                cx.scopes.pop();
+                Linked::Module(Module::Record(hoisted))
+            }
+            (_, Some(p)) => {
+                let sym = intern(p);
+                defs.push(((sym, ast.body.1.clone()), self.expr(&ast.body, &mut cx)?));
+                Linked::Module(Module::Value(sym))
+            }
+            (_, None) => Linked::Expr(self.expr(&ast.body, &mut cx)?),
+        };
+        Ok((defs, body))
+    }
+
+    /// Link the file `raw` names (once) and return its module id.
+    fn import(&mut self, raw: &str, span: &Span, from: Option<&Path>) -> Result<usize, Diagnostic> {
+        let path = imports::resolve(raw, from, span)?;
+        if let Some(&id) = self.by_path.get(&path) {
+            return Ok(id);
+        }
+        if let Some(d) = imports::cycle_error(&self.chain, &path, span) {
+            return Err(d);
+        }
+
+        let ast = imports::load(&path, from, span)?;
+        let prefix = path.display().to_string();
+        self.chain.push(path.clone());
+        let linked = self
+            .file(&ast, Some(&path), Some(&prefix))
+            .map_err(|d| d.imported(&path, from, span));
+        self.chain.pop();
+        let (mut defs, Linked::Module(mut module)) = linked? else {
+            unreachable!("imported files are always hoisted")
+        };
+
+        let id = self.modules.len();
+        let by = (id + 1) * MODULE_SPANS;
+        shift_binds(&mut defs, by);
+        if let Module::Record(fields) = &mut module {
+            fields.iter_mut().for_each(|(name, _)| shift(&mut name.1, by));
+        }
+        self.defs.extend(defs);
+        self.modules.push(module);
+        self.by_path.insert(path.clone(), id);
+        self.sources.by_path.insert(path.clone(), id);
+        self.sources
+            .modules
+            .push((path, from.map(Path::to_path_buf), span.clone()));
+        Ok(id)
+    }
+
+    /// The module `e` denotes, if it is a literal import or an alias of one.
+    fn module_ref(&mut self, e: &Spanned<Expr>, cx: &Cx) -> Result<Option<usize>, Diagnostic> {
+        match &e.0 {
+            Expr::Variable(var) => Ok(match cx.lookup(var) {
+                Target::Module(id) => Some(id),
+                _ => None,
+            }),
+            Expr::Call(callee, args) => match (&callee.0, args.as_slice()) {
+                (Expr::Variable(var), [(Expr::String(path), _)])
+                    if matches!(cx.lookup(var), Target::Import) =>
+                {
+                    self.import(path, &e.1, cx.from).map(Some)
+                }
+                _ => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn module_expr(&self, id: usize, span: &Span) -> Spanned<Expr> {
+        let var = |sym: Symbol| (Expr::Variable(VarRef::new(sym)), span.clone());
+        match &self.modules[id] {
+            Module::Record(fields) => (
+                Expr::Block(
+                    fields
+                        .iter()
+                        .map(|(name, sym)| (name.clone(), var(*sym)))
+                        .collect(),
+                ),
+                span.clone(),
+            ),
+            Module::Value(sym) => var(*sym),
+        }
+    }
+
+    fn expr(&mut self, e: &Spanned<Expr>, cx: &mut Cx) -> Result<Spanned<Expr>, Diagnostic> {
+        if let Some(id) = self.module_ref(e, cx)? {
+            return Ok(self.module_expr(id, &e.1));
+        }
+        let boxed = |this: &mut Self, e: &Spanned<Expr>, cx: &mut Cx| this.expr(e, cx).map(Box::new);
+        let out = match &e.0 {
+            Expr::Number(_) | Expr::String(_) | Expr::Null | Expr::Bool(_) | Expr::Error => e.0.clone(),
+            Expr::Variable(var) => match cx.lookup(var) {
+                Target::Rename(sym) => Expr::Variable(VarRef::new(sym)),
+                Target::Import => {
+                    return Err(Diagnostic::new(
+                        e.1.clone(),
+                        "JIT: import path must be a string literal",
+                        "cannot link this import at compile time",
+                    )
+                    .with_code("E0501"))
+                }
+                _ => Expr::Variable(VarRef::new(var.name)),
+            },
+            Expr::Interpolation(parts) => Expr::Interpolation(
+                parts
+                    .iter()
+                    .map(|p| match p {
+                        InterpPart::Expr(e) => self.expr(e, cx).map(InterpPart::Expr),
+                        InterpPart::Literal(..) => Ok(p.clone()),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::List(items) => Expr::List(self.exprs(items, cx)?),
+            Expr::Function(params, body) => {
+                cx.scopes.push(None);
+                let body = boxed(self, body, cx);
+                cx.scopes.pop();
+                Expr::Function(params.clone(), body?)
+            }
+            Expr::Block(fields) => {
+                cx.scopes.push(None);
+                let fields = self.binds(fields, cx);
+                cx.scopes.pop();
+                Expr::Block(fields?)
+            }
+            Expr::ImmediateBlock(stmt) => {
+                cx.scopes.push(None);
+                let definitions = self.binds(&stmt.definitions, cx);
+                let body = self.expr(&stmt.body, cx);
+                cx.scopes.pop();
+                Expr::ImmediateBlock(Box::new(Statement {
+                    definitions: definitions?,
+                    body: body?,
+                }))
+            }
+            Expr::If { cond, cons, alt } => Expr::If {
+                cond: boxed(self, cond, cx)?,
+                cons: boxed(self, cons, cx)?,
+                alt: boxed(self, alt, cx)?,
+            },
+            Expr::Binary(op, l, r) => Expr::Binary(*op, boxed(self, l, cx)?, boxed(self, r, cx)?),
+            Expr::Unary(op, operand) => Expr::Unary(*op, boxed(self, operand, cx)?),
+            Expr::Call(callee, args) => Expr::Call(boxed(self, callee, cx)?, self.exprs(args, cx)?),
+            Expr::Access(obj, field) => {
+                if let Some(id) = self.module_ref(obj, cx)? {
+                    if let Module::Record(fields) = &self.modules[id] {
+                        if let Some((_, sym)) = fields.iter().find(|(name, _)| name.0 == field.0) {
+                            return Ok((Expr::Variable(VarRef::new(*sym)), e.1.clone()));
+                        }
+                    }
+                }
+                Expr::Access(boxed(self, obj, cx)?, field.clone())
+            }
+            Expr::OptAccess(obj, field) => Expr::OptAccess(boxed(self, obj, cx)?, field.clone()),
+            Expr::Index(arr, idx) => Expr::Index(boxed(self, arr, cx)?, boxed(self, idx, cx)?),
+            Expr::Match { scrutinee, arms } => {
+                let scrutinee = boxed(self, scrutinee, cx)?;
+                let mut out = Vec::with_capacity(arms.len());
+                for arm in arms {
+                    cx.scopes.push(None);
+                    let guard = arm.guard.as_ref().map(|g| self.expr(g, cx)).transpose();
+                    let body = self.expr(&arm.body, cx);
+                    cx.scopes.pop();
+                    out.push(MatchArm {
+                        pattern: arm.pattern.clone(),
+                        guard: guard?,
+                        body: body?,
+                    });
+                }
+                Expr::Match {
+                    scrutinee,
+                    arms: out,
+                }
+            }
+        };
+        Ok((out, e.1.clone()))
+    }
+
+    fn exprs(&mut self, es: &[Spanned<Expr>], cx: &mut Cx) -> Result<Vec<Spanned<Expr>>, Diagnostic> {
+        es.iter().map(|e| self.expr(e, cx)).collect()
+    }
+
+    fn binds(&mut self, binds: &[Bind], cx: &mut Cx) -> Result<Vec<Bind>, Diagnostic> {
+        binds
+            .iter()
+            .map(|(name, body)| Ok((name.clone(), self.expr(body, cx)?)))
+            .collect()
+    }
+}
+
+/// Move every span in `binds` up by `by`, into the range of spans that
+/// `Sources` maps back to an imported file.
+fn shift_binds(binds: &mut [Bind], by: usize) {
+    for (name, body) in binds {
+        shift(&mut name.1, by);
+        shift_expr(body, by);
+    }
+}
+
+fn shift(span: &mut Span, by: usize) {
+    *span = span.start + by..span.end + by;
+}
+
+fn shift_expr(e: &mut Spanned<Expr>, by: usize) {
+    shift(&mut e.1, by);
+    match &mut e.0 {
+        Expr::Number(_)
+        | Expr::String(_)
+        | Expr::Null
+        | Expr::Bool(_)
+        | Expr::Error
+        | Expr::Variable(_) => {}
+        Expr::Interpolation(parts) => {
+            for part in parts {
+                match part {
+                    InterpPart::Literal(_, span) => shift(span, by),
+                    InterpPart::Expr(e) => shift_expr(e, by),
+                }
+            }
+        }
+        Expr::List(items) => items.iter_mut().for_each(|e| shift_expr(e, by)),
+        Expr::Function(params, body) => {
+            params.iter_mut().for_each(|p| shift(&mut p.1, by));
+            shift_expr(body, by);
+        }
+        Expr::Block(fields) => shift_binds(fields, by),
+        Expr::ImmediateBlock(stmt) => {
+            shift_binds(&mut stmt.definitions, by);
+            shift_expr(&mut stmt.body, by);
+        }
+        Expr::If { cond, cons, alt } => {
+            shift_expr(cond, by);
+            shift_expr(cons, by);
+            shift_expr(alt, by);
+        }
+        Expr::Binary(_, l, r) | Expr::Index(l, r) => {
+            shift_expr(l, by);
+            shift_expr(r, by);
+        }
+        Expr::Unary(_, operand) => shift_expr(operand, by),
+        Expr::Call(callee, args) => {
+            shift_expr(callee, by);
+            args.iter_mut().for_each(|e| shift_expr(e, by));
+        }
+        Expr::Access(obj, field) | Expr::OptAccess(obj, field) => {
+            shift_expr(obj, by);
+            shift(&mut field.1, by);
+        }
+        Expr::Match { scrutinee, arms } => {
+            shift_expr(scrutinee, by);
+            for arm in arms {
+                shift_pattern(&mut arm.pattern, by);
+                if let Some(guard) = &mut arm.guard {
+                    shift_expr(guard, by);
+                }
+                shift_expr(&mut arm.body, by);
+            }
+        }
+    }
+}
+
+fn shift_pattern(p: &mut Spanned<Pattern>, by: usize) {
+    shift(&mut p.1, by);
+    match &mut p.0 {
+        Pattern::Bind(name) => shift(&mut name.1, by),
+        Pattern::List { items, rest } => {
+            items.iter_mut().for_each(|item| shift_pattern(item, by));
+            if let Some(Some(name)) = rest {
+                shift(&mut name.1, by);
+            }
+        }
+        Pattern::Record(fields) => {
+            for (key, sub) in fields {
+                shift(&mut key.1, by);
+                if let Some(sub) = sub {
+                    shift_pattern(sub, by);
+                }
+            }
+        }
+        Pattern::Wildcard
+        | Pattern::Number(_)
+        | Pattern::String(_)
+        | Pattern::Bool(_)
+        | Pattern::Null => {}
+    }
+}
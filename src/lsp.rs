@@ -0,0 +1,698 @@
+//! Language server for `.spc` files (`spctr lsp`), over stdio.
+//!
+//! Every change re-runs the front end on the whole document — parse,
+//! resolve, typeck — and publishes its diagnostics. The parser recovers
+//! from syntax errors, so the rest of the front end still runs on what it
+//! could make of a document being edited. The last analysis is kept for
+//! the requests:
+//!
+//! - hover shows the type typeck inferred for the innermost expression (or
+//!   for a definition's value, on its name);
+//! - go-to-definition follows a variable's `BindRef` to its binder, a field
+//!   access to the field's definition when the record is a literal or an
+//!   imported module, and `import("path")` to the file;
+//! - completion after `.` lists the fields of the receiver's type, record
+//!   or module. The document usually does not parse while a member is being
+//!   typed, so a placeholder member is put at the cursor and the patched
+//!   text is checked instead.
+
+use crate::ast::*;
+use crate::diag::{Diagnostic, Severity};
+use crate::interp;
+use crate::lexer::Span;
+use crate::stdlib::imports;
+use crate::symbol::{display, Symbol};
+use crate::types::Type;
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic as LspDiagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability, Location,
+    MarkupContent, MarkupKind, NumberOrString, OneOf, Position, PublishDiagnosticsParams, Range,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Serve on stdin/stdout until the client shuts the server down.
+pub fn run_stdio() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(&connection)?;
+    // The writer thread only finishes once every sender is gone.
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Serve on `connection`: the initialize handshake, then requests and
+/// notifications until shutdown.
+pub fn serve(connection: &Connection) -> anyhow::Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        documents: HashMap::new(),
+    };
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                let response = server.request(req);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(note) => {
+                if let Some(uri) = server.notification(note)? {
+                    let params = server.diagnostics(&uri);
+                    let note = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+                    connection.sender.send(Message::Notification(note))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    documents: HashMap<Url, Document>,
+}
+
+struct Document {
+    text: String,
+    /// The file the document is, for resolving its imports.
+    path: Option<PathBuf>,
+    analysis: Analysis,
+}
+
+/// The front end's view of a document.
+struct Analysis {
+    /// Boxed so `types`, which is keyed by node address, stays valid.
+    ast: Option<Box<Statement>>,
+    types: HashMap<usize, Type>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn analyze(text: &str, path: Option<&Path>) -> Analysis {
+    let (ast, mut diagnostics) = crate::parser::parse_recovering(text);
+    let Some(ast) = ast.map(Box::new) else {
+        return Analysis {
+            ast: None,
+            types: HashMap::new(),
+            diagnostics,
+        };
+    };
+    if let Err(d) = crate::resolver::resolve(&ast, &interp::root_names()) {
+        diagnostics.push(d);
+        return Analysis {
+            ast: Some(ast),
+            types: HashMap::new(),
+            diagnostics,
+        };
+    }
+    let result = crate::typeck::check_in(&ast, &interp::root_schemes(), path);
+    diagnostics.extend(result.warnings);
+    Analysis {
+        ast: Some(ast),
+        types: result.node_types,
+        diagnostics,
+    }
+}
+
+impl Server {
+    /// Apply a notification; returns the document whose diagnostics changed.
+    fn notification(&mut self, note: Notification) -> anyhow::Result<Option<Url>> {
+        let (uri, text) = match note.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams = serde_json::from_value(note.params)?;
+                (params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams = serde_json::from_value(note.params)?;
+                let Some(change) = params.content_changes.into_iter().next_back() else {
+                    return Ok(None);
+                };
+                (params.text_document.uri, change.text)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams = serde_json::from_value(note.params)?;
+                self.documents.remove(&params.text_document.uri);
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        };
+        let path = uri.to_file_path().ok();
+        let analysis = analyze(&text, path.as_deref());
+        self.documents.insert(uri.clone(), Document { text, path, analysis });
+        Ok(Some(uri))
+    }
+
+    fn diagnostics(&self, uri: &Url) -> PublishDiagnosticsParams {
+        let doc = &self.documents[uri];
+        let diagnostics = doc
+            .analysis
+            .diagnostics
+            .iter()
+            .map(|d| {
+                let details = &d.details;
+                let mut message = format!("{}: {}", d.message, d.label);
+                for note in &details.notes {
+                    message.push_str(&format!("\nnote: {}", note));
+                }
+                if let Some(help) = &details.help {
+                    message.push_str(&format!("\nhelp: {}", help));
+                }
+                let severity = match d.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                    Severity::Note => DiagnosticSeverity::INFORMATION,
+                };
+                let code = d.code.map(|c| NumberOrString::String(c.to_string()));
+                // The secondary labels, as locations in `text` at `uri`.
+                let labels = |uri: &Url, text: &str| -> Vec<_> {
+                    details
+                        .labels
+                        .iter()
+                        .map(|(span, label)| DiagnosticRelatedInformation {
+                            location: Location::new(uri.clone(), range(text, span)),
+                            message: label.clone(),
+                        })
+                        .collect()
+                };
+                let (Some(module), Some((_, import))) = (&details.file, details.imported_from.last()) else {
+                    let related = labels(uri, &doc.text);
+                    return LspDiagnostic {
+                        range: range(&doc.text, &d.span),
+                        severity: Some(severity),
+                        code,
+                        source: Some("spctr".to_string()),
+                        message,
+                        related_information: (!related.is_empty()).then_some(related),
+                        ..Default::default()
+                    };
+                };
+                // Inside an imported module: shown on the import, with
+                // the place in the module alongside.
+                let related = crate::diag::source(module)
+                    .zip(Url::from_file_path(module).ok())
+                    .map(|(text, uri)| {
+                        let at = DiagnosticRelatedInformation {
+                            location: Location::new(uri.clone(), range(&text, &d.span)),
+                            message: message.clone(),
+                        };
+                        std::iter::once(at).chain(labels(&uri, &text)).collect()
+                    });
+                LspDiagnostic {
+                    range: range(&doc.text, import),
+                    severity: Some(severity),
+                    code,
+                    source: Some("spctr".to_string()),
+                    message: format!("in {}: {}", module.display(), message),
+                    related_information: related,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        PublishDiagnosticsParams {
+            uri: uri.clone(),
+            diagnostics,
+            version: None,
+        }
+    }
+
+    fn request(&self, req: Request) -> Response {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            HoverRequest::METHOD => self.handle(req, Self::hover),
+            GotoDefinition::METHOD => self.handle(req, Self::definition),
+            Completion::METHOD => self.handle(req, Self::completion),
+            _ => {
+                return Response::new_err(
+                    id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request {}", req.method),
+                )
+            }
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(e) => Response::new_err(id, lsp_server::ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn handle<P: serde::de::DeserializeOwned, R: serde::Serialize>(
+        &self,
+        req: Request,
+        f: impl Fn(&Self, P) -> Option<R>,
+    ) -> Result<serde_json::Value, serde_json::Error> {
+        let params = serde_json::from_value(req.params)?;
+        serde_json::to_value(f(self, params))
+    }
+
+    fn document(&self, uri: &Url, pos: Position) -> Option<(&Document, usize)> {
+        let doc = self.documents.get(uri)?;
+        let offset = offset(&doc.text, pos);
+        Some((doc, offset))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let at = params.text_document_position_params;
+        let (doc, offset) = self.document(&at.text_document.uri, at.position)?;
+        let ast = doc.analysis.ast.as_deref()?;
+        let (span, ty) = match binder_at(ast, offset) {
+            Some((name, value)) => (name.1.clone(), doc.analysis.types.get(&key(value))?),
+            None => {
+                let expr = expr_at(ast, offset)?;
+                (expr.1.clone(), doc.analysis.types.get(&key(expr))?)
+            }
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```spctr\n{}\n```", ty),
+            }),
+            range: Some(range(&doc.text, &span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let at = params.text_document_position_params;
+        let uri = at.text_document.uri;
+        let (doc, offset) = self.document(&uri, at.position)?;
+        let ast = doc.analysis.ast.as_deref()?;
+        let mut scopes = Scopes::new();
+        let expr = scopes.locate(ast, offset)?;
+        let location = match &expr.0 {
+            Expr::Variable(var) => {
+                let level = scopes.frames.len() - 1;
+                let binder = scopes.binder(var, level)?;
+                Location::new(uri.clone(), range(&doc.text, binder.span.as_ref()?))
+            }
+            Expr::String(_) => {
+                // The path in `import("path")`.
+                let call = scopes.parent_call?;
+                let module = scopes.import_of(call, Some(scopes.frames.len() - 1))?;
+                self.module_location(&module, doc.path.as_deref(), None)?
+            }
+            Expr::Access(obj, (field, field_span)) | Expr::OptAccess(obj, (field, field_span))
+                if span_contains(field_span, offset) =>
+            {
+                match scopes.trace(obj, Some(scopes.frames.len() - 1), 16)? {
+                    Target::Record(defs) => {
+                        let ((_, span), _) = defs.iter().find(|((name, _), _)| name == field)?;
+                        Location::new(uri.clone(), range(&doc.text, span))
+                    }
+                    Target::Module(raw) => {
+                        self.module_location(&raw, doc.path.as_deref(), Some(*field))?
+                    }
+                }
+            }
+            _ => return None,
+        };
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    /// The module `raw` names from `from`, at its body's field `field` when
+    /// there is one.
+    fn module_location(&self, raw: &str, from: Option<&Path>, field: Option<Symbol>) -> Option<Location> {
+        let module = imports::resolve(raw, from, &(0..0)).ok()?;
+        let uri = Url::from_file_path(&module).ok()?;
+        let text = match self.documents.get(&uri) {
+            Some(doc) => doc.text.clone(),
+            None => std::fs::read_to_string(&module).ok()?,
+        };
+        let start = Range::new(Position::new(0, 0), Position::new(0, 0));
+        let span = field.and_then(|field| {
+            let ast = crate::parser::parse_recovering(&text).0?;
+            let Expr::Block(defs) = &ast.body.0 else {
+                return None;
+            };
+            let ((_, span), _) = defs.iter().find(|((name, _), _)| *name == field)?;
+            Some(span.clone())
+        });
+        let range = span.map_or(start, |span| range(&text, &span));
+        Some(Location::new(uri, range))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let at = params.text_document_position;
+        let (doc, offset) = self.document(&at.text_document.uri, at.position)?;
+        let text = &doc.text;
+        let start = text[..offset]
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        if !text[..start].ends_with('.') {
+            return None;
+        }
+        let patched = format!("{}{}{}", &text[..start], PLACEHOLDER, &text[offset..]);
+        let analysis = analyze(&patched, doc.path.as_deref());
+        let ast = analysis.ast.as_deref()?;
+        let receiver = match &expr_at(ast, start)?.0 {
+            Expr::Access(obj, _) | Expr::OptAccess(obj, _) => obj,
+            _ => return None,
+        };
+        let ty = analysis.types.get(&key(receiver))?;
+        let items = fields(ty)
+            .into_iter()
+            .filter(|(name, _)| display(*name) != PLACEHOLDER)
+            .map(|(name, detail)| CompletionItem {
+                label: display(name).to_string(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(detail),
+                ..Default::default()
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+/// The member completion puts at the cursor.
+const PLACEHOLDER: &str = "__spctr_complete__";
+
+/// Fields of a record or module type, with their types.
+fn fields(ty: &Type) -> Vec<(Symbol, String)> {
+    match ty {
+        Type::Record(fields) | Type::Row(fields, _) => {
+            fields.iter().map(|(n, t)| (*n, t.to_string())).collect()
+        }
+        Type::Module(fields) => fields.iter().map(|(n, s)| (*n, s.to_string())).collect(),
+        Type::Nullable(inner) => fields(inner),
+        _ => Vec::new(),
+    }
+}
+
+fn key(expr: &Spanned<Expr>) -> usize {
+    expr as *const Spanned<Expr> as usize
+}
+
+fn span_contains(span: &Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+/// The direct subexpressions of `expr`.
+fn children(expr: &Expr) -> Vec<&Spanned<Expr>> {
+    match expr {
+        Expr::Number(_)
+        | Expr::String(_)
+        | Expr::Variable(_)
+        | Expr::Null
+        | Expr::Bool(_)
+        | Expr::Error => Vec::new(),
+        Expr::Interpolation(parts) => parts
+            .iter()
+            .filter_map(|p| match p {
+                InterpPart::Expr(e) => Some(e),
+                InterpPart::Literal(..) => None,
+            })
+            .collect(),
+        Expr::List(items) => items.iter().collect(),
+        Expr::Function(_, body) => vec![body],
+        Expr::Block(defs) => defs.iter().map(|(_, e)| e).collect(),
+        Expr::ImmediateBlock(stmt) => stmt
+            .definitions
+            .iter()
+            .map(|(_, e)| e)
+            .chain(std::iter::once(&stmt.body))
+            .collect(),
+        Expr::If { cond, cons, alt } => vec![cond, cons, alt],
+        Expr::Binary(_, l, r) => vec![l, r],
+        Expr::Unary(_, e) => vec![e],
+        Expr::Call(callee, args) => std::iter::once(&**callee).chain(args).collect(),
+        Expr::Access(obj, _) | Expr::OptAccess(obj, _) => vec![obj],
+        Expr::Index(arr, idx) => vec![arr, idx],
+        Expr::Match { scrutinee, arms } => std::iter::once(&**scrutinee)
+            .chain(arms.iter().flat_map(|arm| arm.guard.iter().chain(std::iter::once(&arm.body))))
+            .collect(),
+    }
+}
+
+/// The innermost expression whose span contains `offset`.
+fn expr_at(stmt: &Statement, offset: usize) -> Option<&Spanned<Expr>> {
+    fn inner(expr: &Spanned<Expr>, offset: usize) -> Option<&Spanned<Expr>> {
+        if !span_contains(&expr.1, offset) {
+            return None;
+        }
+        children(&expr.0)
+            .into_iter()
+            .find_map(|child| inner(child, offset))
+            .or(Some(expr))
+    }
+    stmt.definitions
+        .iter()
+        .map(|(_, e)| e)
+        .chain(std::iter::once(&stmt.body))
+        .find_map(|e| inner(e, offset))
+}
+
+/// The definition (`name: value`) whose name is at `offset`.
+fn binder_at(stmt: &Statement, offset: usize) -> Option<(&Spanned<Symbol>, &Spanned<Expr>)> {
+    fn in_defs(defs: &[Bind], offset: usize) -> Option<(&Spanned<Symbol>, &Spanned<Expr>)> {
+        defs.iter().find_map(|(name, value)| {
+            if span_contains(&name.1, offset) {
+                Some((name, value))
+            } else {
+                in_expr(value, offset)
+            }
+        })
+    }
+    fn in_expr(expr: &Spanned<Expr>, offset: usize) -> Option<(&Spanned<Symbol>, &Spanned<Expr>)> {
+        if !span_contains(&expr.1, offset) {
+            return None;
+        }
+        match &expr.0 {
+            Expr::Block(defs) => in_defs(defs, offset),
+            Expr::ImmediateBlock(stmt) => {
+                in_defs(&stmt.definitions, offset).or_else(|| in_expr(&stmt.body, offset))
+            }
+            e => children(e).into_iter().find_map(|c| in_expr(c, offset)),
+        }
+    }
+    in_defs(&stmt.definitions, offset).or_else(|| in_expr(&stmt.body, offset))
+}
+
+/// A name bound in some scope, as the resolver numbers them.
+struct Binder<'a> {
+    /// `None` for the roots, which have no source.
+    span: Option<Span>,
+    /// The bound expression, for definitions and record fields.
+    value: Option<&'a Spanned<Expr>>,
+}
+
+/// What a field access reads from, as far as it is known statically.
+enum Target<'a> {
+    Record(&'a [Bind]),
+    /// The module `import("path")` names, by its raw path.
+    Module(String),
+}
+
+/// The resolver's scopes at some point of a program, rebuilt by walking it
+/// the way `resolver::resolve` does, so a `BindRef` can be turned back into
+/// its binder.
+struct Scopes<'a> {
+    frames: Vec<Vec<Binder<'a>>>,
+    /// The call whose argument `locate` stopped at, if it stopped at one.
+    parent_call: Option<&'a Spanned<Expr>>,
+}
+
+impl<'a> Scopes<'a> {
+    fn new() -> Self {
+        let roots = interp::root_names()
+            .iter()
+            .map(|_| Binder {
+                span: None,
+                value: None,
+            })
+            .collect();
+        Self {
+            frames: vec![roots],
+            parent_call: None,
+        }
+    }
+
+    fn defs(defs: &'a [Bind]) -> Vec<Binder<'a>> {
+        defs.iter()
+            .map(|((_, span), value)| Binder {
+                span: Some(span.clone()),
+                value: Some(value),
+            })
+            .collect()
+    }
+
+    /// The innermost expression at `offset`, leaving `frames` as the
+    /// resolver had them there.
+    fn locate(&mut self, stmt: &'a Statement, offset: usize) -> Option<&'a Spanned<Expr>> {
+        self.frames.push(Self::defs(&stmt.definitions));
+        let found = stmt
+            .definitions
+            .iter()
+            .map(|(_, e)| e)
+            .chain(std::iter::once(&stmt.body))
+            .find_map(|e| self.visit(e, offset));
+        if found.is_none() {
+            self.frames.pop();
+        }
+        found
+    }
+
+    fn visit(&mut self, expr: &'a Spanned<Expr>, offset: usize) -> Option<&'a Spanned<Expr>> {
+        if !span_contains(&expr.1, offset) {
+            return None;
+        }
+        let pushed = match &expr.0 {
+            Expr::Function(params, _) => {
+                let params = params
+                    .iter()
+                    .map(|(_, span)| Binder {
+                        span: Some(span.clone()),
+                        value: None,
+                    })
+                    .collect();
+                self.frames.push(params);
+                1
+            }
+            Expr::Block(defs) => {
+                self.frames.push(Self::defs(defs));
+                1
+            }
+            Expr::ImmediateBlock(stmt) => return self.locate(stmt, offset).or(Some(expr)),
+            Expr::Match { scrutinee, arms } => {
+                if let Some(found) = self.visit(scrutinee, offset) {
+                    return Some(found);
+                }
+                for arm in arms {
+                    let mut names = Vec::new();
+                    arm.pattern.0.binders(&mut names);
+                    let binders = names
+                        .into_iter()
+                        .map(|(_, span)| Binder {
+                            span: Some(span),
+                            value: None,
+                        })
+                        .collect();
+                    self.frames.push(binders);
+                    let found = arm
+                        .guard
+                        .iter()
+                        .chain(std::iter::once(&arm.body))
+                        .find_map(|e| self.visit(e, offset));
+                    if found.is_some() {
+                        return found;
+                    }
+                    self.frames.pop();
+                }
+                return Some(expr);
+            }
+            _ => 0,
+        };
+        for child in children(&expr.0) {
+            if let Some(found) = self.visit(child, offset) {
+                if self.parent_call.is_none() && matches!(expr.0, Expr::Call(..)) {
+                    self.parent_call = Some(expr);
+                }
+                return Some(found);
+            }
+        }
+        self.frames.truncate(self.frames.len() - pushed);
+        Some(expr)
+    }
+
+    /// The binder of `var`, seen from the scope at `level`.
+    fn binder(&self, var: &VarRef, level: usize) -> Option<&Binder<'a>> {
+        let bref = var.resolved.get()?;
+        let frame = level.checked_sub(bref.depth as usize)?;
+        self.frames.get(frame)?.get(bref.slot as usize)
+    }
+
+    /// The raw path of `import("path")`, when `call` is one. `level` is the
+    /// scope `call` is in, if known, to check `import` is the builtin.
+    fn import_of(&self, call: &Spanned<Expr>, level: Option<usize>) -> Option<String> {
+        let Expr::Call(callee, args) = &call.0 else {
+            return None;
+        };
+        let (Expr::Variable(var), [(Expr::String(raw), _)]) = (&callee.0, args.as_slice()) else {
+            return None;
+        };
+        if display(var.name) != "import" {
+            return None;
+        }
+        if let (Some(level), Some(bref)) = (level, var.resolved.get()) {
+            if level.checked_sub(bref.depth as usize) != Some(0) {
+                return None;
+            }
+        }
+        Some(raw.to_string())
+    }
+
+    /// What `expr` evaluates to, when that is a record literal or a module.
+    /// Variables are followed while their scope is known (`level`); `fuel`
+    /// bounds the chain.
+    fn trace(&self, expr: &'a Spanned<Expr>, level: Option<usize>, fuel: u32) -> Option<Target<'a>> {
+        let fuel = fuel.checked_sub(1)?;
+        match &expr.0 {
+            Expr::Block(defs) => Some(Target::Record(defs)),
+            Expr::Call(..) => self.import_of(expr, level).map(Target::Module),
+            Expr::Variable(var) => {
+                let level = level?;
+                let bref = var.resolved.get()?;
+                let value = self.binder(var, level)?.value?;
+                self.trace(value, Some(level - bref.depth as usize), fuel)
+            }
+            Expr::Access(obj, (field, _)) => match self.trace(obj, level, fuel)? {
+                Target::Record(defs) => {
+                    let (_, value) = defs.iter().find(|((name, _), _)| name == field)?;
+                    self.trace(value, None, fuel)
+                }
+                Target::Module(_) => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The byte offset of `pos` (UTF-16 based, as LSP counts) in `text`.
+fn offset(text: &str, pos: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..pos.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line = &text[line_start..];
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= pos.character as usize || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    Position::new(line as u32, character as u32)
+}
+
+fn range(text: &str, span: &Span) -> Range {
+    Range::new(position(text, span.start), position(text, span.end))
+}
+
@@ -1,33 +1,40 @@
-use anyhow::Result;
-use clap::Parser;
-use spctr::{diag, interp, jit, parser, resolver, stdlib::imports, typeck};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use spctr::manifest::{self, Format, Options};
+use spctr::interp::HostRoot;
+use spctr::{convert, diag, interp, jit, parser, resolver, stdlib::imports, symbol, typeck};
 
 use std::fs;
+use std::io::{Read as _, Write as _};
+use std::path::PathBuf;
 use std::process::ExitCode;
+use std::rc::Rc;
 use std::thread;
 
 /// Runtime stack size for the interpreter thread.
 ///
-/// Tail-recursive code now goes through the TCO loop in `interp::interpret`
-/// without growing the Rust stack at all, so an accumulator-style
-/// `loop(N, acc) => ...` happily reaches 1M+ iterations regardless of this
-/// limit. The remaining stack consumer is *non*-tail recursion (e.g.
-/// `count(n) => if n == 0 then 0 else count(n - 1) + 1`), which still
-/// builds one Rust frame per spctr call. 8 MiB matches the default Linux
-/// pthread stack and comfortably covers `fib(38)` plus non-tail recursion
-/// up to ~5000 levels — well beyond anything the test suite or examples
-/// exercise. A full iterative trampoline (Phase ζ continuation) would
-/// remove the limit entirely.
+/// Evaluation itself no longer uses the Rust stack for spctr-level
+/// recursion: `interp::interpret` keeps its continuations on the heap, so
+/// both tail and non-tail recursion are bounded only by `--max-depth`. What
+/// still recurses natively is the front end (parser, resolver, typeck) and
+/// the JIT compiler, whose depth follows the *nesting of the source*, plus
+/// printing of deeply nested values. 8 MiB matches the default Linux
+/// pthread stack and is plenty for those.
 const INTERP_STACK_SIZE: usize = 8 * 1024 * 1024;
 
 #[derive(Parser)]
-#[command(name = "spctr")]
+#[command(name = "spctr", args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// Source file to evaluate.
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Source file to evaluate, or `-` for stdin.
     file: Option<String>,
     /// Inline source.
-    #[arg(short = 'c', long = "input")]
+    #[arg(short = 'c', short_alias = 'e', long = "input")]
     input: Option<String>,
+    /// Bind `input` to the JSON document in FILE (`-` for stdin).
+    #[arg(long, value_name = "FILE", conflicts_with = "jit")]
+    input_json: Option<String>,
     /// Start the REPL.
     #[arg(long)]
     repl: bool,
@@ -40,13 +47,197 @@ struct Cli {
     /// Run via the Cranelift JIT.
     #[arg(long)]
     jit: bool,
+    /// Maximum depth of nested (non-tail) calls in the interpreter.
+    #[arg(long, value_name = "N", default_value_t = interp::DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+    /// How to print the result.
+    #[arg(long, value_enum, default_value_t = Output::Display, conflicts_with = "jit")]
+    output: Output,
+    /// Write each field of the top-level record to its own file in DIR,
+    /// named after the field, as JSON unless `--output` says otherwise.
+    #[arg(long, value_name = "DIR", conflicts_with = "jit")]
+    multi: Option<PathBuf>,
+    /// Bind `args.NAME` to the string VALUE.
+    #[arg(long, value_name = "NAME=VALUE", value_parser = binding, conflicts_with = "jit")]
+    arg: Vec<(String, String)>,
+    /// Bind `args.NAME` to the JSON value JSON.
+    #[arg(long, value_name = "NAME=JSON", value_parser = binding, conflicts_with = "jit")]
+    arg_json: Vec<(String, String)>,
+    /// Bind `args.NAME` to the contents of the file PATH, as a string.
+    #[arg(long, value_name = "NAME=PATH", value_parser = binding, conflicts_with = "jit")]
+    arg_file: Vec<(String, String)>,
+    /// Print JSON on a single line (other formats ignore this).
+    #[arg(long, conflicts_with = "jit")]
+    compact: bool,
+    /// Print record keys in sorted order instead of declaration order.
+    #[arg(long, conflicts_with = "jit")]
+    sort_keys: bool,
+    /// Explain a diagnostic code, such as E0201, and exit.
+    #[arg(long, value_name = "CODE")]
+    explain: Option<String>,
+    /// How to write diagnostics to stderr.
+    #[arg(long, value_enum, global = true, default_value_t = DiagnosticsFormat::Text)]
+    diagnostics_format: DiagnosticsFormat,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Evaluate EXPR against a JSON document and print the result as JSON.
+    Query(QueryArgs),
+    /// Run the language server over stdin/stdout.
+    Lsp,
+    /// Reformat source files in place, or stdin to stdout.
+    Fmt(FmtArgs),
+}
+
+#[derive(Args)]
+struct FmtArgs {
+    /// Files to format; `-` (the default) formats stdin to stdout.
+    #[arg(default_value = "-")]
+    files: Vec<String>,
+    /// Write nothing; list the files that are not formatted and fail if any.
+    #[arg(long)]
+    check: bool,
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// The query. `.` is the document and `.field` a field of it.
+    expr: String,
+    /// The JSON document, or `-` for stdin (the default).
+    #[arg(default_value = "-")]
+    file: String,
+    /// Also bind the document to NAME.
+    #[arg(long, value_name = "NAME", default_value = "input")]
+    name: String,
+    /// Print a list result as one JSON array instead of one element per line.
+    #[arg(long)]
+    no_stream: bool,
+    /// Print JSON on a single line.
+    #[arg(long)]
+    compact: bool,
+    /// Print record keys in sorted order instead of declaration order.
+    #[arg(long)]
+    sort_keys: bool,
+}
+
+fn binding(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got `{}`", s)),
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Output {
+    /// spctr's own notation; functions print as `[function]`.
+    Display,
+    /// Strict RFC 8259 JSON.
+    Json,
+    /// Block-style YAML.
+    Yaml,
+    /// TOML; the result must be a record.
+    Toml,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DiagnosticsFormat {
+    /// Source excerpts with the problem marked.
+    Text,
+    /// One JSON object per diagnostic, per line.
+    Json,
+    /// A SARIF 2.1.0 log, written once everything is checked.
+    Sarif,
 }
 
 fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
+    let diagnostics = match cli.diagnostics_format {
+        DiagnosticsFormat::Text => diag::Format::Text,
+        DiagnosticsFormat::Json => diag::Format::Json,
+        DiagnosticsFormat::Sarif => diag::Format::Sarif,
+    };
+    match cli.command {
+        Some(Command::Query(query)) => {
+            let document = read(&query.file)?;
+            let handle = thread::Builder::new()
+                .stack_size(INTERP_STACK_SIZE)
+                .spawn(move || reporting(diagnostics, || run_query(query, document)))?;
+            return handle.join().expect("interpreter thread panicked");
+        }
+        Some(Command::Lsp) => {
+            let handle = thread::Builder::new()
+                .stack_size(INTERP_STACK_SIZE)
+                .spawn(spctr::lsp::run_stdio)?;
+            handle.join().expect("language server thread panicked")?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Fmt(args)) => {
+            let handle = thread::Builder::new()
+                .stack_size(INTERP_STACK_SIZE)
+                .spawn(move || reporting(diagnostics, || run_fmt(args)))?;
+            return handle.join().expect("formatter thread panicked");
+        }
+        None => {}
+    }
+    if let Some(code) = cli.explain {
+        let code = code.to_ascii_uppercase();
+        let text = spctr::codes::explain(&code)
+            .ok_or_else(|| anyhow!("no such diagnostic code: {}", code))?;
+        println!("{}: {}", code, text);
+        return Ok(ExitCode::SUCCESS);
+    }
     let show_type = cli.show_type;
     let only_check = cli.check;
     let use_jit = cli.jit;
+    let max_depth = cli.max_depth;
+    let opts = Options {
+        pretty: !cli.compact,
+        sort_keys: cli.sort_keys,
+    };
+    let format = match cli.output {
+        Output::Display => None,
+        Output::Json => Some(Format::Json),
+        Output::Yaml => Some(Format::Yaml),
+        Output::Toml => Some(Format::Toml),
+    };
+    let emit = match (cli.multi, format) {
+        (Some(dir), format) => Emit::Multi(dir, format.unwrap_or(Format::Json), opts),
+        (None, Some(format)) => Emit::Print(format, opts),
+        (None, None) => Emit::Display,
+    };
+
+    let mut given: Vec<(String, serde_json::Value)> = Vec::new();
+    for (name, s) in cli.arg {
+        given.push((name, s.into()));
+    }
+    for (name, json) in cli.arg_json {
+        let v = serde_json::from_str(&json).with_context(|| format!("--arg-json {}", name))?;
+        given.push((name, v));
+    }
+    for (name, path) in cli.arg_file {
+        let s = fs::read_to_string(&path).with_context(|| format!("--arg-file {}", name))?;
+        given.push((name, s.into()));
+    }
+    let args = if given.is_empty() {
+        None
+    } else {
+        let mut args = serde_json::Map::new();
+        for (name, value) in given {
+            if args.insert(name.clone(), value).is_some() {
+                bail!("argument `{}` is given more than once", name);
+            }
+        }
+        Some(serde_json::Value::Object(args))
+    };
+
+    if cli.file.as_deref() == Some("-") && cli.input_json.as_deref() == Some("-") {
+        bail!("the program and --input-json cannot both be read from stdin");
+    }
+    let input = match cli.input_json {
+        Some(path) => Some((display_name(&path), read(&path)?)),
+        None => None,
+    };
 
     let mode = if cli.repl {
         Mode::Repl
@@ -57,8 +248,8 @@ fn main() -> Result<ExitCode> {
         }
     } else if let Some(path) = cli.file {
         Mode::Source {
-            source: fs::read_to_string(&path)?,
-            filename: path,
+            source: read(&path)?,
+            filename: display_name(&path),
         }
     } else {
         Mode::Repl
@@ -66,19 +257,131 @@ fn main() -> Result<ExitCode> {
 
     let handle = thread::Builder::new()
         .stack_size(INTERP_STACK_SIZE)
-        .spawn(move || run(mode, show_type, only_check, use_jit))?;
+        .spawn(move || {
+            interp::set_max_depth(max_depth);
+            reporting(diagnostics, || {
+                if !install_roots(args, input)? {
+                    return Ok(ExitCode::FAILURE);
+                }
+                run(mode, show_type, only_check, use_jit, emit)
+            })
+        })?;
     handle.join().expect("interpreter thread panicked")
 }
 
+/// Run `f` with diagnostics reported as `format`, then write out whatever
+/// the format holds back until the end.
+fn reporting(format: diag::Format, f: impl FnOnce() -> Result<ExitCode>) -> Result<ExitCode> {
+    diag::set_format(format);
+    let result = f();
+    diag::finish();
+    result
+}
+
+/// `spctr fmt`: format each file, or with `--check` only report the ones
+/// formatting would change.
+fn run_fmt(args: FmtArgs) -> Result<ExitCode> {
+    let mut ok = true;
+    for path in &args.files {
+        let source = read(path)?;
+        let formatted = match spctr::fmt::format(&source) {
+            Ok(formatted) => formatted,
+            Err(diags) => {
+                for d in &diags {
+                    diag::report(&display_name(path), &source, d);
+                }
+                ok = false;
+                continue;
+            }
+        };
+        if args.check {
+            if formatted != source {
+                eprintln!("{} is not formatted", display_name(path));
+                ok = false;
+            }
+        } else if path == "-" {
+            print!("{}", formatted);
+        } else if formatted != source {
+            fs::write(path, formatted).with_context(|| format!("cannot write {}", path))?;
+        }
+    }
+    Ok(if ok { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+/// The contents of `path`, or of stdin for `-`.
+fn read(path: &str) -> Result<String> {
+    if path == "-" {
+        let mut s = String::new();
+        std::io::stdin().read_to_string(&mut s)?;
+        Ok(s)
+    } else {
+        fs::read_to_string(path).with_context(|| format!("cannot read {}", path))
+    }
+}
+
+/// How diagnostics name `path`.
+fn display_name(path: &str) -> String {
+    if path == "-" {
+        "<stdin>".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Install the root bindings the command line provides, typed so that
+/// `--check` knows them: with any `--arg*` flag, `args`, the record of
+/// their values, and with `--input-json`, `input`, the document
+/// `(filename, source)`. Returns `false` (with its diagnostics reported)
+/// when the document is not JSON.
+fn install_roots(args: Option<serde_json::Value>, input: Option<(String, String)>) -> Result<bool> {
+    let mut roots = Vec::new();
+    if let Some(args) = args {
+        let args = interp::Value::from(args);
+        roots.push(HostRoot {
+            name: symbol::intern("args"),
+            scheme: convert::data_scheme(&args),
+            value: args,
+        });
+    }
+    if let Some((filename, source)) = input {
+        match convert::parse_json(&source) {
+            Ok((value, scheme)) => roots.push(HostRoot {
+                name: symbol::intern("input"),
+                scheme,
+                value,
+            }),
+            Err(d) => {
+                diag::report(&filename, &source, &d);
+                return Ok(false);
+            }
+        }
+    }
+    interp::set_host_roots(Rc::new(roots));
+    Ok(true)
+}
+
 enum Mode {
     Source { filename: String, source: String },
     Repl,
 }
 
-fn run(mode: Mode, show_type: bool, only_check: bool, use_jit: bool) -> Result<ExitCode> {
+/// What to do with the result.
+enum Emit {
+    Display,
+    Print(Format, Options),
+    Multi(PathBuf, Format, Options),
+}
+
+fn run(
+    mode: Mode,
+    show_type: bool,
+    only_check: bool,
+    use_jit: bool,
+    emit: Emit,
+) -> Result<ExitCode> {
     match mode {
         Mode::Source { filename, source } => {
-            run_source(&filename, &source, show_type, only_check, use_jit)
+            run_source(&filename, &source, show_type, only_check, use_jit, emit)
         }
         Mode::Repl => run_repl(),
     }
@@ -90,10 +393,11 @@ fn run_source(
     show_type: bool,
     only_check: bool,
     use_jit: bool,
+    emit: Emit,
 ) -> Result<ExitCode> {
     if let Some(parent) = std::path::Path::new(filename).parent() {
         if !parent.as_os_str().is_empty() {
-            imports::set_current_dir(parent.to_path_buf());
+            imports::set_resolver(Rc::new(imports::FsResolver::new(parent)));
         }
     }
     let ast = match parser::parse(source) {
@@ -106,25 +410,33 @@ fn run_source(
         }
     };
 
-    if let Err(d) = resolver::resolve(&ast, &interp::ROOT_NAMES) {
+    if let Err(mut d) = resolver::resolve(&ast, &interp::root_names()) {
+        // Only the `--arg*` flags bind `args`, and they cannot go with `--jit`.
+        if use_jit && d.code == Some("E0101") && source.get(d.span.clone()) == Some("args") {
+            d = diag::Diagnostic::new(d.span, "`args` is not supported with --jit", "not available")
+                .with_code("E0501")
+                .with_note("`args` is bound by --arg, --arg-json and --arg-file, which the JIT does not take");
+        }
         diag::report(filename, source, &d);
         return Ok(ExitCode::FAILURE);
     }
 
     if show_type || only_check {
-        let root_types = interp::root_types();
-        let result = typeck::check(&ast, &root_types);
-        for w in &result.warnings {
-            diag::report(filename, source, w);
+        let result = typeck::check_in(&ast, &interp::root_schemes(), None);
+        // The JIT reports them itself when it runs the program.
+        if only_check || !use_jit {
+            for w in &result.warnings {
+                diag::report(filename, source, w);
+            }
         }
         if show_type {
             println!("type: {}", result.program_type);
         }
         if only_check {
-            return Ok(if result.warnings.is_empty() {
-                ExitCode::SUCCESS
-            } else {
+            return Ok(if result.has_errors() {
                 ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
             });
         }
     }
@@ -133,7 +445,11 @@ fn run_source(
         // JIT prints the value internally via `spctr_print` so it can render
         // any program type (record / list / string / etc.) without forcing
         // `__spctr_main` to vary its return ABI.
-        return match jit::run_with_display(&ast) {
+        let report: jit::Report = {
+            let (filename, source) = (filename.to_string(), source.to_string());
+            Rc::new(move |d| diag::report(&filename, &source, d))
+        };
+        return match jit::run_with_display(&ast, report) {
             Ok(()) => Ok(ExitCode::SUCCESS),
             Err(d) => {
                 diag::report(filename, source, &d);
@@ -142,9 +458,38 @@ fn run_source(
         };
     }
 
-    match interp::run(&ast) {
-        Ok(v) => {
-            println!("{}", v);
+    let emitted = interp::run(&ast).and_then(|v| match &emit {
+        Emit::Display => Ok(vec![(None, v.to_string())]),
+        Emit::Print(format, opts) => {
+            Ok(vec![(None, manifest::render(&v, *format, opts, &ast.body.1)?)])
+        }
+        Emit::Multi(dir, format, opts) => {
+            let node = manifest::manifest(&v, opts.sort_keys, &ast.body.1)?;
+            manifest::split(node)?
+                .into_iter()
+                .map(|(name, field)| {
+                    let text = manifest::render_node(&field, *format, opts)?;
+                    Ok((Some(dir.join(name)), text))
+                })
+                .collect()
+        }
+    });
+    match emitted {
+        Ok(outputs) => {
+            // Everything is rendered before anything is written, so a
+            // failing field leaves no partial output directory behind.
+            for (path, text) in outputs {
+                match path {
+                    Some(path) => {
+                        if let Some(dir) = path.parent() {
+                            fs::create_dir_all(dir)?;
+                        }
+                        fs::write(&path, text + "\n")?;
+                        println!("{}", path.display());
+                    }
+                    None => println!("{}", text),
+                }
+            }
             Ok(ExitCode::SUCCESS)
         }
         Err(d) => {
@@ -154,6 +499,89 @@ fn run_source(
     }
 }
 
+/// `spctr query`: the document is converted straight to a value and bound
+/// at the root as `.` (and `--name`), typed from its contents, so the query
+/// is checked before it runs. A list
+/// result is streamed as JSON Lines, one element at a time.
+fn run_query(query: QueryArgs, document: String) -> Result<ExitCode> {
+    let filename = display_name(&query.file);
+    let (value, scheme) = match convert::parse_json(&document) {
+        Ok(parsed) => parsed,
+        Err(d) => {
+            diag::report(&filename, &document, &d);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let mut roots = vec![HostRoot {
+        name: symbol::intern("."),
+        scheme: scheme.clone(),
+        value: value.clone(),
+    }];
+    if query.name != "." {
+        roots.push(HostRoot {
+            name: symbol::intern(&query.name),
+            scheme,
+            value,
+        });
+    }
+    interp::set_host_roots(Rc::new(roots));
+
+    let source = &query.expr;
+    let report = |diags: &[diag::Diagnostic]| {
+        for d in diags {
+            diag::report("<query>", source, d);
+        }
+        Ok(ExitCode::FAILURE)
+    };
+    let ast = match parser::parse_query(source) {
+        Ok(ast) => ast,
+        Err(diags) => return report(&diags),
+    };
+    if let Err(d) = resolver::resolve(&ast, &interp::root_names()) {
+        return report(&[d]);
+    }
+    let checked = typeck::check_in(&ast, &interp::root_schemes(), None);
+    if checked.has_errors() {
+        return report(&checked.warnings);
+    }
+    for d in &checked.warnings {
+        diag::report("<query>", source, d);
+    }
+    let result = match interp::run(&ast) {
+        Ok(v) => v,
+        Err(d) => return report(&[d]),
+    };
+
+    let opts = Options {
+        pretty: !query.compact,
+        sort_keys: query.sort_keys,
+    };
+    let span = &ast.body.1;
+    match &result {
+        interp::Value::List(items) if !query.no_stream => {
+            let lines = Options {
+                pretty: false,
+                ..opts
+            };
+            let mut out = std::io::stdout().lock();
+            for item in items.iter() {
+                match manifest::render(item, Format::Json, &lines, span) {
+                    Ok(line) => {
+                        writeln!(out, "{}", line)?;
+                        out.flush()?;
+                    }
+                    Err(d) => return report(&[d]),
+                }
+            }
+        }
+        v => match manifest::render(v, Format::Json, &opts, span) {
+            Ok(text) => println!("{}", text),
+            Err(d) => return report(&[d]),
+        },
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
 fn run_repl() -> Result<ExitCode> {
     use rustyline::error::ReadlineError;
     use rustyline::DefaultEditor;
@@ -218,7 +646,7 @@ fn evaluate_repl_line(line: &str, accumulated: &mut Vec<spctr::ast::Bind>) {
         body: stmt.body.clone(),
     };
 
-    if let Err(d) = resolver::resolve(&combined, &interp::ROOT_NAMES) {
+    if let Err(d) = resolver::resolve(&combined, &interp::root_names()) {
         diag::report("<repl>", line, &d);
         return;
     }
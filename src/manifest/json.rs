@@ -0,0 +1,61 @@
+//! Strict JSON (RFC 8259), for `--output json`. Non-finite numbers have no
+//! JSON form and are an error.
+
+use super::{quote, shortest, Kind, Node};
+use crate::diag::Diagnostic;
+use std::fmt::Write as _;
+
+pub fn render(node: &Node, pretty: bool) -> Result<String, Diagnostic> {
+    let mut out = String::new();
+    value(&mut out, node, pretty, 0)?;
+    Ok(out)
+}
+
+fn value(out: &mut String, node: &Node, pretty: bool, depth: usize) -> Result<(), Diagnostic> {
+    match &node.kind {
+        Kind::Null => out.push_str("null"),
+        Kind::Bool(b) => write!(out, "{}", b).expect("write to String"),
+        Kind::Integer(i) => write!(out, "{}", i).expect("write to String"),
+        Kind::Number(n) if n.is_finite() => out.push_str(&shortest(*n)),
+        Kind::Number(n) => return Err(node.error(format!("cannot encode {} as JSON", n))),
+        Kind::String(s) => quote(out, s),
+        Kind::List(xs) if xs.is_empty() => out.push_str("[]"),
+        Kind::List(xs) => {
+            out.push('[');
+            for (i, x) in xs.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, pretty, depth + 1);
+                value(out, x, pretty, depth + 1)?;
+            }
+            newline(out, pretty, depth);
+            out.push(']');
+        }
+        Kind::Record(fields) if fields.is_empty() => out.push_str("{}"),
+        Kind::Record(fields) => {
+            out.push('{');
+            for (i, (name, field)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, pretty, depth + 1);
+                quote(out, name);
+                out.push_str(if pretty { ": " } else { ":" });
+                value(out, field, pretty, depth + 1)?;
+            }
+            newline(out, pretty, depth);
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn newline(out: &mut String, pretty: bool, depth: usize) {
+    if pretty {
+        out.push('\n');
+        for _ in 0..depth {
+            out.push_str("  ");
+        }
+    }
+}
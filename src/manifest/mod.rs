@@ -0,0 +1,209 @@
+//! Output manifests for `--output` and `--multi`.
+//!
+//! A program's result is first forced into a [`Node`] tree: plain data with
+//! every lazy field evaluated, record keys in the requested order, integers
+//! told apart from other numbers, and the span and path of each value kept
+//! for errors. Functions, non-record frames and values nested deeper than
+//! [`MAX_DEPTH`] are rejected here, so the renderers and serde can walk the
+//! tree recursively; what else
+//! an encoding cannot represent (JSON's non-finite numbers, TOML's nulls) is
+//! up to its renderer. `Value`'s `Serialize` goes through the same tree, so
+//! serde sees what `--output --sort-keys` writes.
+
+pub mod json;
+pub mod toml;
+pub mod yaml;
+
+use crate::diag::Diagnostic;
+use crate::interp::Value;
+use crate::lexer::Span;
+use crate::symbol::display;
+use std::fmt::Write as _;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// JSON only: indent nested values by two spaces, one entry per line.
+    pub pretty: bool,
+    /// Write record keys in sorted order instead of declaration order.
+    pub sort_keys: bool,
+}
+
+/// A forced value.
+pub struct Node {
+    /// The expression the value came from: its field's definition when that
+    /// is known, otherwise the nearest enclosing one.
+    pub span: Span,
+    /// Where the value sits in the result, as `.field[index]`.
+    pub path: String,
+    pub kind: Kind,
+}
+
+pub enum Kind {
+    Null,
+    Bool(bool),
+    /// A number that is an integer `i64` holds exactly; see `as_integer`.
+    Integer(i64),
+    /// Any other number.
+    Number(f64),
+    String(Rc<String>),
+    List(Vec<Node>),
+    Record(Vec<(&'static str, Node)>),
+}
+
+impl Node {
+    pub fn type_name(&self) -> &'static str {
+        match self.kind {
+            Kind::Null => "null",
+            Kind::Bool(_) => "bool",
+            Kind::Integer(_) | Kind::Number(_) => "number",
+            Kind::String(_) => "string",
+            Kind::List(_) => "list",
+            Kind::Record(_) => "record",
+        }
+    }
+
+    /// A `Diagnostic` at this value, labelled with its path.
+    pub fn error(&self, message: impl Into<String>) -> Diagnostic {
+        error(&self.span, &self.path, message)
+    }
+}
+
+fn error(span: &Span, path: &str, message: impl Into<String>) -> Diagnostic {
+    let at = if path.is_empty() { "the result" } else { path };
+    Diagnostic::new(span.clone(), message, format!("at {}", at)).with_code("E0601")
+}
+
+/// Encode `value` as `format`. `span` is where the value came from,
+/// typically the program body.
+pub fn render(value: &Value, format: Format, opts: &Options, span: &Span) -> Result<String, Diagnostic> {
+    render_node(&manifest(value, opts.sort_keys, span)?, format, opts)
+}
+
+pub fn render_node(node: &Node, format: Format, opts: &Options) -> Result<String, Diagnostic> {
+    match format {
+        Format::Json => json::render(node, opts.pretty),
+        Format::Yaml => yaml::render(node),
+        Format::Toml => toml::render(node),
+    }
+}
+
+/// The files `--multi` writes for `node`: one per field of the top-level
+/// record, named after the field. Names that are not a plain file name
+/// (empty, `.`, `..`, or containing a path separator) are an error.
+pub fn split(node: Node) -> Result<Vec<(&'static str, Node)>, Diagnostic> {
+    let Kind::Record(fields) = node.kind else {
+        return Err(node.error(format!(
+            "--multi needs a record at the top level, got {}",
+            node.type_name()
+        )));
+    };
+    for (name, field) in &fields {
+        if matches!(*name, "" | "." | "..") || name.contains(['/', '\\']) {
+            return Err(field.error(format!("{:?} is not a valid file name", name)));
+        }
+    }
+    Ok(fields)
+}
+
+/// How deep lists and records may nest in a manifest.
+pub const MAX_DEPTH: usize = 512;
+
+/// Force `value` into a [`Node`] tree.
+pub fn manifest(value: &Value, sort_keys: bool, span: &Span) -> Result<Node, Diagnostic> {
+    build(value, sort_keys, span, String::new(), 0)
+}
+
+fn build(value: &Value, sort_keys: bool, span: &Span, path: String, depth: usize) -> Result<Node, Diagnostic> {
+    if depth == MAX_DEPTH && matches!(value, Value::List(_) | Value::Block(_)) {
+        return Err(error(
+            span,
+            &path,
+            format!("cannot encode a value nested more than {} levels deep", MAX_DEPTH),
+        ));
+    }
+    let kind = match value {
+        Value::Null => Kind::Null,
+        Value::Bool(b) => Kind::Bool(*b),
+        Value::Number(n) => match as_integer(*n) {
+            Some(i) => Kind::Integer(i),
+            None => Kind::Number(*n),
+        },
+        Value::String(s) => Kind::String(s.clone()),
+        Value::Function(_) => return Err(error(span, &path, "cannot encode a function")),
+        Value::List(xs) => Kind::List(
+            xs.iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    let mut at = path.clone();
+                    write!(at, "[{}]", i).expect("write to String");
+                    build(x, sort_keys, span, at, depth + 1)
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Block(frame) => {
+            let Some(mut fields) = frame.fields() else {
+                return Err(error(span, &path, "cannot encode a non-record frame"));
+            };
+            if sort_keys {
+                fields.sort_by_key(|(name, _)| display(*name));
+            }
+            let mut out = Vec::with_capacity(fields.len());
+            for (name, slot) in fields {
+                let field_span = frame.span(slot).unwrap_or_else(|| span.clone());
+                let field = frame.get(slot)?;
+                let at = format!("{}.{}", path, display(name));
+                out.push((display(name), build(&field, sort_keys, &field_span, at, depth + 1)?));
+            }
+            Kind::Record(out)
+        }
+    };
+    Ok(Node {
+        span: span.clone(),
+        path,
+        kind,
+    })
+}
+
+/// `n` as an integer, when it is one that `i64` holds exactly.
+pub(crate) fn as_integer(n: f64) -> Option<i64> {
+    (n.fract() == 0.0 && n.abs() < 9.007_199_254_740_992e15).then_some(n as i64)
+}
+
+/// A finite non-integer `n` in the shortest form that reads back as the
+/// same number, with an exponent for very large or small ones (`0.1`,
+/// `1e+300`), as serde_json writes it.
+fn shortest(n: f64) -> String {
+    serde_json::Number::from_f64(n)
+        .expect("finite number")
+        .to_string()
+}
+
+/// `s` as a double-quoted string with JSON escapes, which YAML and TOML
+/// basic strings also accept. TOML additionally wants DEL escaped.
+fn quote(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                write!(out, "\\u{:04x}", c as u32).expect("write to String")
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
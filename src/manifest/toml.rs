@@ -0,0 +1,146 @@
+//! TOML, for `--output toml`.
+//!
+//! The result must be a record, which becomes the document's root table.
+//! In each table, plain keys come first, then records as `[a.b]` tables and
+//! non-empty lists of records as `[[a.b]]` arrays of tables, in field
+//! order; records and lists further down (inside other lists) are written
+//! inline. TOML has no null, and arrays are kept homogeneous as TOML 0.5
+//! requires, so both are errors; a list of numbers is written as floats
+//! throughout when any of them is not an integer.
+
+use super::{quote, shortest, Kind, Node};
+use crate::diag::Diagnostic;
+use std::fmt::Write as _;
+
+pub fn render(node: &Node) -> Result<String, Diagnostic> {
+    let Kind::Record(fields) = &node.kind else {
+        return Err(node.error(format!(
+            "TOML needs a record at the top level, got {}",
+            node.type_name()
+        )));
+    };
+    let mut out = String::new();
+    table(&mut out, fields, &mut Vec::new(), None)?;
+    if out.ends_with('\n') {
+        out.pop();
+    }
+    Ok(out)
+}
+
+fn is_table_array(node: &Node) -> bool {
+    match &node.kind {
+        Kind::List(xs) => !xs.is_empty() && xs.iter().all(|x| matches!(x.kind, Kind::Record(_))),
+        _ => false,
+    }
+}
+
+/// The table at `keys`. `header` is `[` for a table, `[[` for an element of
+/// an array of tables, and `None` for the root.
+fn table<'a>(
+    out: &mut String,
+    fields: &'a [(&'static str, Node)],
+    keys: &mut Vec<&'a str>,
+    header: Option<&str>,
+) -> Result<(), Diagnostic> {
+    let nested = |node: &Node| matches!(node.kind, Kind::Record(_)) || is_table_array(node);
+    let has_plain = fields.iter().any(|(_, v)| !nested(v));
+    let has_nested = fields.iter().any(|(_, v)| nested(v));
+    if let Some(open) = header {
+        // `[a]` may be left implicit when only `[a.b]` follows it.
+        if open == "[[" || has_plain || !has_nested {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(open);
+            for (i, k) in keys.iter().enumerate() {
+                if i > 0 {
+                    out.push('.');
+                }
+                key(out, k);
+            }
+            out.push_str(if open == "[[" { "]]\n" } else { "]\n" });
+        }
+    }
+    for (name, value) in fields.iter().filter(|(_, v)| !nested(v)) {
+        key(out, name);
+        out.push_str(" = ");
+        inline(out, value, false)?;
+        out.push('\n');
+    }
+    for (name, value) in fields.iter().filter(|(_, v)| nested(v)) {
+        keys.push(name);
+        match &value.kind {
+            Kind::Record(sub) => table(out, sub, keys, Some("["))?,
+            Kind::List(xs) => {
+                for x in xs {
+                    let Kind::Record(sub) = &x.kind else {
+                        unreachable!("checked by is_table_array")
+                    };
+                    table(out, sub, keys, Some("[["))?;
+                }
+            }
+            _ => unreachable!("only records and lists nest"),
+        }
+        keys.pop();
+    }
+    Ok(())
+}
+
+fn key(out: &mut String, k: &str) {
+    if !k.is_empty() && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        out.push_str(k);
+    } else {
+        quote(out, k);
+    }
+}
+
+/// A value on the right of `=`. With `float`, numbers are written as
+/// floats even when integral.
+fn inline(out: &mut String, node: &Node, float: bool) -> Result<(), Diagnostic> {
+    match &node.kind {
+        Kind::Null => return Err(node.error("TOML cannot represent null")),
+        Kind::Bool(b) => write!(out, "{}", b).expect("write to String"),
+        Kind::Integer(i) if !float => write!(out, "{}", i).expect("write to String"),
+        Kind::Integer(i) => write!(out, "{}.0", i).expect("write to String"),
+        Kind::Number(n) if n.is_nan() => out.push_str("nan"),
+        Kind::Number(n) if n.is_infinite() => out.push_str(if *n > 0.0 { "inf" } else { "-inf" }),
+        Kind::Number(n) => out.push_str(&shortest(*n)),
+        Kind::String(s) => quote(out, s),
+        Kind::List(xs) => {
+            if let Some(first) = xs.first() {
+                if let Some(x) = xs.iter().find(|x| x.type_name() != first.type_name()) {
+                    return Err(x.error(format!(
+                        "TOML cannot represent a list mixing {} and {}",
+                        first.type_name(),
+                        x.type_name()
+                    )));
+                }
+            }
+            let float = xs
+                .iter()
+                .any(|x| matches!(x.kind, Kind::Number(_)));
+            out.push('[');
+            for (i, x) in xs.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                inline(out, x, float)?;
+            }
+            out.push(']');
+        }
+        Kind::Record(fields) if fields.is_empty() => out.push_str("{}"),
+        Kind::Record(fields) => {
+            out.push_str("{ ");
+            for (i, (name, field)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                key(out, name);
+                out.push_str(" = ");
+                inline(out, field, false)?;
+            }
+            out.push_str(" }");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! YAML in block style, for `--output yaml`. Every spctr value has a YAML
+//! form: non-finite numbers are `.inf`, `-.inf` and `.nan`, and strings
+//! that could be read back as anything else are double-quoted.
+
+use super::{quote, shortest, Kind, Node};
+use crate::diag::Diagnostic;
+use std::fmt::Write as _;
+
+pub fn render(node: &Node) -> Result<String, Diagnostic> {
+    let mut out = String::new();
+    match scalar(node) {
+        Some(s) => out.push_str(&s),
+        None => {
+            block(&mut out, node, 0, false);
+            out.pop();
+        }
+    }
+    Ok(out)
+}
+
+/// A scalar or an empty collection, which fit on the current line.
+fn scalar(node: &Node) -> Option<String> {
+    let mut out = String::new();
+    match &node.kind {
+        Kind::Null => out.push_str("null"),
+        Kind::Bool(b) => write!(out, "{}", b).expect("write to String"),
+        Kind::Integer(i) => write!(out, "{}", i).expect("write to String"),
+        Kind::Number(n) => number(&mut out, *n),
+        Kind::String(s) => string(&mut out, s),
+        Kind::List(xs) if xs.is_empty() => out.push_str("[]"),
+        Kind::Record(fields) if fields.is_empty() => out.push_str("{}"),
+        Kind::List(_) | Kind::Record(_) => return None,
+    }
+    Some(out)
+}
+
+/// A non-empty collection, one entry per line at `indent`. With `inline`,
+/// the first entry goes on the current line (after a `- `).
+fn block(out: &mut String, node: &Node, indent: usize, inline: bool) {
+    let pad = |out: &mut String, i: usize| {
+        if i > 0 || !inline {
+            out.push_str(&" ".repeat(indent));
+        }
+    };
+    match &node.kind {
+        Kind::List(xs) => {
+            for (i, x) in xs.iter().enumerate() {
+                pad(out, i);
+                out.push_str("- ");
+                match scalar(x) {
+                    Some(s) => {
+                        out.push_str(&s);
+                        out.push('\n');
+                    }
+                    None => block(out, x, indent + 2, true),
+                }
+            }
+        }
+        Kind::Record(fields) => {
+            for (i, (name, field)) in fields.iter().enumerate() {
+                pad(out, i);
+                string(out, name);
+                out.push(':');
+                match scalar(field) {
+                    Some(s) => {
+                        out.push(' ');
+                        out.push_str(&s);
+                        out.push('\n');
+                    }
+                    None => {
+                        out.push('\n');
+                        block(out, field, indent + 2, false);
+                    }
+                }
+            }
+        }
+        _ => unreachable!("scalars are written by `scalar`"),
+    }
+}
+
+fn number(out: &mut String, n: f64) {
+    if n.is_nan() {
+        out.push_str(".nan");
+    } else if n.is_infinite() {
+        out.push_str(if n > 0.0 { ".inf" } else { "-.inf" });
+    } else {
+        // YAML 1.1 only reads `1.0e+300` as a float, not `1e+300`.
+        let s = shortest(n);
+        match s.split_once('e') {
+            Some((mantissa, exp)) if !mantissa.contains('.') => {
+                write!(out, "{}.0e{}", mantissa, exp).expect("write to String")
+            }
+            _ => out.push_str(&s),
+        }
+    }
+}
+
+/// `s` plain when it cannot be mistaken for another scalar (or for YAML
+/// syntax), double-quoted otherwise.
+fn string(out: &mut String, s: &str) {
+    const RESERVED: &[&str] = &["true", "false", "yes", "no", "on", "off", "y", "n", "null"];
+    let plain = s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '/')
+        && !s.ends_with(' ')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || " _-./".contains(c))
+        && !RESERVED.contains(&s.to_ascii_lowercase().as_str());
+    if plain {
+        out.push_str(s);
+    } else {
+        quote(out, s);
+    }
+}
@@ -3,43 +3,177 @@ use chumsky::prelude::*;
 
 use crate::ast::*;
 use crate::diag::Diagnostic;
-use crate::lexer::{lex, Token};
-use crate::symbol::{intern, Symbol};
+use crate::lexer::{lex_recovering, Token};
+use crate::symbol::{display, intern, Symbol};
 
+/// Parse `src`, failing with every lex and parse error found in one pass.
 pub fn parse(src: &str) -> Result<Statement, Vec<Diagnostic>> {
-    let tokens = lex(src).map_err(|errs| {
-        errs.into_iter()
-            .map(|e| Diagnostic::new(e.span, "lex error", "unexpected character"))
-            .collect::<Vec<_>>()
-    })?;
+    complete(parse_in(src, false))
+}
+
+/// Parse the expression of `spctr query`, where `.` is the document and
+/// `.field` a field of it.
+pub fn parse_query(src: &str) -> Result<Statement, Vec<Diagnostic>> {
+    complete(parse_in(src, true))
+}
+
+fn complete((stmt, errors): (Option<Statement>, Vec<Diagnostic>)) -> Result<Statement, Vec<Diagnostic>> {
+    match stmt {
+        Some(stmt) if errors.is_empty() => Ok(stmt),
+        _ => Err(errors),
+    }
+}
+
+/// Parse `src` as far as possible: the errors, in source order, and the
+/// program with what could not be parsed replaced by `Expr::Error` nodes.
+/// The program is `None` only when nothing sensible could be recovered.
+///
+/// Recovery works on comma-separated entries (of the program, blocks,
+/// lists, argument lists and `match`): a missing `,` between two entries is
+/// reported and parsing goes on as if it were there, and an entry that does
+/// not parse is skipped up to the next `,` or closing delimiter, keeping
+/// brackets inside it balanced. A bracketed group whose contents cannot be
+/// parsed at all becomes a single error node.
+pub fn parse_recovering(src: &str) -> (Option<Statement>, Vec<Diagnostic>) {
+    parse_in(src, false)
+}
+
+fn parse_in(src: &str, query: bool) -> (Option<Statement>, Vec<Diagnostic>) {
+    let lexed = lex_recovering(src);
+    let mut errors: Vec<Diagnostic> = lexed
+        .errors
+        .into_iter()
+        .map(|e| Diagnostic::new(e.span, "lex error", "unexpected character").with_code("E0001"))
+        .collect();
 
     let eoi: SimpleSpan = (src.len()..src.len()).into();
     let stream = Stream::from_iter(
-        tokens
+        lexed
+            .tokens
             .into_iter()
             .map(|(t, s)| (t, SimpleSpan::from(s.start..s.end))),
     )
     .map(eoi, |(t, s)| (t, s));
 
-    parser().parse(stream).into_result().map_err(|errs| {
-        errs.into_iter()
-            .map(|e| {
-                let span = span_to_range(*e.span());
-                Diagnostic::new(span, "parse error", e.to_string())
-            })
-            .collect()
-    })
+    let (stmt, parse_errors) = parser(query).parse(stream).into_output_errors();
+    errors.extend(parse_errors.into_iter().map(|e| {
+        let span = span_to_range(*e.span());
+        let d = Diagnostic::new(span, "parse error", e.to_string());
+        if d.label == MULTIPLE_BODIES {
+            d.with_code("E0003")
+                .with_note("a program's body is its one entry without a `name:`")
+        } else {
+            d.with_code("E0002")
+        }
+    }));
+    // Entries split apart by an error often leave a second body behind;
+    // only a program that is otherwise fine really has one.
+    if errors.len() > 1 {
+        errors.retain(|d| d.label != MULTIPLE_BODIES);
+    }
+    // A missing `,` and the entry skipped after it are reported at the
+    // same token; once is enough.
+    errors.sort_by_key(|d| d.span.start);
+    errors.dedup_by_key(|d| d.span.start);
+    (stmt, errors)
 }
 
+const MULTIPLE_BODIES: &str = "multiple body expressions";
+
 fn span_to_range(s: SimpleSpan) -> std::ops::Range<usize> {
     s.start..s.end
 }
 
+/// Where skipping an entry stops: its separator, or the end of whatever
+/// encloses it.
+const ENTRY_END: &[Token] = &[
+    Token::Comma,
+    Token::RParen,
+    Token::RBracket,
+    Token::RBrace,
+    Token::InterpClose,
+    Token::StrEnd,
+];
+
+/// The tokens of an entry that does not parse, up to (not including) one of
+/// `stop`. Bracketed groups are skipped whole, so a `,` or closing
+/// delimiter inside them does not end the entry.
+fn junk<'src, I>(
+    stop: &'static [Token],
+) -> impl Parser<'src, I, (), extra::Err<Rich<'src, Token, SimpleSpan>>> + Clone
+where
+    I: ValueInput<'src, Token = Token, Span = SimpleSpan>,
+{
+    let paren = (Token::LParen, Token::RParen);
+    let bracket = (Token::LBracket, Token::RBracket);
+    let brace = (Token::LBrace, Token::RBrace);
+    choice((
+        nested_delimiters(paren.0.clone(), paren.1.clone(), [bracket.clone(), brace.clone()], |_| ()),
+        nested_delimiters(bracket.0.clone(), bracket.1.clone(), [paren.clone(), brace.clone()], |_| ()),
+        nested_delimiters(brace.0, brace.1, [paren, bracket], |_| ()),
+        any().filter(move |t: &Token| !stop.contains(t)).ignored(),
+    ))
+    .repeated()
+    .at_least(1)
+}
+
+/// Tokens an expression can start with; `.` only in a query.
+fn starts_expr(token: &Token, query: bool) -> bool {
+    matches!(
+        token,
+        Token::Ident(_)
+            | Token::Num(_)
+            | Token::Str(_)
+            | Token::StrBegin
+            | Token::Null
+            | Token::True
+            | Token::False
+            | Token::If
+            | Token::Match
+            | Token::LParen
+            | Token::LBracket
+            | Token::LBrace
+            | Token::Minus
+            | Token::Bang
+    ) || query && *token == Token::Dot
+}
+
+/// `expr` as one comma-separated entry. It has to end where an entry can:
+/// at a separator, a closing delimiter or the start of the next entry (the
+/// `,` having been left out). Otherwise the whole entry, up to where it
+/// should have ended, becomes an `Expr::Error`.
+fn entry<'src, I>(
+    query: bool,
+    expr: impl Parser<'src, I, Spanned<Expr>, extra::Err<Rich<'src, Token, SimpleSpan>>> + Clone,
+) -> impl Parser<'src, I, Spanned<Expr>, extra::Err<Rich<'src, Token, SimpleSpan>>> + Clone
+where
+    I: ValueInput<'src, Token = Token, Span = SimpleSpan>,
+{
+    let boundary = choice((
+        any()
+            .filter(move |t: &Token| ENTRY_END.contains(t) || starts_expr(t, query))
+            .ignored(),
+        end(),
+    ))
+    .rewind();
+    expr.then_ignore(boundary).recover_with(via_parser(
+        junk(ENTRY_END).map_with(|_, ex| (Expr::Error, span_to_range(ex.span()))),
+    ))
+}
+
+/// The program parser; `query` lets `.` start an expression.
 fn parser<'src, I>(
+    query: bool,
 ) -> impl Parser<'src, I, Statement, extra::Err<Rich<'src, Token, SimpleSpan>>> + Clone
 where
     I: ValueInput<'src, Token = Token, Span = SimpleSpan>,
 {
+    // Between entries: a `,` missing before something that starts an entry
+    // is reported, then parsing goes on as if it were there.
+    let comma = just(Token::Comma).ignored().recover_with(via_parser(
+        any().filter(move |t| starts_expr(t, query)).ignored().rewind(),
+    ));
+
     let ident = select! { Token::Ident(s) => intern(&s) }
         .map_with(|sym, ex| (sym, span_to_range(ex.span())));
 
@@ -49,11 +183,65 @@ where
     }
     .map_with(|sym, ex| (sym, span_to_range(ex.span())));
 
+    let pattern = recursive(|pattern| {
+        let number = select! { Token::Num(n) => n };
+        let literal = choice((
+            number.map(Pattern::Number),
+            just(Token::Minus)
+                .ignore_then(number)
+                .map(|n| Pattern::Number(-n)),
+            select! {
+                Token::Str(s) => Pattern::String(std::rc::Rc::new(s)),
+                Token::Null => Pattern::Null,
+                Token::True => Pattern::Bool(true),
+                Token::False => Pattern::Bool(false),
+            },
+        ));
+
+        let binder = ident.map(|(sym, span)| {
+            if display(sym) == "_" {
+                Pattern::Wildcard
+            } else {
+                Pattern::Bind((sym, span))
+            }
+        });
+
+        let rest = just(Token::Ellipsis).ignore_then(ident.or_not());
+
+        // `[p, q]`, `[p, ...rest]`, `[...rest]`, `[]`.
+        let list = choice((
+            pattern
+                .clone()
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect::<Vec<_>>()
+                .then(just(Token::Comma).ignore_then(rest.clone()).or_not()),
+            rest.clone().map(|r| (Vec::new(), Some(r))),
+            empty().map(|_| (Vec::new(), None)),
+        ))
+        .delimited_by(just(Token::LBracket), just(Token::RBracket))
+        .map(|(items, rest)| Pattern::List { items, rest });
+
+        let field = key.then(just(Token::Colon).ignore_then(pattern.clone()).or_not());
+
+        let record = field
+            .separated_by(just(Token::Comma))
+            .collect::<Vec<_>>()
+            .delimited_by(just(Token::LBrace), just(Token::RBrace))
+            .map(Pattern::Record);
+
+        choice((literal, list, record, binder))
+            .map_with(|p, ex| (p, span_to_range(ex.span())))
+            .boxed()
+    });
+
     let expr = recursive(|expr| {
+        let entry = entry(query, expr.clone());
+
         let bind = key
             .clone()
             .then_ignore(just(Token::Colon))
-            .then(expr.clone())
+            .then(entry.clone())
             .map(|(name, value)| (name, value));
 
         let literal = select! {
@@ -92,12 +280,18 @@ where
                 Expr::Interpolation(parts)
             });
 
-        let list = expr
+        let list = entry
             .clone()
-            .separated_by(just(Token::Comma))
+            .separated_by(comma.clone())
             .collect::<Vec<_>>()
             .delimited_by(just(Token::LBracket), just(Token::RBracket))
-            .map(Expr::List);
+            .map(Expr::List)
+            .recover_with(via_parser(nested_delimiters(
+                Token::LBracket,
+                Token::RBracket,
+                [(Token::LParen, Token::RParen), (Token::LBrace, Token::RBrace)],
+                |_| Expr::Error,
+            )));
 
         let func = ident
             .clone()
@@ -112,6 +306,7 @@ where
         enum BlockItem {
             Bind(Bind),
             Body(Spanned<Expr>),
+            Junk,
         }
 
         let block_item = choice((
@@ -120,11 +315,12 @@ where
                 .rewind()
                 .ignore_then(bind.clone())
                 .map(BlockItem::Bind),
-            expr.clone().map(BlockItem::Body),
-        ));
+            entry.clone().map(BlockItem::Body),
+        ))
+        .recover_with(via_parser(junk(ENTRY_END).to(BlockItem::Junk)));
 
         let brace = block_item
-            .separated_by(just(Token::Comma))
+            .separated_by(comma.clone())
             .collect::<Vec<_>>()
             .delimited_by(just(Token::LBrace), just(Token::RBrace))
             .map(|items: Vec<BlockItem>| {
@@ -134,6 +330,7 @@ where
                     match item {
                         BlockItem::Bind(b) => defs.push(b),
                         BlockItem::Body(e) => body = Some(e),
+                        BlockItem::Junk => {}
                     }
                 }
                 match body {
@@ -143,7 +340,13 @@ where
                     })),
                     None => Expr::Block(defs),
                 }
-            });
+            })
+            .recover_with(via_parser(nested_delimiters(
+                Token::LBrace,
+                Token::RBrace,
+                [(Token::LParen, Token::RParen), (Token::LBracket, Token::RBracket)],
+                |_| Expr::Error,
+            )));
 
         let if_expr = just(Token::If)
             .ignore_then(expr.clone())
@@ -157,17 +360,73 @@ where
                 alt: Box::new(alt),
             });
 
+        let arm = pattern
+            .clone()
+            .then(just(Token::If).ignore_then(expr.clone()).or_not())
+            .then_ignore(just(Token::FatArrow))
+            .then(expr.clone())
+            .map(|((pattern, guard), body)| MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+
+        let match_expr = just(Token::Match)
+            .ignore_then(expr.clone())
+            .then(
+                arm.separated_by(comma.clone())
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::LBrace), just(Token::RBrace)),
+            )
+            .map(|(scrutinee, arms)| Expr::Match {
+                scrutinee: Box::new(scrutinee),
+                arms,
+            })
+            .boxed();
+
         let paren = expr
             .clone()
-            .delimited_by(just(Token::LParen), just(Token::RParen));
+            .delimited_by(just(Token::LParen), just(Token::RParen))
+            .recover_with(via_parser(nested_delimiters(
+                Token::LParen,
+                Token::RParen,
+                [(Token::LBracket, Token::RBracket), (Token::LBrace, Token::RBrace)],
+                |span| (Expr::Error, span_to_range(span)),
+            )));
+
+        // In a query, `.` is the variable named `.`, the document, and
+        // `.field` is a field of it.
+        let this = just(Token::Dot)
+            .map_with(|_, ex| {
+                let dot = Expr::Variable(VarRef::new(intern(".")));
+                (dot, span_to_range(ex.span()))
+            })
+            .then(ident.or_not())
+            .map_with(|(dot, field), ex| match field {
+                Some(name) => (Expr::Access(Box::new(dot), name), span_to_range(ex.span())),
+                None => dot,
+            });
 
-        let atom = choice((literal, interp_string, if_expr, func, list, brace, var))
-            .map_with(|e, ex| (e, span_to_range(ex.span())))
-            .or(paren);
+        let atom = choice((
+            literal,
+            interp_string,
+            if_expr,
+            match_expr,
+            func,
+            list,
+            brace,
+            var,
+        ))
+        .map_with(|e, ex| (e, span_to_range(ex.span())))
+        .or(paren);
+        // Outside a query `.` is not even an alternative, so parse errors
+        // do not offer it.
+        let atom = if query { atom.or(this).boxed() } else { atom.boxed() };
 
         #[derive(Clone)]
         enum PostfixOp {
             Access(Spanned<Symbol>),
+            OptAccess(Spanned<Symbol>),
             Call(Vec<Spanned<Expr>>),
             Index(Spanned<Expr>),
         }
@@ -176,6 +435,10 @@ where
             .ignore_then(ident.clone())
             .map(PostfixOp::Access);
 
+        let opt_access = just(Token::QuestionDot)
+            .ignore_then(ident)
+            .map(PostfixOp::OptAccess);
+
         let bracket_string_access = select! { Token::Str(s) => intern(&s) }
             .map_with(|sym, ex| (sym, span_to_range(ex.span())))
             .delimited_by(just(Token::LBracket), just(Token::RBracket))
@@ -186,20 +449,27 @@ where
             .delimited_by(just(Token::LBracket), just(Token::RBracket))
             .map(PostfixOp::Index);
 
-        let call = expr
+        let call = entry
             .clone()
-            .separated_by(just(Token::Comma))
+            .separated_by(comma.clone())
             .collect::<Vec<_>>()
             .delimited_by(just(Token::LParen), just(Token::RParen))
             .map(PostfixOp::Call);
 
-        let postfix_op = choice((dot_access, call, bracket_string_access, bracket_index));
+        let postfix_op = choice((
+            dot_access,
+            opt_access,
+            call,
+            bracket_string_access,
+            bracket_index,
+        ));
 
         let postfix = atom
             .foldl_with(postfix_op.repeated(), |lhs, op, ex| {
                 let span = span_to_range(ex.span());
                 let node = match op {
                     PostfixOp::Access(name) => Expr::Access(Box::new(lhs), name),
+                    PostfixOp::OptAccess(name) => Expr::OptAccess(Box::new(lhs), name),
                     PostfixOp::Call(args) => Expr::Call(Box::new(lhs), args),
                     PostfixOp::Index(idx) => Expr::Index(Box::new(lhs), Box::new(idx)),
                 };
@@ -280,7 +550,7 @@ where
             )
             .boxed();
 
-        logic_and
+        let logic_or = logic_and
             .clone()
             .foldl_with(
                 just(Token::OrOr).to(BinOp::Or).then(logic_and).repeated(),
@@ -291,18 +561,38 @@ where
                     )
                 },
             )
+            .boxed();
+
+        // `??` binds loosest so `a?.b ?? x || y` defaults the whole `||`.
+        logic_or
+            .clone()
+            .foldl_with(
+                just(Token::QuestionQuestion)
+                    .to(BinOp::Coalesce)
+                    .then(logic_or)
+                    .repeated(),
+                |lhs, (op, rhs), ex| {
+                    (
+                        Expr::Binary(op, Box::new(lhs), Box::new(rhs)),
+                        span_to_range(ex.span()),
+                    )
+                },
+            )
             .boxed()
     });
 
+    let entry = entry(query, expr);
+
     let bind = key
         .clone()
         .then_ignore(just(Token::Colon))
-        .then(expr.clone());
+        .then(entry.clone());
 
     #[derive(Clone)]
     enum StmtItem {
         Bind(Bind),
         Body(Spanned<Expr>),
+        Junk,
     }
 
     let stmt_item = choice((
@@ -311,28 +601,32 @@ where
             .rewind()
             .ignore_then(bind)
             .map(StmtItem::Bind),
-        expr.clone().map(StmtItem::Body),
-    ));
+        entry.map(StmtItem::Body),
+    ))
+    .recover_with(via_parser(junk(&[Token::Comma]).to(StmtItem::Junk)));
 
     stmt_item
-        .separated_by(just(Token::Comma))
+        .separated_by(comma)
         .collect::<Vec<_>>()
-        .then_ignore(end())
-        .try_map(|items: Vec<StmtItem>, span| {
+        .then_ignore(end().recover_with(via_parser(any().repeated())))
+        .validate(|items: Vec<StmtItem>, ex, emitter| {
+            let span = ex.span();
             let mut definitions = Vec::new();
             let mut body = None;
+            let mut many = false;
             for item in items {
                 match item {
                     StmtItem::Bind(b) => definitions.push(b),
                     StmtItem::Body(e) => {
-                        if body.is_some() {
-                            return Err(Rich::custom(span, "multiple body expressions"));
+                        if body.replace(e).is_some() && !many {
+                            emitter.emit(Rich::custom(span, MULTIPLE_BODIES));
+                            many = true;
                         }
-                        body = Some(e);
                     }
+                    StmtItem::Junk => {}
                 }
             }
             let body = body.unwrap_or_else(|| (Expr::Null, span_to_range(span)));
-            Ok(Statement { definitions, body })
+            Statement { definitions, body }
         })
 }
@@ -34,7 +34,7 @@ impl Resolver {
 
     fn expr(&mut self, expr: &Spanned<Expr>) -> Result<(), Diagnostic> {
         match &expr.0 {
-            Expr::Number(_) | Expr::String(_) | Expr::Null | Expr::Bool(_) => Ok(()),
+            Expr::Number(_) | Expr::String(_) | Expr::Null | Expr::Bool(_) | Expr::Error => Ok(()),
             Expr::Interpolation(parts) => {
                 for p in parts {
                     if let InterpPart::Expr(e) = p {
@@ -53,11 +53,17 @@ impl Resolver {
                         return Ok(());
                     }
                 }
+                let in_scope = self
+                    .scopes
+                    .iter()
+                    .flat_map(|scope| scope.keys().map(|n| display(*n)));
                 Err(Diagnostic::new(
                     expr.1.clone(),
                     format!("undefined variable: {}", display(var.name)),
                     "not found in scope",
-                ))
+                )
+                .with_code("E0101")
+                .did_you_mean(display(var.name), in_scope))
             }
             Expr::List(items) => {
                 for item in items {
@@ -107,12 +113,38 @@ impl Resolver {
                 }
                 Ok(())
             }
-            Expr::Access(obj, _) => self.expr(obj),
+            Expr::Access(obj, _) | Expr::OptAccess(obj, _) => self.expr(obj),
             Expr::Index(arr, idx) => {
                 self.expr(arr)?;
                 self.expr(idx)?;
                 Ok(())
             }
+            Expr::Match { scrutinee, arms } => {
+                self.expr(scrutinee)?;
+                for arm in arms {
+                    let mut binders = Vec::new();
+                    arm.pattern.0.binders(&mut binders);
+                    let mut scope: HashMap<Symbol, u32> = HashMap::new();
+                    for (i, (name, span)) in binders.iter().enumerate() {
+                        if let Some(first) = scope.insert(*name, i as u32) {
+                            return Err(Diagnostic::new(
+                                span.clone(),
+                                format!("duplicate binding in pattern: {}", display(*name)),
+                                "already bound in this pattern",
+                            )
+                            .with_code("E0102")
+                            .with_label(binders[first as usize].1.clone(), "first bound here"));
+                        }
+                    }
+                    self.scopes.push(scope);
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard)?;
+                    }
+                    self.expr(&arm.body)?;
+                    self.scopes.pop();
+                }
+                Ok(())
+            }
         }
     }
 }
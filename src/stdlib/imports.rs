@@ -1,14 +1,142 @@
+use crate::ast::Statement;
 use crate::diag::Diagnostic;
-use crate::interp::{run_eager as interp_run, EvalResult, Value};
+use crate::interp::{run_eager as interp_run, EvalResult, Function, Value};
 use crate::lexer::Span;
 use crate::types::{Scheme, Type};
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+/// Where `import("path")` finds its modules.
+///
+/// A module is identified by the path `resolve` returns; the interpreter
+/// caches imported values by it, and cycles are detected on it. Both the
+/// interpreter and typeck (and the JIT's linker) go through the resolver
+/// installed on the current thread — see [`set_resolver`] and
+/// [`with_resolver`].
+pub trait ImportResolver {
+    /// Identify the module `path` names. `from` is the importing module, or
+    /// `None` for the program being run.
+    fn resolve(&self, path: &str, from: Option<&Path>) -> Result<PathBuf, String>;
+    /// Source text of a module `resolve` returned.
+    fn load(&self, module: &Path) -> Result<String, String>;
+}
+
+/// Reads modules from the filesystem. Relative paths are taken from the
+/// importing file's directory, or from `root` for the program being run.
+pub struct FsResolver {
+    root: PathBuf,
+}
+
+impl FsResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Default for FsResolver {
+    /// Rooted at the process's working directory.
+    fn default() -> Self {
+        Self::new(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+    }
+}
+
+impl ImportResolver for FsResolver {
+    fn resolve(&self, path: &str, from: Option<&Path>) -> Result<PathBuf, String> {
+        let base = from.and_then(Path::parent).unwrap_or(&self.root);
+        let joined = base.join(path);
+        joined
+            .canonicalize()
+            .map_err(|e| format!("cannot read {}: {}", joined.display(), e))
+    }
+
+    fn load(&self, module: &Path) -> Result<String, String> {
+        std::fs::read_to_string(module).map_err(|e| format!("cannot read {}: {}", module.display(), e))
+    }
+}
+
+/// Serves modules from memory, keyed by path. Paths are normalized
+/// lexically (`.` and `..` are folded), and relative ones are taken from
+/// the importing module's directory or from the map's root.
+#[derive(Default)]
+pub struct MapResolver {
+    files: HashMap<PathBuf, String>,
+}
+
+impl MapResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add (or replace) the module at `path`.
+    pub fn insert(&mut self, path: impl AsRef<Path>, source: impl Into<String>) {
+        self.files.insert(normalize(path.as_ref()), source.into());
+    }
+}
+
+impl<P: AsRef<Path>, S: Into<String>> FromIterator<(P, S)> for MapResolver {
+    fn from_iter<I: IntoIterator<Item = (P, S)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for (path, source) in iter {
+            map.insert(path, source);
+        }
+        map
+    }
+}
+
+impl ImportResolver for MapResolver {
+    fn resolve(&self, path: &str, from: Option<&Path>) -> Result<PathBuf, String> {
+        let base = from.and_then(Path::parent).unwrap_or(Path::new(""));
+        let module = normalize(&base.join(path));
+        if self.files.contains_key(&module) {
+            Ok(module)
+        } else {
+            Err(format!("no module {}", module.display()))
+        }
+    }
+
+    fn load(&self, module: &Path) -> Result<String, String> {
+        self.files
+            .get(module)
+            .cloned()
+            .ok_or_else(|| format!("no module {}", module.display()))
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Rejects every import; for evaluating untrusted programs.
+pub struct DenyResolver;
+
+impl ImportResolver for DenyResolver {
+    fn resolve(&self, path: &str, _from: Option<&Path>) -> Result<PathBuf, String> {
+        Err(format!("imports are disabled (importing {path})"))
+    }
+
+    fn load(&self, module: &Path) -> Result<String, String> {
+        Err(format!("imports are disabled (loading {})", module.display()))
+    }
+}
 
 thread_local! {
-    static CURRENT_DIR: RefCell<PathBuf> = RefCell::new(
-        std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
-    );
+    static RESOLVER: RefCell<Rc<dyn ImportResolver>> = RefCell::new(Rc::new(FsResolver::default()));
+    /// Values of the modules imported during this run, by resolved path.
+    static MODULES: RefCell<HashMap<PathBuf, Value>> = RefCell::new(HashMap::new());
+    /// Modules being evaluated, outermost first, for reporting cycles.
+    static LOADING: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
 }
 
 pub fn ty() -> Scheme {
@@ -18,17 +146,44 @@ pub fn ty() -> Scheme {
     }
 }
 
-pub fn set_current_dir(dir: PathBuf) {
-    CURRENT_DIR.with(|cell| *cell.borrow_mut() = dir);
+/// Install the resolver used by every later import on this thread, and
+/// return the one it replaces.
+pub fn set_resolver(resolver: Rc<dyn ImportResolver>) -> Rc<dyn ImportResolver> {
+    RESOLVER.with(|r| std::mem::replace(&mut *r.borrow_mut(), resolver))
 }
 
-pub fn import(args: Vec<Value>, span: &Span) -> EvalResult {
+/// Run `f` with `resolver` installed, then put the previous one back.
+pub fn with_resolver<R>(resolver: Rc<dyn ImportResolver>, f: impl FnOnce() -> R) -> R {
+    let prev = set_resolver(resolver);
+    let out = f();
+    set_resolver(prev);
+    out
+}
+
+/// Forget every module imported so far. `interp::run` calls this so each
+/// run starts from a clean cache; `entry` is the module being run, if it is
+/// one, so importing it back counts as a cycle.
+pub fn clear_cache(entry: Option<PathBuf>) {
+    MODULES.with(|m| m.borrow_mut().clear());
+    LOADING.with(|l| *l.borrow_mut() = entry.into_iter().collect());
+}
+
+/// The `import` builtin as seen from the module `from` (`None` for the
+/// program being run), which relative paths are resolved against.
+pub fn importer(from: Option<PathBuf>) -> Value {
+    Value::Function(Function::Foreign(Rc::new(move |args, span| {
+        import(args, from.as_deref(), span)
+    })))
+}
+
+fn import(args: Vec<Value>, from: Option<&Path>, span: &Span) -> EvalResult {
     if args.len() != 1 {
         return Err(Diagnostic::new(
             span.clone(),
             format!("import expects 1 argument, got {}", args.len()),
             "argument count",
-        ));
+        )
+        .with_code("E0207"));
     }
     let raw_path = match &args[0] {
         Value::String(s) => s.clone(),
@@ -37,54 +192,75 @@ pub fn import(args: Vec<Value>, span: &Span) -> EvalResult {
                 span.clone(),
                 format!("import expects a string path, got {}", other.type_name()),
                 "type mismatch",
-            ))
+            )
+            .with_code("E0201"))
         }
     };
 
-    let candidate = PathBuf::from(raw_path.as_str());
-    let resolved = if candidate.is_absolute() {
-        candidate
-    } else {
-        CURRENT_DIR.with(|cell| cell.borrow().join(&candidate))
-    };
+    let module = resolve(&raw_path, from, span)?;
+    if let Some(v) = MODULES.with(|m| m.borrow().get(&module).cloned()) {
+        return Ok(v);
+    }
+    if let Some(d) = LOADING.with(|l| cycle_error(&l.borrow(), &module, span)) {
+        return Err(d);
+    }
+    let ast = load(&module, from, span)?;
 
-    let source = std::fs::read_to_string(&resolved).map_err(|e| {
-        Diagnostic::new(
-            span.clone(),
-            format!("cannot read {}: {}", resolved.display(), e),
-            "import error",
-        )
-    })?;
+    LOADING.with(|l| l.borrow_mut().push(module.clone()));
+    let result = interp_run(&ast, module.clone());
+    LOADING.with(|l| l.borrow_mut().pop());
+    let v = result.map_err(|d| d.imported(&module, from, span))?;
+    MODULES.with(|m| m.borrow_mut().insert(module, v.clone()));
+    Ok(v)
+}
 
-    let ast = crate::parser::parse(&source).map_err(|errs| {
-        let summary = errs
-            .iter()
-            .map(|d| format!("{}: {}", d.message, d.label))
-            .collect::<Vec<_>>()
-            .join("; ");
-        Diagnostic::new(
-            span.clone(),
-            format!("parse error in {}: {}", resolved.display(), summary),
-            "import error",
-        )
-    })?;
+/// Identify the module `raw_path` names from `from`, through the installed
+/// resolver.
+pub fn resolve(raw_path: &str, from: Option<&Path>, span: &Span) -> Result<PathBuf, Diagnostic> {
+    RESOLVER
+        .with(|r| r.borrow().resolve(raw_path, from))
+        .map_err(|msg| Diagnostic::new(span.clone(), msg, "import error").with_code("E0401"))
+}
 
-    crate::resolver::resolve(&ast, &crate::interp::ROOT_NAMES).map_err(|d| {
-        Diagnostic::new(
-            span.clone(),
-            format!("resolve error in {}: {}", resolved.display(), d.message),
-            "import error",
-        )
+/// The error for importing `module` while `chain` (outermost first) is
+/// still loading, or `None` if `module` is not on the chain.
+pub fn cycle_error(chain: &[PathBuf], module: &Path, span: &Span) -> Option<Diagnostic> {
+    let start = chain.iter().position(|p| p == module)?;
+    let cycle = chain[start..]
+        .iter()
+        .map(|p| p.as_path())
+        .chain(std::iter::once(module))
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ");
+    Some(Diagnostic::new(
+        span.clone(),
+        format!("import cycle: {cycle}"),
+        "import error",
+    )
+    .with_code("E0402"))
+}
+
+/// Load, parse and resolve a module `resolve` returned, for the `import`
+/// in `from` at `span`. Errors inside the module point into it; its source
+/// is kept for rendering them (`diag::add_source`).
+pub fn load(module: &Path, from: Option<&Path>, span: &Span) -> Result<Statement, Diagnostic> {
+    let source = RESOLVER
+        .with(|r| r.borrow().load(module))
+        .map_err(|msg| Diagnostic::new(span.clone(), msg, "import error").with_code("E0401"))?;
+    crate::diag::add_source(module, &source);
+
+    let ast = crate::parser::parse(&source).map_err(|errs| {
+        let more = errs.len() - 1;
+        let mut first = errs.into_iter().next().expect("parse failed without errors");
+        if more > 0 {
+            first.message = format!("{} (and {} more)", first.message, more);
+        }
+        first.imported(module, from, span)
     })?;
 
-    let imported_dir = resolved
-        .parent()
-        .map(|p| p.to_path_buf())
-        .unwrap_or_else(|| PathBuf::from("."));
+    crate::resolver::resolve(&ast, &crate::interp::root_names())
+        .map_err(|d| d.imported(module, from, span))?;
 
-    let prev_dir = CURRENT_DIR.with(|cell| cell.borrow().clone());
-    set_current_dir(imported_dir);
-    let result = interp_run(&ast);
-    set_current_dir(prev_dir);
-    result
+    Ok(ast)
 }
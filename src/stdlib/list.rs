@@ -1,5 +1,5 @@
 use crate::diag::Diagnostic;
-use crate::interp::{call_value, BindState, Env, EvalResult, Frame, Function, Value};
+use crate::interp::{BindState, Driver, Env, EvalResult, Frame, Function, Resume, Value};
 use crate::lexer::Span;
 use crate::symbol::intern;
 use crate::types::{Scheme, Type, TypeVar};
@@ -95,18 +95,24 @@ pub fn module() -> Value {
         ("concat", concat),
         ("take", take),
         ("drop", drop_),
-        ("map", map),
-        ("filter", filter),
-        ("reduce", reduce),
     ];
+    // The ones that call back into spctr code.
+    type Start = fn(Vec<Value>, &Span) -> Result<Box<dyn Driver>, Diagnostic>;
+    let driven: Vec<(&str, Start)> = vec![("map", map), ("filter", filter), ("reduce", reduce)];
 
-    let mut binds = Vec::with_capacity(entries.len());
-    let mut names = HashMap::with_capacity(entries.len());
-    for (i, (name, f)) in entries.into_iter().enumerate() {
+    let mut binds = Vec::with_capacity(entries.len() + driven.len());
+    let mut names = HashMap::with_capacity(entries.len() + driven.len());
+    for (name, f) in entries {
+        names.insert(intern(name), binds.len() as u32);
         binds.push(Rc::new(RefCell::new(BindState::Done(Value::Function(
             Function::Foreign(Rc::new(f)),
         )))));
-        names.insert(intern(name), i as u32);
+    }
+    for (name, f) in driven {
+        names.insert(intern(name), binds.len() as u32);
+        binds.push(Rc::new(RefCell::new(BindState::Done(Value::Function(
+            Function::Driven(Rc::new(f)),
+        )))));
     }
 
     Value::Block(Rc::new(Frame {
@@ -122,7 +128,8 @@ fn arity(args: &[Value], expected: usize, name: &str, span: &Span) -> Result<(),
             span.clone(),
             format!("List.{} expects {} arguments, got {}", name, expected, args.len()),
             "argument count",
-        ))
+        )
+        .with_code("E0207"))
     } else {
         Ok(())
     }
@@ -135,7 +142,8 @@ fn into_list(v: &Value, span: &Span) -> Result<Rc<Vec<Value>>, Diagnostic> {
             span.clone(),
             format!("expected list, got {}", other.type_name()),
             "type mismatch",
-        )),
+        )
+        .with_code("E0201")),
     }
 }
 
@@ -146,7 +154,8 @@ fn into_number(v: &Value, span: &Span) -> Result<f64, Diagnostic> {
             span.clone(),
             format!("expected number, got {}", other.type_name()),
             "type mismatch",
-        )),
+        )
+        .with_code("E0201")),
     }
 }
 
@@ -173,6 +182,7 @@ fn head(args: Vec<Value>, span: &Span) -> EvalResult {
             "List.head on empty list",
             "no first element",
         )
+        .with_code("E0301")
     })
 }
 
@@ -184,7 +194,8 @@ fn tail(args: Vec<Value>, span: &Span) -> EvalResult {
             span.clone(),
             "List.tail on empty list",
             "no tail",
-        ));
+        )
+        .with_code("E0301"));
     }
     Ok(Value::List(Rc::new(xs[1..].to_vec())))
 }
@@ -214,46 +225,104 @@ fn drop_(args: Vec<Value>, span: &Span) -> EvalResult {
     Ok(Value::List(Rc::new(xs[n..].to_vec())))
 }
 
-fn map(args: Vec<Value>, span: &Span) -> EvalResult {
+fn map(args: Vec<Value>, span: &Span) -> Result<Box<dyn Driver>, Diagnostic> {
     arity(&args, 2, "map", span)?;
     let xs = into_list(&args[0], span)?;
-    let f = args[1].clone();
-    let mut result = Vec::with_capacity(xs.len());
-    for x in xs.iter() {
-        result.push(call_value(f.clone(), vec![x.clone()], span)?);
+    let out = Vec::with_capacity(xs.len());
+    Ok(Box::new(Map {
+        xs,
+        f: args[1].clone(),
+        out,
+    }))
+}
+
+struct Map {
+    xs: Rc<Vec<Value>>,
+    f: Value,
+    out: Vec<Value>,
+}
+
+impl Driver for Map {
+    fn resume(&mut self, last: Option<Value>, _span: &Span) -> Result<Resume, Diagnostic> {
+        if let Some(v) = last {
+            self.out.push(v);
+        }
+        Ok(match self.xs.get(self.out.len()) {
+            Some(x) => Resume::Call(self.f.clone(), vec![x.clone()]),
+            None => Resume::Done(Value::List(Rc::new(std::mem::take(&mut self.out)))),
+        })
     }
-    Ok(Value::List(Rc::new(result)))
 }
 
-fn filter(args: Vec<Value>, span: &Span) -> EvalResult {
+fn filter(args: Vec<Value>, span: &Span) -> Result<Box<dyn Driver>, Diagnostic> {
     arity(&args, 2, "filter", span)?;
-    let xs = into_list(&args[0], span)?;
-    let f = args[1].clone();
-    let mut result = Vec::new();
-    for x in xs.iter() {
-        let v = call_value(f.clone(), vec![x.clone()], span)?;
-        match v {
-            Value::Bool(true) => result.push(x.clone()),
-            Value::Bool(false) => {}
-            other => {
+    Ok(Box::new(Filter {
+        xs: into_list(&args[0], span)?,
+        f: args[1].clone(),
+        next: 0,
+        out: Vec::new(),
+    }))
+}
+
+struct Filter {
+    xs: Rc<Vec<Value>>,
+    f: Value,
+    next: usize,
+    out: Vec<Value>,
+}
+
+impl Driver for Filter {
+    fn resume(&mut self, last: Option<Value>, span: &Span) -> Result<Resume, Diagnostic> {
+        match last {
+            None | Some(Value::Bool(false)) => {}
+            Some(Value::Bool(true)) => self.out.push(self.xs[self.next - 1].clone()),
+            Some(other) => {
                 return Err(Diagnostic::new(
                     span.clone(),
                     format!("List.filter predicate returned {}", other.type_name()),
                     "expected bool",
-                ))
+                )
+                .with_code("E0201"))
             }
         }
+        Ok(match self.xs.get(self.next) {
+            Some(x) => {
+                self.next += 1;
+                Resume::Call(self.f.clone(), vec![x.clone()])
+            }
+            None => Resume::Done(Value::List(Rc::new(std::mem::take(&mut self.out)))),
+        })
     }
-    Ok(Value::List(Rc::new(result)))
 }
 
-fn reduce(args: Vec<Value>, span: &Span) -> EvalResult {
+fn reduce(args: Vec<Value>, span: &Span) -> Result<Box<dyn Driver>, Diagnostic> {
     arity(&args, 3, "reduce", span)?;
-    let xs = into_list(&args[0], span)?;
-    let mut acc = args[1].clone();
-    let f = args[2].clone();
-    for x in xs.iter() {
-        acc = call_value(f.clone(), vec![acc, x.clone()], span)?;
+    Ok(Box::new(Reduce {
+        xs: into_list(&args[0], span)?,
+        acc: args[1].clone(),
+        f: args[2].clone(),
+        next: 0,
+    }))
+}
+
+struct Reduce {
+    xs: Rc<Vec<Value>>,
+    acc: Value,
+    f: Value,
+    next: usize,
+}
+
+impl Driver for Reduce {
+    fn resume(&mut self, last: Option<Value>, _span: &Span) -> Result<Resume, Diagnostic> {
+        if let Some(v) = last {
+            self.acc = v;
+        }
+        Ok(match self.xs.get(self.next) {
+            Some(x) => {
+                self.next += 1;
+                Resume::Call(self.f.clone(), vec![self.acc.clone(), x.clone()])
+            }
+            None => Resume::Done(self.acc.clone()),
+        })
     }
-    Ok(acc)
 }
@@ -68,7 +68,8 @@ fn arity(args: &[Value], expected: usize, name: &str, span: &Span) -> Result<(),
             span.clone(),
             format!("Number.{} expects {} arguments, got {}", name, expected, args.len()),
             "argument count",
-        ))
+        )
+        .with_code("E0207"))
     } else {
         Ok(())
     }
@@ -81,7 +82,8 @@ fn into_number(v: &Value, span: &Span) -> Result<f64, Diagnostic> {
             span.clone(),
             format!("expected number, got {}", other.type_name()),
             "type mismatch",
-        )),
+        )
+        .with_code("E0201")),
     }
 }
 
@@ -92,7 +94,8 @@ fn into_string(v: &Value, span: &Span) -> Result<Rc<String>, Diagnostic> {
             span.clone(),
             format!("expected string, got {}", other.type_name()),
             "type mismatch",
-        )),
+        )
+        .with_code("E0201")),
     }
 }
 
@@ -111,6 +114,7 @@ fn parse(args: Vec<Value>, span: &Span) -> EvalResult {
             format!("cannot parse {:?} as number", s.as_str()),
             "invalid number",
         )
+        .with_code("E0304")
     })
 }
 
@@ -78,7 +78,8 @@ fn arity(args: &[Value], expected: usize, name: &str, span: &Span) -> Result<(),
             span.clone(),
             format!("String.{} expects {} arguments, got {}", name, expected, args.len()),
             "argument count",
-        ))
+        )
+        .with_code("E0207"))
     } else {
         Ok(())
     }
@@ -91,7 +92,8 @@ fn into_string(v: &Value, span: &Span) -> Result<Rc<String>, Diagnostic> {
             span.clone(),
             format!("expected string, got {}", other.type_name()),
             "type mismatch",
-        )),
+        )
+        .with_code("E0201")),
     }
 }
 
@@ -1,24 +1,45 @@
 use crate::ast::*;
-use crate::diag::Diagnostic;
+use crate::diag::{Diagnostic, Severity};
 use crate::lexer::Span;
-use crate::symbol::display;
+use crate::stdlib::imports;
+use crate::symbol::{display, Symbol};
 use crate::types::*;
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 pub struct TypeCheckResult {
     pub program_type: Type,
+    /// Everything typeck reported: type errors, and warnings about code
+    /// that may fail (a possibly null value, a non-exhaustive match).
     pub warnings: Vec<Diagnostic>,
     /// Per-expression resolved types, keyed by `&expr as *const Spanned<Expr> as usize`.
     /// Only valid while the AST stays alive at the same address.
     pub node_types: HashMap<usize, Type>,
+    /// The expressions behind the possibly-null warnings, keyed like
+    /// `node_types`: each may be null where a value is needed, so a backend
+    /// that trusts the types has to check them at run time.
+    pub null_checks: HashSet<usize>,
+}
+
+impl TypeCheckResult {
+    /// Whether any of `warnings` is an error rather than a warning.
+    pub fn has_errors(&self) -> bool {
+        self.warnings.iter().any(|d| d.severity == Severity::Error)
+    }
 }
 
 pub fn check(stmt: &Statement, root_types: &[Type]) -> TypeCheckResult {
-    let mut inferer = Inferer::new();
-    let mut env = TypeEnv {
-        frames: vec![root_types.iter().cloned().map(Scheme::mono).collect()],
-    };
-    let program_type = inferer.infer_statement(stmt, &mut env);
+    let roots: Vec<Scheme> = root_types.iter().cloned().map(Scheme::mono).collect();
+    check_in(stmt, &roots, None)
+}
+
+/// Like `check`, with a (possibly polymorphic) scheme per root, for a
+/// program that is itself the module `module`: relative imports are
+/// resolved from it.
+pub fn check_in(stmt: &Statement, roots: &[Scheme], module: Option<&Path>) -> TypeCheckResult {
+    let mut inferer = Inferer::new(roots);
+    inferer.importing.extend(module.map(Path::to_path_buf));
+    let program_type = inferer.infer_program(stmt);
     let resolved = program_type.apply(&inferer.subst);
     let subst = inferer.subst;
     let node_types: HashMap<usize, Type> = inferer
@@ -30,6 +51,7 @@ pub fn check(stmt: &Statement, root_types: &[Type]) -> TypeCheckResult {
         program_type: resolved,
         warnings: inferer.warnings,
         node_types,
+        null_checks: inferer.null_checks,
     }
 }
 
@@ -54,18 +76,102 @@ struct Inferer {
     subst: Subst,
     warnings: Vec<Diagnostic>,
     node_types: HashMap<usize, Type>,
+    null_checks: HashSet<usize>,
+    roots: Vec<Scheme>,
+    /// Generalized types of the modules imported so far, by resolved path.
+    imports: HashMap<PathBuf, Scheme>,
+    /// Modules being inferred, outermost first, for reporting cycles. The
+    /// last one is the module relative imports are resolved from.
+    importing: Vec<PathBuf>,
+    /// Set just before inferring an expression whose enclosing literal will
+    /// `widen` it, so a nested literal leaves that to the outermost one.
+    widened_later: bool,
 }
 
 impl Inferer {
-    fn new() -> Self {
+    fn new(roots: &[Scheme]) -> Self {
         Self {
             next_var: INFERER_VAR_START,
             subst: Subst::new(),
             warnings: Vec::new(),
             node_types: HashMap::new(),
+            null_checks: HashSet::new(),
+            roots: roots.to_vec(),
+            imports: HashMap::new(),
+            importing: Vec::new(),
+            widened_later: false,
         }
     }
 
+    fn infer_program(&mut self, stmt: &Statement) -> Type {
+        let mut env = TypeEnv {
+            frames: vec![self.roots.clone()],
+        };
+        self.infer_statement(stmt, &mut env)
+    }
+
+    /// The type of `import("path")` with a literal path: the imported
+    /// program's type, generalized. A record body becomes a `Module` so each
+    /// field keeps its own polymorphic scheme, like a top-level binding.
+    fn import_type(&mut self, raw: &str, span: &Span) -> Type {
+        let path = match imports::resolve(raw, self.importing.last().map(PathBuf::as_path), span) {
+            Ok(path) => path,
+            Err(d) => {
+                self.warnings.push(d);
+                return Type::Any;
+            }
+        };
+        if let Some(sch) = self.imports.get(&path) {
+            let sch = sch.clone();
+            return self.instantiate(&sch);
+        }
+        if let Some(d) = imports::cycle_error(&self.importing, &path, span) {
+            self.warnings.push(d);
+            return Type::Any;
+        }
+        let from = self.importing.last().cloned();
+        let ast = match imports::load(&path, from.as_deref(), span) {
+            Ok(ast) => ast,
+            Err(d) => {
+                self.warnings.push(d);
+                return Type::Any;
+            }
+        };
+
+        // The imported file gets its own substitution and warnings, but
+        // shares the var counter so its schemes never capture our vars.
+        let mut child = Inferer::new(&self.roots);
+        child.next_var = self.next_var;
+        child.imports = std::mem::take(&mut self.imports);
+        child.importing = std::mem::take(&mut self.importing);
+        child.importing.push(path.clone());
+        let ty = child.infer_program(&ast).apply(&child.subst);
+        self.next_var = child.next_var;
+        self.imports = child.imports;
+        self.importing = child.importing;
+        self.importing.pop();
+        for w in child.warnings {
+            self.warnings.push(w.imported(&path, from.as_deref(), span));
+        }
+
+        let closed = |ty: Type| {
+            let mut vars = HashSet::new();
+            ty.free_vars(&mut vars);
+            Scheme {
+                vars: vars.into_iter().collect(),
+                ty,
+            }
+        };
+        let sch = match ty {
+            Type::Record(fields) => Scheme::mono(Type::Module(
+                fields.into_iter().map(|(n, t)| (n, closed(t))).collect(),
+            )),
+            other => closed(other),
+        };
+        self.imports.insert(path, sch.clone());
+        self.instantiate(&sch)
+    }
+
     fn fresh(&mut self) -> Type {
         let v = TypeVar(self.next_var);
         self.next_var += 1;
@@ -98,15 +204,31 @@ impl Inferer {
     }
 
     fn unify(&mut self, a: &Type, b: &Type, span: &Span) {
+        self.unify_at(a, b, span, None);
+    }
+
+    /// `unify` for two expressions that must have the same type, the one at
+    /// `b_span` checked against the one at `a_span`: a mismatch points at
+    /// both.
+    fn unify_sides(&mut self, a: &Type, a_span: &Span, b: &Type, b_span: &Span) {
+        self.unify_at(a, b, b_span, Some(a_span));
+    }
+
+    fn unify_at(&mut self, a: &Type, b: &Type, span: &Span, a_span: Option<&Span>) {
         let a = a.apply(&self.subst);
         let b = b.apply(&self.subst);
         if let Err(reason) = self.unify_inner(&a, &b) {
             let (sa, sb) = crate::types::pretty_pair(&a, &b);
-            self.warnings.push(Diagnostic::new(
+            let mut d = Diagnostic::new(
                 span.clone(),
                 format!("type mismatch: {} vs {}", sa, sb),
                 reason,
-            ));
+            )
+            .with_code("E0201");
+            if let Some(a_span) = a_span {
+                d = d.with_label(a_span.clone(), format!("this is {}", sa));
+            }
+            self.warnings.push(d);
         }
     }
 
@@ -133,6 +255,19 @@ impl Inferer {
                 self.subst.insert(*v, t_resolved);
                 Ok(())
             }
+            // A row var solved since this pair was applied (nested
+            // `unify_inner` calls don't re-apply): retry on the solved types.
+            (Type::Row(_, rho), _) | (_, Type::Row(_, rho)) if self.subst.contains_key(rho) => {
+                let a = a.apply(&self.subst);
+                let b = b.apply(&self.subst);
+                self.unify_inner(&a, &b)
+            }
+            (Type::Nullable(x), Type::Nullable(y)) => self.unify_inner(x, y),
+            (Type::Nullable(_), Type::Null) | (Type::Null, Type::Nullable(_)) => Ok(()),
+            // A plain `T` is also a `T?`. Unification can't tell which side
+            // flows into which, so the unsafe direction is caught where a
+            // possibly-null value is used (operators, `.field`, calls).
+            (Type::Nullable(x), t) | (t, Type::Nullable(x)) => self.unify_inner(x, t),
             (Type::Fn(a1, r1), Type::Fn(a2, r2)) => {
                 if a1.len() != a2.len() {
                     return Err(format!(
@@ -164,6 +299,61 @@ impl Inferer {
                 }
                 Ok(())
             }
+            (Type::Row(fs, rho), Type::Record(cs)) | (Type::Record(cs), Type::Row(fs, rho)) => {
+                for (n, t) in fs {
+                    match cs.iter().find(|(m, _)| m == n) {
+                        Some((_, ct)) => {
+                            let t = t.apply(&self.subst);
+                            let ct = ct.apply(&self.subst);
+                            self.unify_inner(&t, &ct)?
+                        }
+                        None => return Err(format!("missing field '{}'", display(*n))),
+                    }
+                }
+                self.bind_row(*rho, Type::Record(cs.clone()))
+            }
+            (Type::Row(fs, rho), Type::Module(ms)) | (Type::Module(ms), Type::Row(fs, rho)) => {
+                for (n, t) in fs {
+                    match ms.iter().find(|(m, _)| m == n) {
+                        Some((_, sch)) => {
+                            let mt = self.instantiate(sch);
+                            let t = t.apply(&self.subst);
+                            self.unify_inner(&t, &mt)?
+                        }
+                        None => return Err(format!("missing field '{}'", display(*n))),
+                    }
+                }
+                self.bind_row(*rho, Type::Module(ms.clone()))
+            }
+            (Type::Row(f1, r1), Type::Row(f2, r2)) => {
+                let mut merged = f1.clone();
+                for (n, t2) in f2 {
+                    match f1.iter().find(|(m, _)| m == n) {
+                        Some((_, t1)) => {
+                            let t1 = t1.apply(&self.subst);
+                            let t2 = t2.apply(&self.subst);
+                            self.unify_inner(&t1, &t2)?
+                        }
+                        None => merged.push((*n, t2.clone())),
+                    }
+                }
+                // Same tail on both sides: the explicit fields must match
+                // exactly, since a field only one side lists would have to
+                // be in the shared tail and so on the other side too.
+                if r1 == r2 {
+                    if merged.len() != f1.len() {
+                        let (n, _) = &merged[f1.len()];
+                        return Err(format!("missing field '{}'", display(*n)));
+                    }
+                    if let Some((n, _)) = f1.iter().find(|(n, _)| !f2.iter().any(|(m, _)| m == n)) {
+                        return Err(format!("missing field '{}'", display(*n)));
+                    }
+                    return Ok(());
+                }
+                let row = Type::Row(merged, self.fresh_var()).apply(&self.subst);
+                self.bind_row(*r1, row.clone())?;
+                self.bind_row(*r2, row)
+            }
             (Type::Module(f1), Type::Module(f2)) => {
                 if f1.len() != f2.len() {
                     return Err(format!("module fields: {} vs {}", f1.len(), f2.len()));
@@ -181,7 +371,17 @@ impl Inferer {
         }
     }
 
+    /// Solve row variable `rho` to the record (or module) `ty` it stands for.
+    fn bind_row(&mut self, rho: TypeVar, ty: Type) -> Result<(), String> {
+        if ty.contains(rho) {
+            return Err("infinite type (occurs check)".into());
+        }
+        self.subst.insert(rho, ty);
+        Ok(())
+    }
+
     fn infer_statement(&mut self, stmt: &Statement, env: &mut TypeEnv) -> Type {
+        let widened_later = std::mem::take(&mut self.widened_later);
         // Pre-allocate fresh-var slots so siblings can reference each other
         let frame: Vec<Scheme> = stmt
             .definitions
@@ -200,6 +400,7 @@ impl Inferer {
             env.frames.last_mut().unwrap()[i] = gen;
         }
 
+        self.widened_later = widened_later;
         let body_t = self.infer(&stmt.body, env);
         env.frames.pop();
         body_t
@@ -229,6 +430,7 @@ impl Inferer {
     }
 
     fn infer_inner(&mut self, expr: &Spanned<Expr>, env: &mut TypeEnv) -> Type {
+        let widened_later = std::mem::take(&mut self.widened_later);
         match &expr.0 {
             Expr::Number(_) => Type::Number,
             Expr::String(_) => Type::String,
@@ -244,16 +446,21 @@ impl Inferer {
                             | Type::Bool
                             | Type::Null
                             | Type::Any => {}
+                            Type::Nullable(inner)
+                                if matches!(**inner, Type::String | Type::Number | Type::Bool) => {}
                             // Unresolved: default to String (the most common
                             // intent — e.g. `"hello ${name}"` where name is
                             // a polymorphic parameter).
                             Type::Var(_) => self.unify(&t, &Type::String, &e.1),
                             other => {
-                                self.warnings.push(Diagnostic::new(
-                                    e.1.clone(),
-                                    format!("cannot interpolate {} into a string", other),
-                                    "interpolation supports number, string, bool, and null",
-                                ));
+                                self.warnings.push(
+                                    Diagnostic::new(
+                                        e.1.clone(),
+                                        format!("cannot interpolate {} into a string", other),
+                                        "interpolation supports number, string, bool, and null",
+                                    )
+                                    .with_code("E0206"),
+                                );
                             }
                         }
                     }
@@ -262,6 +469,9 @@ impl Inferer {
             }
             Expr::Bool(_) => Type::Bool,
             Expr::Null => Type::Null,
+            // Whatever was meant there; `any` keeps it from causing errors
+            // of its own.
+            Expr::Error => Type::Any,
             Expr::Variable(var) => match var.resolved.get() {
                 Some(bref) => match env.lookup(bref.depth, bref.slot) {
                     Some(sch) => {
@@ -273,10 +483,20 @@ impl Inferer {
                 None => Type::Any,
             },
             Expr::List(items) => {
-                let elem = self.fresh();
+                let mut elem: Option<Type> = None;
                 for it in items {
+                    self.widened_later = true;
                     let t = self.infer(it, env);
-                    self.unify(&elem, &t, &it.1);
+                    elem = Some(match elem {
+                        Some(e) => self.join(&e, &t, &it.1, &items[0].1),
+                        None => t,
+                    });
+                }
+                let elem = elem.unwrap_or_else(|| self.fresh()).apply(&self.subst);
+                if !widened_later {
+                    for it in items {
+                        self.widen(it, &elem);
+                    }
                 }
                 Type::List(Box::new(elem.apply(&self.subst)))
             }
@@ -298,6 +518,7 @@ impl Inferer {
 
                 for (i, (_, body)) in defs.iter().enumerate() {
                     let slot_ty = env.frames.last().unwrap()[i].ty.clone();
+                    self.widened_later = widened_later;
                     let bt = self.infer(body, env);
                     self.unify(&slot_ty, &bt, &body.1);
                     let outer_vars = self.outer_vars_excluding(env, i);
@@ -313,19 +534,28 @@ impl Inferer {
                     .collect();
                 Type::Record(fields)
             }
-            Expr::ImmediateBlock(stmt) => self.infer_statement(stmt, env),
+            Expr::ImmediateBlock(stmt) => {
+                self.widened_later = widened_later;
+                self.infer_statement(stmt, env)
+            }
             Expr::If { cond, cons, alt } => {
                 let ct = self.infer(cond, env);
                 self.unify(&ct, &Type::Bool, &cond.1);
+                self.widened_later = true;
                 let at = self.infer(cons, env);
+                self.widened_later = true;
                 let bt = self.infer(alt, env);
-                self.unify(&at, &bt, &expr.1);
-                at.apply(&self.subst)
+                let t = self.join(&at, &bt, &alt.1, &cons.1);
+                if !widened_later {
+                    self.widen(cons, &t);
+                    self.widen(alt, &t);
+                }
+                t
             }
             Expr::Binary(op, l, r) => {
                 let lt = self.infer(l, env);
                 let rt = self.infer(r, env);
-                self.infer_binop(*op, &lt, &rt, &l.1, &r.1)
+                self.infer_binop(*op, &lt, &rt, l, r)
             }
             Expr::Unary(op, e) => {
                 let t = self.infer(e, env);
@@ -341,58 +571,84 @@ impl Inferer {
                 }
             }
             Expr::Call(callee, args) => {
-                let ct = self.infer(callee, env);
+                if let (Expr::Variable(var), [(Expr::String(path), _)]) = (&callee.0, args.as_slice()) {
+                    let is_root = var
+                        .resolved
+                        .get()
+                        .is_some_and(|bref| bref.depth as usize + 1 == env.frames.len());
+                    if is_root && display(var.name) == "import" {
+                        self.infer(callee, env);
+                        self.infer(&args[0], env);
+                        return self.import_type(path, &expr.1);
+                    }
+                }
+                // `obj?.method(args)` skips the call when `obj` is null.
+                let callee_key = callee.as_ref() as *const _ as usize;
+                let (ct, short) = match &callee.0 {
+                    Expr::OptAccess(obj, (name, name_span)) => {
+                        match self.infer_opt_access(obj, *name, name_span, &callee.1, env) {
+                            Some((t, short)) => {
+                                self.node_types.insert(callee_key, t.clone());
+                                (t, short)
+                            }
+                            None => {
+                                self.node_types.insert(callee_key, Type::Null);
+                                for a in args {
+                                    self.infer(a, env);
+                                }
+                                return Type::Null;
+                            }
+                        }
+                    }
+                    _ => (self.infer(callee, env), false),
+                };
+                self.expect_non_null(&ct, callee);
                 let arg_ts: Vec<Type> = args.iter().map(|a| self.infer(a, env)).collect();
                 let ret = self.fresh();
-                let expected = Type::Fn(arg_ts, Box::new(ret.clone()));
+                let expected = Type::Fn(arg_ts.clone(), Box::new(ret.clone()));
                 self.unify(&ct, &expected, &expr.1);
-                ret.apply(&self.subst)
+                let ct = ct.apply(&self.subst);
+                if let Type::Fn(params, _) = &ct {
+                    for ((a, at), p) in args.iter().zip(&arg_ts).zip(params) {
+                        match p {
+                            Type::Nullable(_) => self.widen(a, p),
+                            Type::Var(_) | Type::Any | Type::Null => {}
+                            _ => self.expect_non_null(at, a),
+                        }
+                    }
+                }
+                // Unifying with `any` binds nothing, so a callee declared to
+                // return `any` (such as `import` of a computed path) would
+                // leave `ret` a free variable.
+                let ret = match &ct {
+                    Type::Fn(_, decl) if matches!(**decl, Type::Any) => Type::Any,
+                    _ => ret.apply(&self.subst),
+                };
+                if short {
+                    ret.nullable()
+                } else {
+                    ret
+                }
             }
             Expr::Access(obj, (name, name_span)) => {
                 let obj_t = self.infer(obj, env).apply(&self.subst);
-                match &obj_t {
-                    Type::Any => Type::Any,
-                    Type::Var(_) => Type::Any,
-                    Type::Record(fields) => fields
-                        .iter()
-                        .find(|(n, _)| n == name)
-                        .map(|(_, t)| t.clone())
-                        .unwrap_or_else(|| {
-                            self.warnings.push(Diagnostic::new(
-                                name_span.clone(),
-                                format!("no field '{}' on {}", display(*name), obj_t),
-                                "field not found in record",
-                            ));
-                            Type::Any
-                        }),
-                    Type::Module(fields) => fields
-                        .iter()
-                        .find(|(n, _)| n == name)
-                        .map(|(_, sch)| {
-                            let sch = sch.clone();
-                            self.instantiate(&sch)
-                        })
-                        .unwrap_or_else(|| {
-                            self.warnings.push(Diagnostic::new(
-                                name_span.clone(),
-                                format!("no field '{}' on {}", display(*name), obj_t),
-                                "field not found in module",
-                            ));
-                            Type::Any
-                        }),
-                    _ => {
-                        self.warnings.push(Diagnostic::new(
-                            expr.1.clone(),
-                            format!("field access on {}", obj_t),
-                            "expected record",
-                        ));
-                        Type::Any
-                    }
+                if let Type::Nullable(_) = obj_t {
+                    self.null_checks.insert(obj.as_ref() as *const _ as usize);
+                }
+                self.access_type(&obj_t, *name, name_span, &obj.1, &expr.1)
+            }
+            Expr::OptAccess(obj, (name, name_span)) => {
+                match self.infer_opt_access(obj, *name, name_span, &expr.1, env) {
+                    None => Type::Null,
+                    Some((t, true)) => t.nullable(),
+                    Some((t, false)) => t,
                 }
             }
             Expr::Index(arr, idx) => {
                 let arr_t = self.infer(arr, env).apply(&self.subst);
                 let idx_t = self.infer(idx, env).apply(&self.subst);
+                self.expect_non_null(&arr_t, arr);
+                self.expect_non_null(&idx_t, idx);
                 match &arr_t {
                     Type::Any => Type::Any,
                     // Unresolved arr type: assume it's a list so callers like
@@ -411,15 +667,431 @@ impl Inferer {
                         elem.as_ref().apply(&self.subst)
                     }
                     _ => {
-                        self.warnings.push(Diagnostic::new(
-                            expr.1.clone(),
-                            format!("indexing {}", arr_t),
-                            "expected list",
-                        ));
+                        self.warnings.push(
+                            Diagnostic::new(
+                                expr.1.clone(),
+                                format!("indexing {}", arr_t),
+                                "expected list",
+                            )
+                            .with_code("E0203"),
+                        );
                         Type::Any
                     }
                 }
             }
+            Expr::Match { scrutinee, arms } => {
+                let st = self.infer(scrutinee, env);
+                let mut result: Option<Type> = None;
+                // Once an unguarded `null` arm has run, later arms only see
+                // the non-null part of a `T?` scrutinee.
+                let mut null_handled = false;
+                for arm in arms {
+                    let expected = match st.apply(&self.subst) {
+                        Type::Nullable(inner) if null_handled => *inner,
+                        _ => st.clone(),
+                    };
+                    let mut frame = Vec::new();
+                    self.infer_pattern(&arm.pattern, &expected, &mut frame);
+                    env.frames.push(frame);
+                    if let Some(guard) = &arm.guard {
+                        let gt = self.infer(guard, env);
+                        self.unify(&gt, &Type::Bool, &guard.1);
+                    }
+                    self.widened_later = true;
+                    let bt = self.infer(&arm.body, env);
+                    result = Some(match result {
+                        Some(r) => self.join(&r, &bt, &arm.body.1, &arms[0].body.1),
+                        None => bt,
+                    });
+                    env.frames.pop();
+                    if arm.guard.is_none() && matches!(arm.pattern.0, Pattern::Null) {
+                        null_handled = true;
+                    }
+                }
+                let result = result.unwrap_or_else(|| self.fresh());
+                if !widened_later {
+                    for arm in arms {
+                        self.widen(&arm.body, &result);
+                    }
+                }
+                let st = st.apply(&self.subst);
+                let rows: Vec<Vec<Pat>> = arms
+                    .iter()
+                    .filter(|arm| arm.guard.is_none())
+                    .map(|arm| vec![Pat::from(&arm.pattern.0)])
+                    .collect();
+                if is_useful_wildcard(rows, vec![st]) {
+                    self.warnings.push(
+                        Diagnostic::new(
+                            expr.1.clone(),
+                            "non-exhaustive match",
+                            "some values are not covered by any arm",
+                        )
+                        .warning()
+                        .with_code("E0205")
+                        .with_help("add the missing cases, or a `_ => ...` arm"),
+                    );
+                }
+                result.apply(&self.subst)
+            }
+        }
+    }
+
+    /// Check `pat` against `expected`, pushing one monomorphic scheme per
+    /// binder onto `frame` in `Pattern::binders` order.
+    fn infer_pattern(&mut self, pat: &Spanned<Pattern>, expected: &Type, frame: &mut Vec<Scheme>) {
+        let span = &pat.1;
+        match &pat.0 {
+            Pattern::Wildcard => {}
+            Pattern::Bind(_) => frame.push(Scheme::mono(expected.apply(&self.subst))),
+            Pattern::Number(_) => self.unify(expected, &Type::Number, span),
+            Pattern::String(_) => self.unify(expected, &Type::String, span),
+            Pattern::Bool(_) => self.unify(expected, &Type::Bool, span),
+            // On a still-unknown scrutinee a `null` arm means "may be null",
+            // not "is always null".
+            Pattern::Null => match expected.apply(&self.subst) {
+                Type::Var(_) => {
+                    let inner = self.fresh();
+                    self.unify(expected, &inner.nullable(), span);
+                }
+                _ => self.unify(expected, &Type::Null, span),
+            },
+            Pattern::List { items, rest } => {
+                let elem = self.fresh();
+                let list_ty = Type::List(Box::new(elem.clone()));
+                self.unify(expected, &list_ty, span);
+                for item in items {
+                    self.infer_pattern(item, &elem, frame);
+                }
+                if let Some(Some(_)) = rest {
+                    frame.push(Scheme::mono(list_ty.apply(&self.subst)));
+                }
+            }
+            Pattern::Record(fields) => {
+                let t = expected.apply(&self.subst);
+                let field_tys: Vec<Type> = match &t {
+                    Type::Any => fields.iter().map(|_| Type::Any).collect(),
+                    Type::Record(known) => fields
+                        .iter()
+                        .map(
+                            |((name, name_span), _)| match known.iter().find(|(n, _)| n == name) {
+                                Some((_, ft)) => ft.clone(),
+                                None => {
+                                    self.warnings.push(
+                                        Diagnostic::new(
+                                            name_span.clone(),
+                                            format!("no field '{}' on {}", display(*name), t),
+                                            "field not found in record",
+                                        )
+                                        .with_code("E0202")
+                                        .did_you_mean(
+                                            display(*name),
+                                            known.iter().map(|(n, _)| display(*n)),
+                                        ),
+                                    );
+                                    Type::Any
+                                }
+                            },
+                        )
+                        .collect(),
+                    // Record patterns only look at the fields they name, so
+                    // anything else is constrained through an open row.
+                    _ => {
+                        let tys: Vec<Type> = fields.iter().map(|_| self.fresh()).collect();
+                        let rec = Type::Row(
+                            fields
+                                .iter()
+                                .zip(tys.iter())
+                                .map(|(((name, _), _), ft)| (*name, ft.clone()))
+                                .collect(),
+                            self.fresh_var(),
+                        );
+                        self.unify(&t, &rec, span);
+                        tys
+                    }
+                };
+                for ((_, sub), ft) in fields.iter().zip(field_tys.iter()) {
+                    match sub {
+                        Some(p) => self.infer_pattern(p, ft, frame),
+                        None => frame.push(Scheme::mono(ft.apply(&self.subst))),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Type of `obj.name` given the (applied) type of `obj`.
+    fn access_type(
+        &mut self,
+        obj_t: &Type,
+        name: Symbol,
+        name_span: &Span,
+        obj_span: &Span,
+        span: &Span,
+    ) -> Type {
+        match obj_t {
+            Type::Any => Type::Any,
+            // Unknown shape: require the field through an open row so
+            // `(r) => r.x` generalizes over every record with an `x`.
+            Type::Var(_) | Type::Row(..) => {
+                let field = self.fresh();
+                let row = Type::Row(vec![(name, field.clone())], self.fresh_var());
+                self.unify(obj_t, &row, obj_span);
+                field.apply(&self.subst)
+            }
+            Type::Record(fields) => fields
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, t)| t.clone())
+                .unwrap_or_else(|| {
+                    self.warnings.push(
+                        Diagnostic::new(
+                            name_span.clone(),
+                            format!("no field '{}' on {}", display(name), obj_t),
+                            "field not found in record",
+                        )
+                        .with_code("E0202")
+                        .did_you_mean(display(name), fields.iter().map(|(n, _)| display(*n))),
+                    );
+                    Type::Any
+                }),
+            Type::Module(fields) => fields
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, sch)| {
+                    let sch = sch.clone();
+                    self.instantiate(&sch)
+                })
+                .unwrap_or_else(|| {
+                    self.warnings.push(
+                        Diagnostic::new(
+                            name_span.clone(),
+                            format!("no field '{}' on {}", display(name), obj_t),
+                            "field not found in module",
+                        )
+                        .with_code("E0202")
+                        .did_you_mean(display(name), fields.iter().map(|(n, _)| display(*n))),
+                    );
+                    Type::Any
+                }),
+            Type::Nullable(inner) => {
+                self.warnings.push(
+                    Diagnostic::new(
+                        span.clone(),
+                        format!("field access on possibly null {}", self.shown(obj_t)),
+                        "may be null",
+                    )
+                    .warning()
+                    .with_code("E0204")
+                    .with_help("use `?.` to skip null"),
+                );
+                self.access_type(inner, name, name_span, obj_span, span)
+            }
+            _ => {
+                self.warnings.push(
+                    Diagnostic::new(
+                        span.clone(),
+                        format!("field access on {}", obj_t),
+                        "expected record",
+                    )
+                    .with_code("E0203"),
+                );
+                Type::Any
+            }
+        }
+    }
+
+    /// `obj?.name`: the field's type and whether `obj` may be null (in which
+    /// case the access short-circuits), or `None` when `obj` is always null.
+    fn infer_opt_access(
+        &mut self,
+        obj: &Spanned<Expr>,
+        name: Symbol,
+        name_span: &Span,
+        span: &Span,
+        env: &mut TypeEnv,
+    ) -> Option<(Type, bool)> {
+        let obj_t = self.infer(obj, env).apply(&self.subst);
+        let (inner, may_be_null) = match &obj_t {
+            Type::Null => return None,
+            Type::Nullable(inner) => (inner.as_ref(), true),
+            t => (t, false),
+        };
+        match inner {
+            // Unknown: `?.` is the programmer saying it may be null.
+            Type::Var(_) => {
+                let field = self.fresh();
+                let row = Type::Row(vec![(name, field.clone())], self.fresh_var());
+                self.unify(inner, &row.nullable(), &obj.1);
+                Some((field.apply(&self.subst), true))
+            }
+            _ => Some((
+                self.access_type(inner, name, name_span, &obj.1, span),
+                may_be_null,
+            )),
+        }
+    }
+
+    /// Merge the types of values that meet in one place (`if` branches, list
+    /// items, `match` arms). Unlike `unify`, `null` and a `T` merge into `T?`,
+    /// also inside lists and records, so `[1, null]` is a `list<number?>`,
+    /// and a field only some of the records have is optional: `[{a: 1},
+    /// {a: 2, b: 3}]` is a `list<{a: number, b: number?}>`.
+    /// `b` is the type of the value at `span`, `a` that of the ones merged
+    /// before it, the first of which is at `a_span`.
+    fn join(&mut self, a: &Type, b: &Type, span: &Span, a_span: &Span) -> Type {
+        let a = a.apply(&self.subst);
+        let b = b.apply(&self.subst);
+        self.join_applied(&a, &b, span, a_span)
+    }
+
+    /// `join` of two types the substitution has already been applied to.
+    fn join_applied(&mut self, a: &Type, b: &Type, span: &Span, a_span: &Span) -> Type {
+        match (a, b) {
+            (Type::Null, Type::Null) => Type::Null,
+            (Type::Null, t) | (t, Type::Null) => t.clone().nullable(),
+            (Type::Nullable(x), Type::Nullable(y)) => {
+                self.join_applied(x, y, span, a_span).nullable()
+            }
+            (Type::Nullable(x), t) | (t, Type::Nullable(x)) => {
+                self.join_applied(x, t, span, a_span).nullable()
+            }
+            (Type::List(x), Type::List(y)) => {
+                Type::List(Box::new(self.join_applied(x, y, span, a_span)))
+            }
+            (Type::Record(f1), Type::Record(f2)) => {
+                let mut fields = Vec::with_capacity(f1.len());
+                for (n, t1) in f1 {
+                    let t = match f2.iter().find(|(m, _)| m == n) {
+                        Some((_, t2)) => self.join_applied(t1, t2, span, a_span),
+                        None => t1.clone().nullable(),
+                    };
+                    fields.push((*n, t));
+                }
+                for (n, t2) in f2 {
+                    if !f1.iter().any(|(m, _)| m == n) {
+                        fields.push((*n, t2.clone().nullable()));
+                    }
+                }
+                Type::Record(fields)
+            }
+            _ => {
+                self.unify_sides(a, a_span, b, span);
+                a.apply(&self.subst)
+            }
+        }
+    }
+
+    /// Record a `join` result as the type of the literal structure under
+    /// `expr` (the `null`s, list and record literals, and branches leading
+    /// to them), so the JIT builds those values directly in the merged
+    /// layout — a `null` item of a `list<number?>` becomes a null `number?`.
+    /// Only the outermost literal calls this: it reaches the nested ones.
+    fn widen(&mut self, expr: &Spanned<Expr>, target: &Type) {
+        let target = target.apply(&self.subst);
+        self.widen_applied(expr, &target);
+    }
+
+    /// `widen` to a target the substitution has already been applied to.
+    fn widen_applied(&mut self, expr: &Spanned<Expr>, target: &Type) {
+        let key = expr as *const _ as usize;
+        match (&expr.0, target) {
+            (Expr::Null, Type::Nullable(_)) => {
+                self.node_types.insert(key, target.clone());
+            }
+            (Expr::If { cons, alt, .. }, _) => {
+                self.node_types.insert(key, target.clone());
+                self.widen_applied(cons, target);
+                self.widen_applied(alt, target);
+            }
+            (Expr::Match { arms, .. }, _) => {
+                self.node_types.insert(key, target.clone());
+                for arm in arms {
+                    self.widen_applied(&arm.body, target);
+                }
+            }
+            (Expr::ImmediateBlock(stmt), _) => {
+                self.node_types.insert(key, target.clone());
+                self.widen_applied(&stmt.body, target);
+            }
+            // A list or record literal is never null itself.
+            (Expr::List(_) | Expr::Block(_), Type::Nullable(inner)) => {
+                self.widen_applied(expr, inner)
+            }
+            (Expr::List(items), Type::List(elem)) => {
+                self.node_types.insert(key, target.clone());
+                for it in items {
+                    self.widen_applied(it, elem);
+                }
+            }
+            (Expr::Block(defs), Type::Record(fields))
+                if defs.iter().all(|((name, _), _)| fields.iter().any(|(n, _)| n == name)) =>
+            {
+                // The literal keeps its own fields, in its own order, each
+                // with the type its value is built at: that is its layout.
+                // The JIT converts it where a record of the merged layout
+                // is expected.
+                let mut own = Vec::with_capacity(defs.len());
+                for ((name, _), body) in defs {
+                    let (_, t) = fields.iter().find(|(n, _)| n == name).expect("checked above");
+                    self.widen_applied(body, t);
+                    let built = self.node_types.get(&(body as *const _ as usize)).unwrap_or(t);
+                    own.push((*name, built.clone()));
+                }
+                self.node_types.insert(key, Type::Record(own));
+            }
+            // A target that says nothing about the literal's shape (`any`,
+            // or a mismatch already reported): merge inside it by its own
+            // type, as it would have if it weren't nested.
+            (Expr::List(_) | Expr::Block(_), _) => {
+                let own = self.node_types.get(&key).map(|t| t.apply(&self.subst));
+                if let Some(own @ (Type::List(_) | Type::Record(_))) = own {
+                    self.widen_applied(expr, &own);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `t` as written in a message: solved as far as it goes, with what is
+    /// still unknown shown as `any` rather than as a type variable.
+    fn shown(&self, t: &Type) -> Type {
+        fn erase(t: &Type) -> Type {
+            match t {
+                Type::Var(_) => Type::Any,
+                Type::Fn(args, ret) => {
+                    Type::Fn(args.iter().map(erase).collect(), Box::new(erase(ret)))
+                }
+                Type::List(t) => Type::List(Box::new(erase(t))),
+                Type::Record(fields) => {
+                    Type::Record(fields.iter().map(|(n, t)| (*n, erase(t))).collect())
+                }
+                Type::Row(fields, rho) => {
+                    Type::Row(fields.iter().map(|(n, t)| (*n, erase(t))).collect(), *rho)
+                }
+                // Not `nullable()`: `any?` still says the value may be null.
+                Type::Nullable(t) => Type::Nullable(Box::new(erase(t))),
+                t => t.clone(),
+            }
+        }
+        erase(&t.apply(&self.subst))
+    }
+
+    /// Warn when an operand that cannot be null may be.
+    fn expect_non_null(&mut self, t: &Type, expr: &Spanned<Expr>) {
+        let t = t.apply(&self.subst);
+        if let Type::Nullable(_) = t {
+            self.null_checks.insert(expr as *const _ as usize);
+            self.warnings.push(
+                Diagnostic::new(
+                    expr.1.clone(),
+                    format!("possibly null value of type {}", self.shown(&t)),
+                    "may be null",
+                )
+                .warning()
+                .with_code("E0204")
+                .with_help("use `??` to supply a default"),
+            );
         }
     }
 
@@ -428,29 +1100,250 @@ impl Inferer {
         op: BinOp,
         lt: &Type,
         rt: &Type,
-        ls: &Span,
-        rs: &Span,
+        l: &Spanned<Expr>,
+        r: &Spanned<Expr>,
     ) -> Type {
+        let (ls, rs) = (&l.1, &r.1);
         match op {
             BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+                self.expect_non_null(lt, l);
+                self.expect_non_null(rt, r);
                 self.unify(lt, &Type::Number, ls);
                 self.unify(rt, &Type::Number, rs);
                 Type::Number
             }
             BinOp::Gt | BinOp::Lt | BinOp::Ge | BinOp::Le => {
+                self.expect_non_null(lt, l);
+                self.expect_non_null(rt, r);
                 self.unify(lt, &Type::Number, ls);
                 self.unify(rt, &Type::Number, rs);
                 Type::Bool
             }
             BinOp::Eq | BinOp::Ne => {
-                self.unify(lt, rt, rs);
+                self.unify_sides(lt, ls, rt, rs);
                 Type::Bool
             }
             BinOp::And | BinOp::Or => {
+                self.expect_non_null(lt, l);
+                self.expect_non_null(rt, r);
                 self.unify(lt, &Type::Bool, ls);
                 self.unify(rt, &Type::Bool, rs);
                 Type::Bool
             }
+            BinOp::Coalesce => match lt.apply(&self.subst) {
+                Type::Null => rt.apply(&self.subst),
+                // `a ?? b` on an unknown `a`: it may be null, and then `b`
+                // stands in for it.
+                Type::Var(_) => {
+                    let inner = self.fresh();
+                    self.unify(lt, &inner.clone().nullable(), ls);
+                    self.unify(&inner, rt, rs);
+                    rt.apply(&self.subst)
+                }
+                Type::Nullable(inner) => {
+                    self.unify_sides(&inner, ls, rt, rs);
+                    rt.apply(&self.subst)
+                }
+                // Never null: `b` is dead, but must still fit.
+                other => {
+                    self.unify_sides(&other, ls, rt, rs);
+                    other.apply(&self.subst)
+                }
+            },
+        }
+    }
+}
+
+// === Match exhaustiveness ====================================================
+//
+// A small version of Maranget's usefulness check: the match is exhaustive iff
+// a row of wildcards is *not* useful with respect to the (guard-free) arms.
+// Column types steer constructor enumeration: bools and nulls are finite,
+// records have a single constructor, lists are split by length (every length
+// up to the longest pattern, plus one "longer than that" bucket), and
+// numbers/strings are treated as infinite so they only count as covered by a
+// wildcard or binder.
+
+#[derive(Clone)]
+enum Pat {
+    Any,
+    /// A number or string literal. Their domains are infinite, so the only
+    /// thing exhaustiveness needs to know is that they are not wildcards.
+    Literal,
+    Bool(bool),
+    Null,
+    List(Vec<Pat>, bool),
+    Record(Vec<(Symbol, Pat)>),
+}
+
+impl From<&Pattern> for Pat {
+    fn from(p: &Pattern) -> Self {
+        match p {
+            Pattern::Wildcard | Pattern::Bind(_) => Pat::Any,
+            Pattern::Number(_) | Pattern::String(_) => Pat::Literal,
+            Pattern::Bool(b) => Pat::Bool(*b),
+            Pattern::Null => Pat::Null,
+            Pattern::List { items, rest } => Pat::List(
+                items.iter().map(|(p, _)| Pat::from(p)).collect(),
+                rest.is_some(),
+            ),
+            Pattern::Record(fields) => Pat::Record(
+                fields
+                    .iter()
+                    .map(|((name, _), sub)| {
+                        (*name, sub.as_ref().map_or(Pat::Any, |(p, _)| Pat::from(p)))
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Ctor {
+    Bool(bool),
+    Null,
+    /// A list of exactly this many elements.
+    Len(usize),
+    /// Any list longer than the longest pattern in the column; carries the
+    /// number of element columns to specialize into.
+    LongerThan(usize),
+    /// The single record constructor, specialized into one column per
+    /// field named anywhere in the column.
+    Record(Vec<Symbol>),
+}
+
+fn is_useful_wildcard(rows: Vec<Vec<Pat>>, tys: Vec<Type>) -> bool {
+    if tys.is_empty() {
+        return rows.is_empty();
+    }
+    let heads: Vec<&Pat> = rows.iter().map(|r| &r[0]).collect();
+    let ctors = column_ctors(&heads, &tys[0]);
+    match ctors {
+        Some(ctors) => ctors.into_iter().any(|(c, sub_tys)| {
+            let specialized: Vec<Vec<Pat>> = rows
+                .iter()
+                .filter_map(|r| specialize(r, &c, sub_tys.len()))
+                .collect();
+            let mut next_tys = sub_tys;
+            next_tys.extend(tys[1..].iter().cloned());
+            is_useful_wildcard(specialized, next_tys)
+        }),
+        // Infinite constructor set: only wildcard heads can cover it.
+        None => {
+            let default: Vec<Vec<Pat>> = rows
+                .iter()
+                .filter(|r| matches!(r[0], Pat::Any))
+                .map(|r| r[1..].to_vec())
+                .collect();
+            is_useful_wildcard(default, tys[1..].to_vec())
+        }
+    }
+}
+
+/// Enumerate the constructors of a column (with the types of the columns
+/// each one specializes into), or `None` when the set is infinite.
+fn column_ctors(heads: &[&Pat], ty: &Type) -> Option<Vec<(Ctor, Vec<Type>)>> {
+    let has = |f: fn(&Pat) -> bool| heads.iter().any(|p| f(p));
+    match ty {
+        Type::Bool => Some(vec![
+            (Ctor::Bool(true), vec![]),
+            (Ctor::Bool(false), vec![]),
+        ]),
+        Type::Null => Some(vec![(Ctor::Null, vec![])]),
+        Type::Nullable(t) => column_ctors(heads, t).map(|mut ctors| {
+            ctors.push((Ctor::Null, vec![]));
+            ctors
+        }),
+        Type::List(elem) => Some(list_ctors(heads, elem)),
+        Type::Record(fields) | Type::Row(fields, _) => {
+            Some(vec![record_ctor(heads, Some(fields))])
+        }
+        Type::Number | Type::String | Type::Fn(_, _) | Type::Module(_) => None,
+        // Unknown column type: infer the constructor family from the heads.
+        Type::Any | Type::Var(_) => {
+            if has(|p| matches!(p, Pat::Bool(_))) {
+                column_ctors(heads, &Type::Bool)
+            } else if has(|p| matches!(p, Pat::List(..))) {
+                Some(list_ctors(heads, &Type::Any))
+            } else if has(|p| matches!(p, Pat::Record(_))) {
+                Some(vec![record_ctor(heads, None)])
+            } else if has(|p| matches!(p, Pat::Null)) {
+                column_ctors(heads, &Type::Null)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn list_ctors(heads: &[&Pat], elem: &Type) -> Vec<(Ctor, Vec<Type>)> {
+    let longest = heads
+        .iter()
+        .filter_map(|p| match p {
+            Pat::List(items, _) => Some(items.len()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    let mut out: Vec<(Ctor, Vec<Type>)> = (0..=longest)
+        .map(|n| (Ctor::Len(n), vec![elem.clone(); n]))
+        .collect();
+    out.push((
+        Ctor::LongerThan(longest + 1),
+        vec![elem.clone(); longest + 1],
+    ));
+    out
+}
+
+/// The record constructor for a column: one sub-column per field named by
+/// some head pattern, typed from the record type when it is known.
+fn record_ctor(heads: &[&Pat], fields: Option<&Vec<(Symbol, Type)>>) -> (Ctor, Vec<Type>) {
+    let mut names: Vec<Symbol> = Vec::new();
+    for p in heads {
+        if let Pat::Record(fs) = p {
+            for (n, _) in fs {
+                if !names.contains(n) {
+                    names.push(*n);
+                }
+            }
         }
     }
+    let tys = names
+        .iter()
+        .map(|name| {
+            fields
+                .and_then(|fs| fs.iter().find(|(n, _)| n == name))
+                .map_or(Type::Any, |(_, t)| t.clone())
+        })
+        .collect();
+    (Ctor::Record(names), tys)
+}
+
+/// Specialize one row by constructor `c`, or drop it (`None`) when its head
+/// cannot match `c`.
+fn specialize(row: &[Pat], c: &Ctor, arity: usize) -> Option<Vec<Pat>> {
+    let mut out: Vec<Pat> = match (&row[0], c) {
+        (Pat::Any, _) => vec![Pat::Any; arity],
+        (Pat::Bool(b), Ctor::Bool(c)) if b == c => vec![],
+        (Pat::Null, Ctor::Null) => vec![],
+        (Pat::List(items, false), Ctor::Len(n)) if items.len() == *n => items.clone(),
+        (Pat::List(items, true), Ctor::Len(n) | Ctor::LongerThan(n)) if items.len() <= *n => {
+            let mut cols = items.clone();
+            cols.resize(*n, Pat::Any);
+            cols
+        }
+        (Pat::Record(fields), Ctor::Record(names)) => names
+            .iter()
+            .map(|name| {
+                fields
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map_or(Pat::Any, |(_, p)| p.clone())
+            })
+            .collect(),
+        _ => return None,
+    };
+    out.extend(row[1..].iter().cloned());
+    Some(out)
 }
@@ -16,6 +16,14 @@ pub enum Type {
     Fn(Vec<Type>, Box<Type>),
     List(Box<Type>),
     Record(Vec<(Symbol, Type)>),
+    /// Open record `{x: number | ρ}`: some record that has at least these
+    /// fields. The row variable stands for the whole record, so once it is
+    /// solved `apply` yields the solved type itself (keeping its field order)
+    /// rather than splicing fields together.
+    Row(Vec<(Symbol, Type)>, TypeVar),
+    /// `T?` — either a `T` or `null`. Build it with `Type::nullable`, which
+    /// keeps it flat (`T??` is `T?`, `null?` is `null`).
+    Nullable(Box<Type>),
     /// Module-like value with potentially polymorphic field schemes.
     /// Used for builtin modules (List, String, Iterator…); each field can
     /// be instantiated independently when accessed.
@@ -25,6 +33,13 @@ pub enum Type {
 pub type Subst = HashMap<TypeVar, Type>;
 
 impl Type {
+    pub fn nullable(self) -> Type {
+        match self {
+            Type::Null | Type::Nullable(_) | Type::Any => self,
+            t => Type::Nullable(Box::new(t)),
+        }
+    }
+
     pub fn apply(&self, s: &Subst) -> Type {
         match self {
             Type::Var(v) => match s.get(v) {
@@ -39,6 +54,16 @@ impl Type {
             Type::Record(fields) => {
                 Type::Record(fields.iter().map(|(n, t)| (*n, t.apply(s))).collect())
             }
+            Type::Row(fields, rho) => match s.get(rho) {
+                // Renamed (instantiation) — still open.
+                Some(Type::Var(v)) => Type::Row(fields.clone(), *v).apply(s),
+                Some(t) => t.apply(s),
+                None => Type::Row(
+                    fields.iter().map(|(n, t)| (*n, t.apply(s))).collect(),
+                    *rho,
+                ),
+            },
+            Type::Nullable(t) => t.apply(s).nullable(),
             Type::Module(fields) => Type::Module(
                 fields
                     .iter()
@@ -66,8 +91,9 @@ impl Type {
         match self {
             Type::Var(v) => *v == var,
             Type::Fn(args, ret) => ret.contains(var) || args.iter().any(|a| a.contains(var)),
-            Type::List(t) => t.contains(var),
+            Type::List(t) | Type::Nullable(t) => t.contains(var),
             Type::Record(fields) => fields.iter().any(|(_, t)| t.contains(var)),
+            Type::Row(fields, rho) => *rho == var || fields.iter().any(|(_, t)| t.contains(var)),
             Type::Module(fields) => fields.iter().any(|(_, sch)| {
                 !sch.vars.contains(&var) && sch.ty.contains(var)
             }),
@@ -86,12 +112,18 @@ impl Type {
                 }
                 ret.free_vars(set);
             }
-            Type::List(t) => t.free_vars(set),
+            Type::List(t) | Type::Nullable(t) => t.free_vars(set),
             Type::Record(fields) => {
                 for (_, t) in fields {
                     t.free_vars(set);
                 }
             }
+            Type::Row(fields, rho) => {
+                for (_, t) in fields {
+                    t.free_vars(set);
+                }
+                set.insert(*rho);
+            }
             Type::Module(fields) => {
                 for (_, sch) in fields {
                     sch.free_vars(set);
@@ -200,6 +232,27 @@ fn fmt_type(ty: &Type, ren: &mut Renamer, f: &mut fmt::Formatter<'_>) -> fmt::Re
             }
             f.write_str("}")
         }
+        Type::Row(fields, rho) => {
+            f.write_str("{")?;
+            for (i, (n, t)) in fields.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}: ", display(*n))?;
+                fmt_type(t, ren, f)?;
+            }
+            write!(f, " | {}}}", ren.name(*rho))
+        }
+        Type::Nullable(t) => {
+            if matches!(**t, Type::Fn(..)) {
+                f.write_str("(")?;
+                fmt_type(t, ren, f)?;
+                f.write_str(")?")
+            } else {
+                fmt_type(t, ren, f)?;
+                f.write_str("?")
+            }
+        }
         Type::Module(fields) => {
             f.write_str("module{")?;
             for (i, (n, sch)) in fields.iter().enumerate() {
@@ -0,0 +1,116 @@
+//! The `spctr` binary: flags, subcommands and exit codes.
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run `spctr args` with `stdin`, returning its exit code, stdout and
+/// stderr.
+fn spctr(args: &[&str], stdin: &str) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_spctr"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A run that does not read stdin may have exited already.
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    let out = child.wait_with_output().unwrap();
+    (
+        out.status.code().unwrap(),
+        String::from_utf8(out.stdout).unwrap(),
+        String::from_utf8(out.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn check_fails_on_errors_only() {
+    let check = |src: &str| spctr(&["--check", "--diagnostics-format=json", "-c", src], "");
+    let (code, _, err) = check("u: if true then {a: 1} else null,\nu.a");
+    assert_eq!(code, 0, "{}", err);
+    assert!(err.contains(r#""severity":"warning""#), "{}", err);
+    let (code, _, err) = check("1 + \"a\"");
+    assert_eq!(code, 1);
+    assert!(err.contains(r#""severity":"error""#), "{}", err);
+}
+
+const DOCUMENT: &str = r#"{"items": [{"name": "a", "tags": ["x"]}, {"name": "b"}], "n": 2}"#;
+
+#[test]
+fn query_reads_the_document_as_dot() {
+    let (code, out, err) = spctr(&["query", ".", "--compact"], DOCUMENT);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "{\"items\":[{\"name\":\"a\",\"tags\":[\"x\"]},{\"name\":\"b\"}],\"n\":2}\n");
+    let (code, out, err) = spctr(&["query", ".n + 1"], DOCUMENT);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "3\n");
+    let (code, _, err) = spctr(&["query", ".itemz", "--diagnostics-format=json"], DOCUMENT);
+    assert_eq!(code, 1);
+    assert!(err.contains("no field 'itemz'"), "{}", err);
+    // Fields only some items have are optional, the others are still checked.
+    let (code, _, err) = spctr(&["query", "List.map(.items, (i) => i.nam)"], DOCUMENT);
+    assert_eq!(code, 1);
+    assert!(err.contains("missing field 'nam'"), "{}", err);
+    // Outside a query `.` is not an expression.
+    let (code, _, _) = spctr(&["-c", "."], "");
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn query_binds_the_document_to_name() {
+    let (code, out, err) = spctr(&["query", "--name", "doc", "doc.n * .n"], DOCUMENT);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "4\n");
+}
+
+#[test]
+fn query_streams_lists_as_json_lines() {
+    let (code, out, err) = spctr(&["query", "List.map(.items, (i) => i.name)"], DOCUMENT);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "\"a\"\n\"b\"\n");
+    let (code, out, err) = spctr(&["query", "--no-stream", "--compact", ".items[1]"], DOCUMENT);
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "{\"name\":\"b\"}\n");
+}
+
+#[test]
+fn query_rejects_deep_documents_quickly() {
+    let deep = format!("{}{}", "[".repeat(3000), "]".repeat(3000));
+    let (code, _, err) = spctr(&["query", ".", "--diagnostics-format=json"], &deep);
+    assert_eq!(code, 1);
+    assert!(err.contains("recursion limit exceeded"), "{}", err);
+}
+
+#[test]
+fn args_are_bound_only_when_given() {
+    let (code, out, err) = spctr(&["-c", "args.x", "--arg", "x=1"], "");
+    assert_eq!(code, 0, "{}", err);
+    assert_eq!(out, "\"1\"\n");
+    let (code, _, err) = spctr(&["-c", "args", "--diagnostics-format=json"], "");
+    assert_eq!(code, 1);
+    assert!(err.contains("undefined variable: args"), "{}", err);
+    let (code, _, err) = spctr(&["--jit", "-c", "args", "--diagnostics-format=json"], "");
+    assert_eq!(code, 1);
+    assert!(err.contains("`args` is not supported with --jit"), "{}", err);
+    for flag in ["--compact", "--sort-keys"] {
+        let (code, _, err) = spctr(&["--jit", flag, "-c", "1"], "");
+        assert_eq!(code, 2);
+        assert!(err.contains("cannot be used with"), "{}", err);
+    }
+}
+
+#[test]
+fn jit_runs_programs_with_warnings() {
+    let jit = |src: &str| spctr(&["--jit", "--diagnostics-format=json", "-c", src], "");
+    let (code, out, err) = jit("match 1 { 1 => 2 }");
+    assert_eq!((code, out.as_str()), (0, "2\n"), "{}", err);
+    assert!(err.contains(r#""severity":"warning""#), "{}", err);
+    let (code, out, err) = jit("x: if true then {a: 1} else null, x.a + 1");
+    assert_eq!((code, out.as_str()), (0, "2\n"), "{}", err);
+    // What the warnings were about fails cleanly when it happens.
+    let (code, _, err) = jit("match 3 { 1 => 2 }");
+    assert_eq!(code, 1);
+    assert!(err.contains("no match arm matched"), "{}", err);
+    let (code, _, err) = jit("x: if false then 1 else null, x + 1");
+    assert_eq!(code, 1);
+    assert!(err.contains("unexpected null"), "{}", err);
+}
@@ -0,0 +1,169 @@
+//! serde conversions between `interp::Value` and Rust data.
+use serde::{Deserialize, Serialize};
+use spctr::{from_value, interp, parser, resolver, to_value};
+
+fn eval(src: &str) -> interp::Value {
+    let ast = parser::parse(src).expect("parse");
+    resolver::resolve(&ast, &interp::ROOT_NAMES).expect("resolve");
+    interp::run(&ast).expect("run")
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Config {
+    name: String,
+    replicas: u32,
+    ratio: f64,
+    tags: Vec<String>,
+    owner: Option<String>,
+    mode: Mode,
+    limits: Limits,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+enum Mode {
+    Fast,
+    Slow { delay: u32 },
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+struct Limits {
+    cpu: f64,
+}
+
+#[test]
+fn deserialize_program_result() {
+    let v = eval(
+        r#"
+        base: 2,
+        {
+            name: "web",
+            replicas: base * 3,
+            ratio: 0.5,
+            tags: List.map(["a", "b"], (t) => "${t}!"),
+            owner: null,
+            mode: {Slow: {delay: base}},
+            limits: {cpu: 1.5}
+        }
+        "#,
+    );
+    let config: Config = from_value(v).unwrap();
+    assert_eq!(
+        config,
+        Config {
+            name: "web".into(),
+            replicas: 6,
+            ratio: 0.5,
+            tags: vec!["a!".into(), "b!".into()],
+            owner: None,
+            mode: Mode::Slow { delay: 2 },
+            limits: Limits { cpu: 1.5 },
+        }
+    );
+    let mode: Mode = from_value(eval(r#""Fast""#)).unwrap();
+    assert_eq!(mode, Mode::Fast);
+    let err = from_value::<u32>(eval("1.5")).unwrap_err();
+    assert!(err.to_string().contains("invalid type"), "{err}");
+    let err = from_value::<Limits>(eval("{cpu: (x) => x}")).unwrap_err();
+    assert_eq!(err.to_string(), "cannot deserialize a function");
+    let err = from_value::<Limits>(eval(r#"{cpu: List.head([])}"#)).unwrap_err();
+    assert_eq!(err.to_string(), "List.head on empty list");
+    // The field's own diagnostic, pointing into the program.
+    let spctr::convert::Error::Eval(d) = err else {
+        panic!("{err:?}")
+    };
+    assert_eq!(d.span, 6..19);
+}
+
+#[test]
+fn serialize_and_inject() {
+    let v = eval(r#"{b: [1, 2.5, null], a: {on: true}, c: "x"}"#);
+    let json = serde_json::Value::try_from(v).unwrap();
+    assert_eq!(json.to_string(), r#"{"a":{"on":true},"b":[1,2.5,null],"c":"x"}"#);
+    assert!(serde_json::Value::try_from(eval("(x) => x")).is_err());
+
+    let host = Config {
+        name: "db".into(),
+        replicas: 1,
+        ratio: 0.25,
+        tags: vec![],
+        owner: Some("ops".into()),
+        mode: Mode::Fast,
+        limits: Limits { cpu: 2.0 },
+    };
+    let v = to_value(&host).unwrap();
+    assert_eq!(
+        v.to_string(),
+        r#"{"limits": {"cpu": 2}, "mode": "Fast", "name": "db", "owner": "ops", "ratio": 0.25, "replicas": 1, "tags": []}"#
+    );
+    assert_eq!(from_value::<Config>(v).unwrap(), host);
+}
+
+#[test]
+fn json_types_for_injected_data() {
+    let ty = |json: &str| {
+        let s = spctr::convert::data_scheme(&serde_json::from_str::<interp::Value>(json).unwrap());
+        format!("{} / {} vars", s.ty, s.vars.len())
+    };
+    assert_eq!(
+        ty(r#"{"name": "web", "ports": [80, null], "on": true}"#),
+        "{name: string, ports: list<number?>, on: bool} / 0 vars"
+    );
+    assert_eq!(ty(r#"[[], [1]]"#), "list<list<number>> / 1 vars");
+    assert_eq!(ty(r#"[{"a": 1}, {"a": null}]"#), "list<{a: number?}> / 0 vars");
+    assert_eq!(ty(r#"[{"a": 1, "b": ""}, {"b": "", "a": 2}]"#), "list<{a: number, b: string}> / 0 vars");
+    assert_eq!(ty(r#"[1, "a", null]"#), "list<any> / 0 vars");
+    assert_eq!(ty(r#"[{"a": 1}, {"b": 1}]"#), "list<{a: number?, b: number?}> / 0 vars");
+    assert_eq!(ty(r#"[{"a": 1}, {"a": "x"}]"#), "list<{a: any}> / 0 vars");
+}
+
+#[test]
+fn json_documents_convert_directly() {
+    let parse = |src: &str| match spctr::convert::parse_json(src) {
+        Ok((v, s)) => format!("{v} : {}", s.ty),
+        Err(d) => format!("{} @ {:?}", d.message, d.span),
+    };
+    assert_eq!(
+        parse(r#"{"c": "${x}A", "a": [1, null], "b": -2.5e1}"#),
+        r#"{"a": [1, null], "b": -25, "c": "${x}A"} : {c: string, a: list<number?>, b: number}"#
+    );
+    assert_eq!(parse("[]"), "[] : list<α>");
+    // Records with optional fields, and mixed arrays, are data like any other.
+    assert_eq!(
+        parse(r#"{"items":[{"name":"a","tags":["x"]},{"name":"b"}]}"#),
+        r#"{"items": [{"name": "a", "tags": ["x"]}, {"name": "b"}]} : {items: list<{name: string, tags: list<string>?}>}"#
+    );
+    assert_eq!(parse(r#"[1,"a",null]"#), r#"[1, "a", null] : list<any>"#);
+    assert_eq!(parse("[1,\n List.head([1])]"), "expected value @ 5..5");
+    assert_eq!(parse("x: 1, x"), "expected value @ 0..0");
+    let deep = format!("{}{}", "[".repeat(3000), "]".repeat(3000));
+    assert_eq!(parse(&deep), "recursion limit exceeded @ 127..127");
+}
+
+#[test]
+fn injected_integers_stay_exact() {
+    use std::collections::BTreeMap;
+    assert_eq!(to_value(&(1u64 << 53)).unwrap().to_string(), "9007199254740992");
+    assert_eq!(to_value(&-(1i64 << 53)).unwrap().to_string(), "-9007199254740992");
+    let err = to_value(&((1u64 << 53) + 1)).unwrap_err();
+    assert_eq!(err.to_string(), "9007199254740993 cannot be held exactly by a number");
+    assert!(to_value(&u64::MAX).is_err());
+    assert!(to_value(&i64::MIN).is_err());
+    assert!(to_value(&u128::MAX).is_err());
+
+    // Shapes serde_json had no say in: integer keys, tuples, bytes, and
+    // variants the deserializer reads back.
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    enum Shape {
+        Point(f64, f64),
+        Named { id: u32 },
+    }
+    let keyed: BTreeMap<u32, &str> = [(1, "a"), (2, "b")].into_iter().collect();
+    assert_eq!(to_value(&keyed).unwrap().to_string(), r#"{"1": "a", "2": "b"}"#);
+    assert_eq!(to_value(&(1, "x", ())).unwrap().to_string(), r#"[1, "x", null]"#);
+    let shapes = vec![Shape::Point(1.0, 2.5), Shape::Named { id: 7 }];
+    let v = to_value(&shapes).unwrap();
+    assert_eq!(v.to_string(), r#"[{"Point": [1, 2.5]}, {"Named": {"id": 7}}]"#);
+    assert_eq!(from_value::<Vec<Shape>>(v).unwrap(), shapes);
+    let err = to_value(&[((), 1)].into_iter().collect::<BTreeMap<_, _>>()).unwrap_err();
+    assert_eq!(err.to_string(), "a record's field names are strings, got null");
+}
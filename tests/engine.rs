@@ -0,0 +1,128 @@
+//! Embedding through `engine::Engine`: host bindings, resolvers, limits.
+use spctr::diag::Severity;
+use spctr::engine::{Engine, HostModule};
+use spctr::interp::Value;
+use spctr::stdlib::imports::MapResolver;
+use spctr::types::{Scheme, Type, TypeVar};
+
+fn eval(engine: &Engine, src: &str) -> String {
+    match engine.eval_str(src) {
+        Ok(out) => out.value.to_string(),
+        Err(ds) => ds
+            .iter()
+            .map(|d| format!("{}: {}", d.message, d.label))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn host_engine() -> Engine {
+    let num_fn = Type::Fn(vec![Type::Number], Box::new(Type::Number));
+    let mut engine = Engine::new();
+    engine
+        .register_fn(
+            "pair",
+            Scheme {
+                vars: vec![TypeVar(0)],
+                ty: Type::Fn(
+                    vec![Type::Var(TypeVar(0))],
+                    Box::new(Type::List(Box::new(Type::Var(TypeVar(0))))),
+                ),
+            },
+            |args, _| Ok(Value::List(std::rc::Rc::new(vec![args[0].clone(), args[0].clone()]))),
+        )
+        .register_module(
+            "Host",
+            HostModule::new().function("twice", Scheme::mono(num_fn), |args, _| match args[0] {
+                Value::Number(n) => Ok(Value::Number(n * 2.0)),
+                _ => unreachable!(),
+            }),
+        );
+    engine
+}
+
+#[test]
+fn host_bindings_are_roots() {
+    let engine = host_engine();
+    assert_eq!(eval(&engine, "Host.twice(21)"), "42");
+    // A polymorphic host scheme is instantiated per use.
+    assert_eq!(
+        eval(&engine, r#"{a: pair(1), b: pair("a")}"#),
+        r#"{"a": [1, 1], "b": ["a", "a"]}"#
+    );
+    assert_eq!(
+        eval(&engine, r#"Host.twice("x")"#),
+        "type mismatch: (number) -> number vs (string) -> α: incompatible: number vs string"
+    );
+    assert_eq!(
+        eval(&engine, "Host.thrice(1)"),
+        "no field 'thrice' on module{twice: (number) -> number}: field not found in module"
+    );
+    assert_eq!(eval(&Engine::new(), "pair(1)"), "undefined variable: pair: not found in scope");
+}
+
+#[test]
+fn eval_file_goes_through_the_resolver() {
+    let mut engine = host_engine();
+    let files: MapResolver = [
+        ("app/main.spc", r#"lib: import("./lib.spc"), lib.quad(5)"#),
+        ("app/lib.spc", "{quad: (x) => Host.twice(Host.twice(x))}"),
+        ("app/loop.spc", r#"import("./loop.spc")"#),
+    ]
+    .into_iter()
+    .collect();
+    engine.set_resolver(files);
+    assert_eq!(engine.eval_file("app/main.spc").unwrap().value.to_string(), "20");
+    let errs = engine.eval_file("app/loop.spc").unwrap_err();
+    assert_eq!(errs[0].message, "import cycle: app/loop.spc -> app/loop.spc");
+    let errs = engine.eval_file("missing.spc").unwrap_err();
+    assert_eq!(errs[0].message, "no module missing.spc");
+}
+
+#[test]
+fn engine_limits_and_errors() {
+    let mut engine = Engine::new();
+    engine.set_max_depth(100);
+    let out = eval(&engine, "f: (n) => if n == 0 then 0 else 1 + f(n - 1), f(1000)");
+    assert!(out.starts_with("maximum recursion depth of 100 exceeded"), "{out}");
+    assert_eq!(eval(&Engine::new(), "f: (n) => if n == 0 then 0 else 1 + f(n - 1), f(1000)"), "1000");
+    assert_eq!(engine.eval_str("1 +").unwrap_err().len(), 1);
+}
+
+#[test]
+fn warnings_come_back_with_the_value() {
+    let engine = Engine::new();
+    let out = engine.eval_str("u: if true then {a: 1} else null,\nu.a").unwrap();
+    assert_eq!(out.value.to_string(), "1");
+    assert_eq!(out.warnings.len(), 1);
+    assert_eq!(out.warnings[0].severity, Severity::Warning);
+    let errs = engine.eval_str("u: if true then {a: 1} else null,\nu.a + \"s\"").unwrap_err();
+    let severities: Vec<_> = errs.iter().map(|d| d.severity).collect();
+    assert_eq!(severities, [Severity::Warning, Severity::Error]);
+}
+
+#[test]
+fn host_panics_restore_the_thread() {
+    let mut engine = Engine::new();
+    engine
+        .set_max_depth(10)
+        .register_fn("boom", Scheme::mono(Type::Fn(vec![], Box::new(Type::Number))), |_, _| {
+            panic!("boom")
+        });
+    let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| engine.eval_str("boom()")));
+    assert!(caught.is_err());
+    assert_eq!(spctr::interp::max_depth(), spctr::interp::DEFAULT_MAX_DEPTH);
+    assert_eq!(eval(&Engine::new(), "boom()"), "undefined variable: boom: not found in scope");
+}
+
+#[test]
+fn debug_shows_structure() {
+    let v = Engine::new().eval_str(r#"{n: 1, xs: [true, null], f: (x) => x}"#).unwrap().value;
+    // Fields nothing needed yet are left alone.
+    assert_eq!(format!("{:?}", v), r#"{"n": <unevaluated>, "xs": <unevaluated>, "f": <unevaluated>}"#);
+    assert_eq!(v.to_string(), r#"{"f": [function], "n": 1, "xs": [true, null]}"#);
+    assert_eq!(
+        format!("{:?}", v),
+        r#"{"n": Number(1.0), "xs": List([Bool(true), Null]), "f": Function { params: ["x"], .. }}"#
+    );
+}
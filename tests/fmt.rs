@@ -0,0 +1,94 @@
+//! `spctr fmt`: layout, comments, and idempotence.
+use spctr::fmt::format;
+
+fn fmt(src: &str) -> String {
+    let out = format(src).unwrap_or_else(|d| panic!("{:?}", d));
+    assert_eq!(format(&out).unwrap(), out, "not idempotent");
+    out
+}
+
+#[test]
+fn examples_are_stable() {
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        let src = std::fs::read_to_string(&path).unwrap();
+        let out = fmt(&src);
+        assert_eq!(fmt(&out), out, "{}", path.display());
+    }
+}
+
+#[test]
+fn layout() {
+    assert_eq!(fmt("x:1,y:[1,2],{a:x,\"b\":y, \"c d\": 3}"), "x: 1,\ny: [1, 2],\n{a: x, b: y, \"c d\": 3}\n");
+    // A block written across lines stays broken.
+    assert_eq!(fmt("{\n  a: 1, b: {c: 2}}"), "{\n  a: 1,\n  b: {c: 2}\n}\n");
+    // Too long for one line.
+    let src = format!("f(\"{}\", [1, 2])", "x".repeat(70));
+    assert_eq!(fmt(&src), format!("f(\n  \"{}\",\n  [1, 2]\n)\n", "x".repeat(70)));
+    // `if` chains put each `else` on its own line when broken.
+    let src = "f: (i) => if i % 15 == 0 then \"FizzBuzz\" else if i % 3 == 0 then \"Fizz\" else if i % 5 == 0 then \"Buzz\" else \"${ i }\",\nf(1)";
+    assert_eq!(
+        fmt(src),
+        r#"f: (i) =>
+  if i % 15 == 0 then "FizzBuzz"
+  else if i % 3 == 0 then "Fizz"
+  else if i % 5 == 0 then "Buzz"
+  else "${i}",
+f(1)
+"#
+    );
+    assert_eq!(
+        fmt("match v {[h, ...t] if h>0 => h, {k: 1, \"q r\"} => 2, _ => -1}"),
+        "match v { [h, ...t] if h > 0 => h, {k: 1, \"q r\"} => 2, _ => -1 }\n"
+    );
+    // Literals are kept as written; bracket access becomes `.` when it can.
+    assert_eq!(fmt(r#"[1e3, "\u0041", r["x"], r["y z"], a?.b]"#), "[1e3, \"\\u0041\", r.x, r[\"y z\"], a?.b]\n");
+    // A program without a body stays without one.
+    assert_eq!(fmt("a: 1, b: 2"), "a: 1,\nb: 2\n");
+    assert_eq!(fmt(""), "");
+}
+
+#[test]
+fn parentheses_follow_precedence() {
+    assert_eq!(fmt("((1 + 2)) * 3 - (4 - 5) - -(6)"), "(1 + 2) * 3 - (4 - 5) - -6\n");
+    assert_eq!(fmt("(a ?? b) || (c && d) == (e == f)"), "(a ?? b) || (c && d) == (e == f)\n");
+    assert_eq!(fmt("((x) => x)(1) + (if a then 1 else 2)"), "((x) => x)(1) + (if a then 1 else 2)\n");
+    assert_eq!(fmt("(-x).y + (!a)[0]"), "(-x).y + (!a)[0]\n");
+}
+
+#[test]
+fn keeps_comments() {
+    let src = "/* header */
+// about a
+a: 1, // trailing a
+
+
+b: {x: 1, // x
+  y: [1, 2, /* two */ 3]
+  // dangling
+},
+c: 1 + /* inside */ 2,
+b // end";
+    assert_eq!(
+        fmt(src),
+        "/* header */
+// about a
+a: 1, // trailing a
+
+b: {
+  x: 1, // x
+  y: [1, 2, /* two */ 3]
+  // dangling
+},
+c: 1 + 2, /* inside */
+b // end
+"
+    );
+    assert_eq!(fmt("// only a comment"), "// only a comment\n");
+}
+
+#[test]
+fn parse_errors_are_reported() {
+    let errs = format("a: 1,").unwrap_err();
+    assert_eq!(errs[0].message, "parse error");
+}
@@ -617,3 +617,143 @@ fn record_bracket_string_index() {
     "#;
     assert_eq!(jit_run(src).unwrap(), 30.0);
 }
+
+#[test]
+fn match_list_recursion() {
+    let src = r#"
+        sum: (xs) => match xs { [] => 0, [h, ...t] => h + sum(t) },
+        sum(List.range(1, 11))
+    "#;
+    assert_eq!(jit_run(src).unwrap(), 55.0);
+}
+
+#[test]
+fn match_literals_guards_and_records() {
+    let src = r#"
+        sign: (n) => match n { x if x < 0 => -1, 0 => 0, _ => 1 },
+        area: (r) => match r { {w: 0, h} => 0, {w, h} => w * h },
+        sign(-5) + sign(0) * 10 + sign(3) * 100 + area({w: 2, h: 3}) * 1000
+    "#;
+    assert_eq!(jit_run(src).unwrap(), 6099.0);
+}
+
+#[test]
+fn match_binders_are_captured() {
+    let src = r#"
+        f: (xs) => match xs { [h, ...t] => List.map(t, (x) => x + h), [] => [] },
+        List.reduce(f([10, 1, 2]), 0, (a, b) => a + b)
+    "#;
+    assert_eq!(jit_run(src).unwrap(), 23.0);
+}
+
+#[test]
+fn row_polymorphic_field_access() {
+    // Each instance of `getx` sees its caller's record layout, so `x` lives
+    // at a different slot in every call.
+    let src = r#"
+        getx: (r) => r.x,
+        norm: (p) => p.x * p.x + p.y * p.y,
+        getx({x: 1}) + getx({y: 20, x: 2}) * 10 + norm({z: true, y: 4, x: 3}) * 100
+    "#;
+    assert_eq!(jit_run(src).unwrap(), 2521.0);
+}
+
+#[test]
+fn optional_chaining_and_coalescing() {
+    let src = r#"
+        port: (c) => c?.server?.port ?? 80,
+        port({server: {port: 8080}}) + port({server: {port: 1, tls: true}})
+    "#;
+    assert_eq!(jit_run(src).unwrap(), 8081.0);
+    assert_eq!(jit_run("null?.x ?? 3").unwrap(), 3.0);
+    assert_eq!(jit_run("r: {a: 1}, r?.a ?? 7").unwrap(), 1.0);
+    let src = r#"
+        len: (u) => u?.name("bob") ?? "",
+        String.length(len({name: (n) => "hi ${n}"}))
+    "#;
+    assert_eq!(jit_run(src).unwrap(), 6.0);
+}
+
+#[test]
+fn nullable_slots() {
+    assert_eq!(jit_run("xs: [1, null, 2], (xs[0] ?? 0) + (xs[1] ?? 10) + (xs[2] ?? 0)").unwrap(), 13.0);
+    assert_eq!(jit_run("pick: (b) => if b then 1 else null, pick(false) ?? 7").unwrap(), 7.0);
+    let src = r#"
+        flags: [true, null, false],
+        names: [null, "bob"],
+        (if flags[1] == null then 1 else 0)
+            + (if flags[2] == false then 10 else 0)
+            + (if names[0] == names[1] then 0 else 100)
+            + String.length(names[1] ?? "")
+    "#;
+    assert_eq!(jit_run(src).unwrap(), 114.0);
+    let src = r#"
+        half: (v) => match v { null => 0, n => n / 2 },
+        pick: (b) => if b then 8 else null,
+        half(pick(true)) + half(pick(false))
+    "#;
+    assert_eq!(jit_run(src).unwrap(), 4.0);
+    let src = r#"xs: [1, null], String.length("${xs[0]}/${xs[1]}")"#;
+    assert_eq!(jit_run(src).unwrap(), 6.0);
+}
+
+#[test]
+fn records_merged_in_another_field_order() {
+    let src = "xs: [{a: 1, b: 2}, {b: 3, a: 4}, {y: 5, {b: y, a: 6}}], xs[1].a * 10 + xs[2].a";
+    assert_eq!(jit_run(src).unwrap(), 46.0);
+    let src = "r: if true then {p: {x: 1, y: null}} else {p: {y: 2, x: 3}}, r.p.x";
+    assert_eq!(jit_run(src).unwrap(), 1.0);
+    // Filling in a missing optional field would print a field the record
+    // doesn't have.
+    let err = jit_run("xs: [{a: 1}, {a: 2, b: 3}], xs[0].a").unwrap_err();
+    assert!(err.contains("no field 'b'"), "{}", err);
+}
+
+#[test]
+fn imports_link_into_one_module() {
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+    let src = format!(
+        r#"math: import("{examples}/math.spc"), m: import("{examples}/middle.spc"),
+        math.square(5) + math.cube(3) + m.inc_then_double(3)"#
+    );
+    assert_eq!(jit_run(&src).unwrap(), 60.0);
+
+    use std::fs;
+    let dir = std::env::temp_dir().join(format!("spctr-jit-import-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // Polymorphic exports get one instance per use type; the module's own
+    // top-level bindings and sibling fields stay reachable.
+    fs::write(
+        dir.join("poly.spc"),
+        "k: 10,\n{id: (x) => x, add_k: (x) => id(x) + k}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("a.spc"), "b: import(\"./b.spc\"), {x: 1}\n").unwrap();
+    fs::write(dir.join("b.spc"), "a: import(\"./a.spc\"), {y: 2}\n").unwrap();
+    let poly = dir.join("poly.spc");
+    let src = format!(
+        r#"p: import("{}"), String.length(p.id("abc")) + p.id(1) + p.add_k(2)"#,
+        poly.display()
+    );
+    assert_eq!(jit_run(&src).unwrap(), 16.0);
+    let src = format!(r#"import("{}").x"#, dir.join("a.spc").display());
+    assert!(jit_run(&src).unwrap_err().contains("import cycle"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn errors_in_imported_modules_point_into_them() {
+    use std::fs;
+    let dir = std::env::temp_dir().join(format!("spctr-jit-import-span-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let lib = dir.join("lib.spc");
+    fs::write(&lib, "{k: base * 2, base: 3}\n").unwrap();
+    let src = format!(r#"l: import("{}"), l.k"#, lib.display());
+    let ast = parser::parse(&src).unwrap();
+    resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+    let d = jit::run(&ast).unwrap_err();
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(d.details.file.as_deref(), Some(lib.as_path()), "{:?}", d);
+    assert_eq!(d.span, 4..12, "{:?}", d);
+    assert_eq!(d.details.imported_from, vec![(None, 3..src.find(", l.k").unwrap())]);
+}
@@ -0,0 +1,259 @@
+//! The language server, driven over an in-memory connection, and once as
+//! `spctr lsp` over stdio.
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::thread;
+
+struct Client {
+    conn: Connection,
+    server: Option<thread::JoinHandle<()>>,
+    next_id: i32,
+}
+
+impl Client {
+    fn start() -> Self {
+        let (conn, server_conn) = Connection::memory();
+        let server = thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(move || spctr::lsp::serve(&server_conn).expect("serve"))
+            .unwrap();
+        let mut client = Self {
+            conn,
+            server: Some(server),
+            next_id: 0,
+        };
+        client.request("initialize", json!({"capabilities": {}}));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        let note = Notification::new(method.to_string(), params);
+        self.conn.sender.send(Message::Notification(note)).unwrap();
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let req = Request::new(id.clone(), method.to_string(), params);
+        self.conn.sender.send(Message::Request(req)).unwrap();
+        loop {
+            match self.conn.receiver.recv().unwrap() {
+                Message::Response(resp) if resp.id == id => {
+                    assert!(resp.error.is_none(), "{:?}", resp.error);
+                    return resp.result.unwrap_or(Value::Null);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Open `path` with `text` and return the diagnostics published for it.
+    fn open(&self, path: &Path, text: &str) -> Value {
+        self.notify(
+            "textDocument/didOpen",
+            json!({"textDocument": {"uri": uri(path), "languageId": "spctr", "version": 1, "text": text}}),
+        );
+        loop {
+            if let Message::Notification(note) = self.conn.receiver.recv().unwrap() {
+                if note.method == "textDocument/publishDiagnostics" {
+                    return note.params["diagnostics"].clone();
+                }
+            }
+        }
+    }
+
+    fn at(&mut self, method: &str, path: &Path, line: u32, character: u32) -> Value {
+        self.request(
+            method,
+            json!({"textDocument": {"uri": uri(path)}, "position": {"line": line, "character": character}}),
+        )
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        self.server.take().unwrap().join().unwrap();
+    }
+}
+
+fn uri(path: &Path) -> String {
+    lsp_types::Url::from_file_path(path).unwrap().to_string()
+}
+
+fn workspace(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("spctr-lsp-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.canonicalize().unwrap()
+}
+
+#[test]
+fn publishes_diagnostics() {
+    let dir = workspace("diag");
+    let mut client = Client::start();
+    let diags = client.open(&dir.join("a.spc"), "x: 1,\ny: x + \"s\",\ny");
+    assert_eq!(diags.as_array().unwrap().len(), 1, "{diags}");
+    assert_eq!(diags[0]["range"]["start"], json!({"line": 1, "character": 7}));
+    assert_eq!((&diags[0]["severity"], &diags[0]["code"]), (&json!(1), &json!("E0201")));
+    let diags = client.open(&dir.join("e.spc"), "x: 1,\nx == \"s\"");
+    let related = &diags[0]["relatedInformation"][0];
+    assert_eq!(related["message"], "this is number");
+    assert_eq!(related["location"]["range"]["start"], json!({"line": 1, "character": 0}));
+    let diags = client.open(&dir.join("b.spc"), "nope");
+    assert_eq!(diags[0]["message"], "undefined variable: nope: not found in scope");
+    let diags = client.open(&dir.join("c.spc"), "{a: 1}");
+    assert_eq!(diags, json!([]));
+    assert_eq!(client.at("textDocument/hover", &dir.join("b.spc"), 0, 1), Value::Null);
+
+    // One inside an imported module is shown on the import.
+    std::fs::write(dir.join("lib.spc"), "{\n  n: 1 + \"s\"\n}").unwrap();
+    let diags = client.open(&dir.join("d.spc"), "lib: import(\"./lib.spc\"),\nlib");
+    assert_eq!(diags[0]["range"]["start"], json!({"line": 0, "character": 5}));
+    let related = &diags[0]["relatedInformation"][0]["location"];
+    assert_eq!(related["uri"], json!(uri(&dir.join("lib.spc"))));
+    assert_eq!(related["range"]["start"], json!({"line": 1, "character": 9}));
+}
+
+#[test]
+fn analyzes_past_syntax_errors() {
+    let dir = workspace("recover");
+    let path = dir.join("main.spc");
+    let mut client = Client::start();
+    let diags = client.open(&path, "x: 1 2,\ny: [x 3],\nz: x + \"s\",\n{a: 1 +}");
+    let lines: Vec<_> = diags.as_array().unwrap().iter().map(|d| d["range"]["start"]["line"].clone()).collect();
+    assert_eq!(lines, [0, 1, 3, 2], "{diags}");
+    let hover = client.at("textDocument/hover", &path, 1, 0);
+    assert_eq!(hover["contents"]["value"], "```spctr\nlist<number>\n```");
+}
+
+#[test]
+fn hover_shows_inferred_types() {
+    let dir = workspace("hover");
+    let path = dir.join("main.spc");
+    let mut client = Client::start();
+    client.open(&path, "inc: (n) => n + 1,\nr: {name: \"x\"},\nm: import(r.name),\ninc(2)");
+    let hover = client.at("textDocument/hover", &path, 0, 1);
+    assert_eq!(hover["contents"]["value"], "```spctr\n(number) -> number\n```");
+    let hover = client.at("textDocument/hover", &path, 1, 11);
+    assert_eq!(hover["contents"]["value"], "```spctr\nstring\n```");
+    // A computed import path cannot be followed.
+    let hover = client.at("textDocument/hover", &path, 2, 0);
+    assert_eq!(hover["contents"]["value"], "```spctr\nany\n```");
+    let hover = client.at("textDocument/hover", &path, 3, 0);
+    assert_eq!(hover["contents"]["value"], "```spctr\n(number) -> number\n```");
+    assert_eq!(hover["range"], json!({"start": {"line": 3, "character": 0}, "end": {"line": 3, "character": 3}}));
+}
+
+#[test]
+fn goes_to_definitions() {
+    let dir = workspace("def");
+    std::fs::write(dir.join("lib.spc"), "{\n  double: (n) => n * 2\n}").unwrap();
+    let path = dir.join("main.spc");
+    let mut client = Client::start();
+    client.open(
+        &path,
+        "lib: import(\"./lib.spc\"),\ncfg: {port: 80},\nf: (x) => x,\n[lib.double(cfg.port), f(1)]",
+    );
+    let main = json!(uri(&path));
+    let lib = json!(uri(&dir.join("lib.spc")));
+
+    // A parameter and a top-level definition.
+    let def = client.at("textDocument/definition", &path, 2, 10);
+    assert_eq!(def["uri"], main);
+    assert_eq!(def["range"]["start"], json!({"line": 2, "character": 4}));
+    let def = client.at("textDocument/definition", &path, 3, 23);
+    assert_eq!(def["range"]["start"], json!({"line": 2, "character": 0}));
+    // A field of a local record.
+    let def = client.at("textDocument/definition", &path, 3, 17);
+    assert_eq!(def["range"]["start"], json!({"line": 1, "character": 6}));
+    // A member of an imported module, and the import itself.
+    let def = client.at("textDocument/definition", &path, 3, 6);
+    assert_eq!(def["uri"], lib);
+    assert_eq!(def["range"]["start"], json!({"line": 1, "character": 2}));
+    let def = client.at("textDocument/definition", &path, 0, 14);
+    assert_eq!(def["uri"], lib);
+    assert_eq!(def["range"]["start"], json!({"line": 0, "character": 0}));
+    // Roots have no source.
+    let def = client.at("textDocument/definition", &path, 0, 6);
+    assert_eq!(def, Value::Null);
+}
+
+#[test]
+fn completes_members() {
+    let dir = workspace("complete");
+    std::fs::write(dir.join("lib.spc"), "{ double: (n) => n * 2, name: \"lib\" }").unwrap();
+    let path = dir.join("main.spc");
+    let mut client = Client::start();
+    let labels = |v: Value| {
+        let mut labels: Vec<String> = v
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap().to_string())
+            .collect();
+        labels.sort();
+        labels
+    };
+
+    client.open(&path, "cfg: {host: \"h\", port: 80},\ncfg.po");
+    let items = client.at("textDocument/completion", &path, 1, 6);
+    assert_eq!(labels(items.clone()), ["host", "port"]);
+    assert_eq!(items[1]["detail"], "number");
+
+    client.open(&path, "lib: import(\"./lib.spc\"),\nlib.");
+    let items = client.at("textDocument/completion", &path, 1, 4);
+    assert_eq!(labels(items), ["double", "name"]);
+
+    client.open(&path, "List.");
+    let items = client.at("textDocument/completion", &path, 0, 5);
+    assert!(labels(items).contains(&"map".to_string()));
+
+    client.open(&path, "x: 1,\nx");
+    assert_eq!(client.at("textDocument/completion", &path, 1, 1), Value::Null);
+}
+
+#[test]
+fn stdio_server_exits_after_shutdown() {
+    use std::io::{Read, Write};
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_spctr"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for msg in [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}}),
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+    ] {
+        let body = msg.to_string();
+        write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    }
+    stdin.flush().unwrap();
+
+    // Stdin stays open: the server has to leave on `exit` alone.
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > Duration::from_secs(10) {
+            child.kill().unwrap();
+            panic!("the server is still running after exit");
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    assert!(status.success());
+    let mut out = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut out).unwrap();
+    assert!(out.contains("\"id\":2"), "{}", out);
+    drop(stdin);
+}
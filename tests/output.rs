@@ -0,0 +1,197 @@
+//! Output encodings of program results.
+use spctr::manifest::{manifest, render, render_node, split, Format, Options};
+use spctr::{interp, parser, resolver};
+
+fn output(src: &str, format: Format, opts: Options) -> String {
+    let ast = parser::parse(src).expect("parse");
+    resolver::resolve(&ast, &interp::ROOT_NAMES).expect("resolve");
+    let v = interp::run(&ast).expect("run");
+    match render(&v, format, &opts, &ast.body.1) {
+        Ok(s) => s,
+        Err(d) => format!("{} @ {:?}: {}", d.message, d.span, d.label),
+    }
+}
+
+fn json(src: &str, pretty: bool, sort_keys: bool) -> String {
+    output(src, Format::Json, Options { pretty, sort_keys })
+}
+
+fn yaml(src: &str) -> String {
+    output(src, Format::Yaml, Options::default())
+}
+
+fn toml(src: &str) -> String {
+    output(src, Format::Toml, Options::default())
+}
+
+#[test]
+fn json_encoding() {
+    let src = r#"{b: 1, a: [0.5, "q\"\n"], c: {}, d: [], e: null}"#;
+    assert_eq!(
+        json(src, false, false),
+        r#"{"b":1,"a":[0.5,"q\"\n"],"c":{},"d":[],"e":null}"#
+    );
+    assert_eq!(
+        json(src, false, true),
+        r#"{"a":[0.5,"q\"\n"],"b":1,"c":{},"d":[],"e":null}"#
+    );
+    assert_eq!(
+        json("{x: [1, {y: true}]}", true, false),
+        "{\n  \"x\": [\n    1,\n    {\n      \"y\": true\n    }\n  ]\n}"
+    );
+    // Every result is valid JSON.
+    let out = json(src, true, true);
+    assert!(serde_json::from_str::<serde_json::Value>(&out).is_ok(), "{out}");
+}
+
+#[test]
+fn json_rejects_what_it_cannot_encode() {
+    assert_eq!(
+        json("{ok: 1, bad: [(x) => x]}", false, false),
+        "cannot encode a function @ 13..23: at .bad[0]"
+    );
+    assert_eq!(json("{n: 1 / 0}", false, false), "cannot encode inf as JSON @ 4..9: at .n");
+    assert_eq!(json("0 / 0", false, false), "cannot encode NaN as JSON @ 0..5: at the result");
+}
+
+#[test]
+fn numbers_are_written_shortest_with_exponents() {
+    let src = "{big: 1e300, small: 0.0000001, half: 0.5, xs: [1, 2.5e-300]}";
+    assert_eq!(
+        json(src, false, false),
+        r#"{"big":1e+300,"small":1e-7,"half":0.5,"xs":[1,2.5e-300]}"#
+    );
+    assert_eq!(yaml(src), "big: 1.0e+300\nsmall: 1.0e-7\nhalf: 0.5\nxs:\n  - 1\n  - 2.5e-300");
+    assert_eq!(toml(src), "big = 1e+300\nsmall = 1e-7\nhalf = 0.5\nxs = [1.0, 2.5e-300]");
+}
+
+#[test]
+fn deep_values_are_an_error_not_a_crash() {
+    let records = |n| format!("f: (n) => if n == 0 then 1 else {{a: f(n - 1)}}, f({n})");
+    let too_deep = "cannot encode a value nested more than 512 levels deep";
+    for format in [Format::Json, Format::Yaml, Format::Toml] {
+        assert!(!output(&records(512), format, Options::default()).starts_with("cannot"));
+        let err = output(&records(513), format, Options::default());
+        assert!(err.starts_with(too_deep), "{err}");
+    }
+    let lists = "f: (n) => if n == 0 then [] else [f(n - 1)], f(20000)";
+    assert!(json(lists, true, false).starts_with(too_deep));
+
+    // `Serialize` builds the same tree, so serde gets the error too.
+    let ast = parser::parse(lists).expect("parse");
+    resolver::resolve(&ast, &interp::ROOT_NAMES).expect("resolve");
+    let v = interp::run(&ast).expect("run");
+    let err = serde_json::to_string(&v).unwrap_err();
+    assert!(err.to_string().starts_with(too_deep), "{err}");
+}
+
+#[test]
+fn yaml_encoding() {
+    let src = r#"{
+        name: "web",
+        tags: ["a", "b c", "true", "1", ""],
+        db: {host: "x", port: 5432},
+        workers: [{id: 1, env: {}}, {id: 2, env: {A: null}}],
+        grid: [[1, 2], []],
+        inf: 1 / 0
+    }"#;
+    assert_eq!(
+        yaml(src),
+        r#"name: web
+tags:
+  - a
+  - b c
+  - "true"
+  - "1"
+  - ""
+db:
+  host: x
+  port: 5432
+workers:
+  - id: 1
+    env: {}
+  - id: 2
+    env:
+      A: null
+grid:
+  - - 1
+    - 2
+  - []
+inf: .inf"#
+    );
+    assert_eq!(yaml("[]"), "[]");
+    assert_eq!(yaml(r#""yes""#), r#""yes""#);
+}
+
+#[test]
+fn toml_tables_and_arrays_of_tables() {
+    let src = r#"{
+        name: "web",
+        db: {host: "x", opts: {ssl: true}},
+        ratio: [1, 0.5],
+        workers: [{id: 1, env: {A: "1"}}, {id: 2, ports: [{n: 80}]}],
+        deep: {only: {x: 1}},
+        "a key": [[1], ["a"]]
+    }"#;
+    assert_eq!(
+        toml(src),
+        r#"name = "web"
+ratio = [1.0, 0.5]
+"a key" = [[1], ["a"]]
+
+[db]
+host = "x"
+
+[db.opts]
+ssl = true
+
+[[workers]]
+id = 1
+
+[workers.env]
+A = "1"
+
+[[workers]]
+id = 2
+
+[[workers.ports]]
+n = 80
+
+[deep.only]
+x = 1"#
+    );
+}
+
+#[test]
+fn toml_rejects_what_it_cannot_represent() {
+    assert_eq!(toml("{a: {b: null}}"), "TOML cannot represent null @ 8..12: at .a.b");
+    assert_eq!(
+        toml(r#"{xs: [1, "x"]}"#),
+        "TOML cannot represent a list mixing number and string @ 5..13: at .xs[1]"
+    );
+    assert_eq!(toml("[1]"), "TOML needs a record at the top level, got list @ 0..3: at the result");
+}
+
+#[test]
+fn multi_splits_the_top_level_record() {
+    let files = |src: &str| {
+        let ast = parser::parse(src).expect("parse");
+        resolver::resolve(&ast, &interp::ROOT_NAMES).expect("resolve");
+        let v = interp::run(&ast).expect("run");
+        let node = manifest(&v, false, &ast.body.1).expect("manifest");
+        match split(node) {
+            Ok(files) => files
+                .iter()
+                .map(|(name, node)| {
+                    let text = render_node(node, Format::Yaml, &Options::default()).unwrap();
+                    format!("{name}: {text}")
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Err(d) => format!("{} @ {:?}: {}", d.message, d.span, d.label),
+        }
+    };
+    assert_eq!(files(r#"{"b.yaml": [1], "a.yaml": {x: 1}}"#), "b.yaml: - 1\na.yaml: x: 1");
+    assert_eq!(files("[1]"), "--multi needs a record at the top level, got list @ 0..3: at the result");
+    assert_eq!(files(r#"{"../x": 1}"#), r#""../x" is not a valid file name @ 9..10: at .../x"#);
+}
@@ -97,6 +97,297 @@ fn import_basic() {
     let _ = fs::remove_dir_all(&dir);
 }
 
+#[test]
+fn import_cache_and_cycles() {
+    use std::fs;
+    let dir = std::env::temp_dir().join(format!("spctr-test-cache-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("util.spc"), "{inc: (x) => x + 1}\n").unwrap();
+    fs::write(dir.join("left.spc"), "util: import(\"./util.spc\"), {u: util}\n").unwrap();
+    fs::write(dir.join("right.spc"), "v: import(\"./util.spc\"), {u: v}\n").unwrap();
+    fs::write(dir.join("a.spc"), "b: import(\"./b.spc\"), {x: b.y}\n").unwrap();
+    fs::write(dir.join("b.spc"), "a: import(\"./a.spc\"), {y: a.x}\n").unwrap();
+
+    // Both sides of the diamond see the very same `util` value.
+    let src = format!(
+        r#"[import("{d}/left.spc").u, import("{d}/right.spc").u]"#,
+        d = dir.display()
+    );
+    let ast = parser::parse(&src).unwrap();
+    resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+    match &interp::run(&ast).unwrap() {
+        interp::Value::List(xs) => match (&xs[0], &xs[1]) {
+            (interp::Value::Block(l), interp::Value::Block(r)) => {
+                assert!(std::rc::Rc::ptr_eq(l, r))
+            }
+            _ => panic!("expected two modules"),
+        },
+        _ => panic!("expected a list"),
+    }
+
+    let canon = dir.canonicalize().unwrap();
+    let out = run(&format!(r#"import("{}/a.spc").x"#, dir.display()));
+    assert_snapshot!(
+        out.replace(&canon.display().to_string(), "<dir>"),
+        @"[runtime error] import cycle: <dir>/a.spc -> <dir>/b.spc -> <dir>/a.spc: import error"
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn import_resolvers() {
+    use spctr::stdlib::imports::{self, DenyResolver, MapResolver};
+    use std::rc::Rc;
+    let files: MapResolver = [
+        ("lib/math.spc", "util: import(\"../util.spc\"), {twice: (x) => util.inc(util.inc(x))}"),
+        ("util.spc", "{inc: (x) => x + 1}"),
+    ]
+    .into_iter()
+    .collect();
+    let out = imports::with_resolver(Rc::new(files), || {
+        run(r#"m: import("lib/math.spc"), [m.twice(1), import("./lib/../util.spc").inc(0)]"#)
+    });
+    assert_snapshot!(out, @"[3, 1]");
+    let out = imports::with_resolver(Rc::new(MapResolver::new()), || run(r#"import("x.spc")"#));
+    assert_snapshot!(out, @"[runtime error] no module x.spc: import error");
+    let out = imports::with_resolver(Rc::new(DenyResolver), || run(r#"import("x.spc")"#));
+    assert_snapshot!(out, @"[runtime error] imports are disabled (importing x.spc): import error");
+}
+
+#[test]
+fn import_errors_point_into_the_module() {
+    use spctr::diag;
+    use spctr::stdlib::imports::{self, MapResolver};
+    use std::rc::Rc;
+    let files: MapResolver = [
+        ("lib/bad.spc", "{\n  x: 1 +\n}"),
+        ("lib/mid.spc", "b: import(\"./bad.spc\"),\n{y: b}"),
+        ("eager.spc", "n: 1 + \"s\",\n{n: n}"),
+    ]
+    .into_iter()
+    .collect();
+    let fail = |src: &str| {
+        let ast = parser::parse(src).unwrap();
+        resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+        interp::run(&ast).unwrap_err()
+    };
+    let src = "m: import(\"lib/mid.spc\"),\nm.y";
+    let (d, eager) = imports::with_resolver(Rc::new(files), || (fail(src), fail("import(\"eager.spc\")")));
+    // Errors raised while the import evaluates the module point into it too.
+    assert_eq!(eager.details.file.as_deref(), Some(std::path::Path::new("eager.spc")));
+    assert_eq!(eager.span, 3..10);
+    assert_eq!(d.details.file.as_deref(), Some(std::path::Path::new("lib/bad.spc")));
+    let out = diag::render("main.spc", src, &d);
+    let out: Vec<_> = out.lines().map(str::trim_end).collect();
+    assert_snapshot!(out.join("\n"), @r#"
+    [E0002] Error: parse error
+       ╭─[ lib/bad.spc:3:1 ]
+       │
+     3 │ }
+       │ ┬
+       │ ╰── found '}' expected '-', '!', something else, '"', 'if', 'match', '(', '[', or '{'
+       │
+       ├─[ lib/mid.spc:1:4 ]
+       │
+     1 │ b: import("./bad.spc"),
+       │    ─────────┬─────────
+       │             ╰─────────── imported here
+       │
+       ├─[ main.spc:1:4 ]
+       │
+     1 │ m: import("lib/mid.spc"),
+       │    ──────────┬──────────
+       │              ╰──────────── imported here
+    ───╯
+    "#);
+}
+
+#[test]
+fn diagnostics_have_codes_labels_and_help() {
+    use spctr::{diag, typeck};
+    let warnings = |src: &str| {
+        let ast = parser::parse(src).unwrap();
+        resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+        typeck::check(&ast, &interp::root_types()).warnings
+    };
+    let render = |src: &str| {
+        let out = diag::render("main.spc", src, &warnings(src)[0]);
+        out.lines().map(str::trim_end).collect::<Vec<_>>().join("\n")
+    };
+    assert_snapshot!(render("n: 1,\nn == \"one\""), @r#"
+    [E0201] Error: type mismatch: number vs string
+       ╭─[ main.spc:2:6 ]
+       │
+     2 │ n == "one"
+       │ ┬    ──┬──
+       │ ╰─────────── this is number
+       │        │
+       │        ╰──── incompatible: number vs string
+    ───╯
+    "#);
+    assert_snapshot!(render("u: if true then {a: 1} else null,\nu.a"), @r#"
+    [E0204] Warning: field access on possibly null {a: number}?
+       ╭─[ main.spc:2:1 ]
+       │
+     2 │ u.a
+       │ ─┬─
+       │  ╰─── may be null
+       │
+       │ Help: use `?.` to skip null
+    ───╯
+    "#);
+}
+
+#[test]
+fn diagnostics_as_json_and_sarif() {
+    use serde_json::json;
+    use spctr::{diag, typeck};
+    let src = "n: 1,\n\"é\" + (n == \"one\")";
+    let ast = parser::parse(src).unwrap();
+    resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+    let warnings = typeck::check(&ast, &interp::root_types()).warnings;
+    let eq = warnings.iter().find(|w| !w.details.labels.is_empty()).unwrap();
+    assert_eq!(
+        diag::to_json("main.spc", src, eq),
+        json!({
+            "file": "main.spc",
+            "span": {"start": 19, "end": 24},
+            "start": {"line": 2, "column": 13},
+            "end": {"line": 2, "column": 18},
+            "severity": "error",
+            "code": "E0201",
+            "message": "type mismatch: number vs string",
+            "label": "incompatible: number vs string",
+            "labels": [{
+                "file": "main.spc",
+                "span": {"start": 14, "end": 15},
+                "start": {"line": 2, "column": 8},
+                "end": {"line": 2, "column": 9},
+                "message": "this is number",
+            }],
+            "notes": [],
+            "help": null,
+            "imported_from": [],
+            "trace": null,
+        })
+    );
+
+    let log = diag::to_sarif("main.spc", src, &warnings);
+    assert_eq!(log["version"], "2.1.0");
+    let run = &log["runs"][0];
+    let results = run["results"].as_array().unwrap();
+    assert_eq!(results.len(), warnings.len());
+    let result = results.iter().find(|r| r["relatedLocations"].is_array()).unwrap();
+    assert_eq!((&result["ruleId"], &result["level"]), (&json!("E0201"), &json!("error")));
+    let region = &result["locations"][0]["physicalLocation"]["region"];
+    assert_eq!(
+        region,
+        &json!({
+            "startLine": 2,
+            "startColumn": 13,
+            "endLine": 2,
+            "endColumn": 18,
+            "byteOffset": 19,
+            "byteLength": 5,
+        })
+    );
+    assert_eq!(result["relatedLocations"][0]["message"]["text"], "this is number");
+    let rules = &run["tool"]["driver"]["rules"];
+    assert_eq!(rules.as_array().unwrap().len(), 1);
+    assert_eq!(rules[0]["id"], "E0201");
+    assert_eq!(rules[0]["shortDescription"]["text"], "Type mismatch");
+}
+
+#[test]
+fn every_code_is_explained() {
+    use spctr::codes;
+    for (i, (code, text)) in codes::CODES.iter().enumerate() {
+        assert!(!codes::CODES[..i].iter().any(|(c, _)| c == code), "{code} is listed twice");
+        assert!(!text.lines().next().unwrap().is_empty(), "{code} has no title");
+    }
+    let mut dirs = vec![std::path::PathBuf::from("src")];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let text = std::fs::read_to_string(&path).unwrap();
+            for (_, rest) in text.match_indices("with_code(\"").map(|(i, _)| text.split_at(i + 11)) {
+                let code = &rest[..rest.find('"').unwrap()];
+                assert!(codes::explain(code).is_some(), "{code} in {} is not explained", path.display());
+            }
+        }
+    }
+    assert!(codes::explain("E9999").is_none());
+}
+
+#[test]
+fn runtime_errors_have_a_backtrace() {
+    use spctr::diag;
+    use spctr::stdlib::imports::{self, MapResolver};
+    use std::path::Path;
+    use std::rc::Rc;
+    let fail = |src: &str| {
+        let ast = parser::parse(src).unwrap();
+        resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+        interp::run(&ast).unwrap_err()
+    };
+    let frames = |d: &diag::Diagnostic| {
+        let trace = d.details.trace.as_ref().unwrap();
+        trace.iter().map(|f| (f.name.clone(), f.span.clone(), f.elided)).collect::<Vec<_>>()
+    };
+
+    // The three calls `loop` made to itself reused its frame.
+    let src = "first: (xs) => List.head(xs),\n\
+               loop: (n) => if n == 0 then first([]) else loop(n - 1),\n\
+               List.map([1], (x) => loop(3) + x)";
+    let d = fail(src);
+    assert_eq!(
+        frames(&d),
+        [
+            ("first".to_string(), 58..67, 3),
+            ("loop".to_string(), 107..114, 0),
+            ("<function passed to List.map>".to_string(), 86..119, 0),
+        ]
+    );
+    let out = diag::render("main.spc", src, &d);
+    let out: Vec<_> = out.lines().map(str::trim_end).collect();
+    assert_snapshot!(out.join("\n"), @r"
+    [E0301] Error: List.head on empty list
+       ╭─[ main.spc:1:16 ]
+       │
+     1 │ first: (xs) => List.head(xs),
+       │                ──────┬──────
+       │                      ╰──────── no first element
+       │
+       │ Note: backtrace, most recent call first:
+       │         at first (main.spc:2:29)
+       │         ... 3 tail calls elided
+       │         at loop (main.spc:3:22)
+       │         at <function passed to List.map> (main.spc:3:1)
+    ───╯
+    ");
+
+    // A module's function failing after the import points into the module.
+    let files: MapResolver = [("m.spc", "{\n  half: (n) => n / 2 + n.x\n}")].into_iter().collect();
+    let d = imports::with_resolver(Rc::new(files), || fail("m: import(\"m.spc\"),\nm.half(4)"));
+    assert_eq!((d.details.file.as_deref(), d.span.clone()), (Some(Path::new("m.spc")), 25..28));
+    let trace = d.details.trace.as_ref().unwrap();
+    assert_eq!(trace[0].name, "m.half");
+    assert_eq!((trace[0].file.as_deref(), trace[0].span.clone()), (None, 20..29));
+
+    // A runaway recursion shows both ends.
+    interp::set_max_depth(100);
+    let d = fail("f: (n) => f(n + 1) + 1, f(0)");
+    interp::set_max_depth(interp::DEFAULT_MAX_DEPTH);
+    assert_eq!(d.details.trace.as_ref().unwrap().len(), 100);
+    let out = diag::render("main.spc", "f: (n) => f(n + 1) + 1, f(0)", &d);
+    assert!(out.contains("at f (main.spc:1:11)\n"), "{out}");
+    assert!(out.contains("... 80 more frames\n"), "{out}");
+}
+
 #[test]
 fn string_escapes() {
     assert_snapshot!(run(r#""hello\nworld""#), @r###""hello\nworld""###);
@@ -170,6 +461,58 @@ fn deep_tail_recursion_with_immediate_block() {
     );
 }
 
+#[test]
+fn deep_non_tail_recursion() {
+    // Non-tail calls keep their continuations on the heap, so this no longer
+    // depends on the Rust stack size.
+    assert_snapshot!(
+        run("count: (n) => if n == 0 then 0 else count(n - 1) + 1, count(300000)"),
+        @"300000"
+    );
+    // Recursion through stdlib callbacks (`List.map` over a tree's children).
+    assert_snapshot!(
+        run(r#"
+          tree: (d) => if d == 0 then {kids: []} else {kids: [tree(d - 1)]},
+          size: (t) => 1 + List.reduce(List.map(t.kids, size), 0, (a, b) => a + b),
+          size(tree(100000))
+        "#),
+        @"100001"
+    );
+}
+
+#[test]
+fn deep_values() {
+    // Dropping and printing nested lists, records and closures doesn't
+    // recurse on the Rust stack either.
+    assert_snapshot!(
+        run("build: (n) => if n == 0 then [] else [build(n - 1)], List.length(build(100000))"),
+        @"1"
+    );
+    let out = run("build: (n) => if n == 0 then [] else [build(n - 1)], build(100000)");
+    assert_eq!(out, format!("{}{}", "[".repeat(100001), "]".repeat(100001)));
+    assert_snapshot!(
+        run("build: (n) => if n == 0 then [] else [build(n - 1)], build(100000) == build(100000)"),
+        @"true"
+    );
+    let out = run("build: (n) => if n == 0 then 0 else {a: build(n - 1)}, build(100000)");
+    assert_eq!(out, format!("{}0{}", r#"{"a": "#.repeat(100000), "}".repeat(100000)));
+    assert_snapshot!(
+        run("build: (n) => if n == 0 then (x) => x else {f: build(n - 1), (x) => f(x) + 1}, build(100000)(0)"),
+        @"100000"
+    );
+}
+
+#[test]
+fn max_depth() {
+    interp::set_max_depth(1000);
+    let out = run("count: (n) => if n == 0 then 0 else count(n - 1) + 1, count(5000)");
+    // Tail calls don't count towards the limit.
+    let tail = run("loop_n: (n) => if n == 0 then 0 else loop_n(n - 1), loop_n(5000)");
+    interp::set_max_depth(interp::DEFAULT_MAX_DEPTH);
+    assert_snapshot!(out, @"[runtime error] maximum recursion depth of 1000 exceeded: raise the limit with --max-depth");
+    assert_snapshot!(tail, @"0");
+}
+
 #[test]
 fn comments() {
     assert_snapshot!(run("// comment\n1 + 2"), @"3");
@@ -253,6 +596,22 @@ fn errors_undefined_variable() {
     assert_snapshot!(run("foo + 1"), @"[resolve error] undefined variable: foo: not found in scope");
 }
 
+#[test]
+fn errors_suggest_similar_names() {
+    let help = |src: &str| {
+        let ast = parser::parse(src).unwrap();
+        let d = match resolver::resolve(&ast, &interp::ROOT_NAMES) {
+            Err(d) => d,
+            Ok(()) => interp::run(&ast).unwrap_err(),
+        };
+        d.details.help
+    };
+    assert_eq!(help("total: 1, totl + 1").as_deref(), Some("did you mean `total`?"));
+    assert_eq!(help("Lsit.length([])").as_deref(), Some("did you mean `List`?"));
+    assert_eq!(help("x: {name: 1}, x.nmae").as_deref(), Some("did you mean `name`?"));
+    assert_eq!(help("foo + 1"), None);
+}
+
 #[test]
 fn errors_type_mismatch() {
     assert_snapshot!(
@@ -267,6 +626,32 @@ fn errors_parse() {
     assert!(out.starts_with("[parse error]"), "got: {}", out);
 }
 
+#[test]
+fn errors_parse_recovers() {
+    use spctr::ast::Expr;
+    let starts = |src: &str| {
+        let (_, diags) = parser::parse_recovering(src);
+        diags.iter().map(|d| d.span.start).collect::<Vec<_>>()
+    };
+    // Every missing `,`, and every bad entry, in one pass.
+    assert_eq!(starts("a: 1 b: 2 c: 3, a"), [5, 10]);
+    assert_eq!(starts("[1 2, 3 4]"), [3, 8]);
+    assert_eq!(starts("x: 1 $, y: (1 +), z: [}, y"), [5, 15, 22]);
+    // Bad groups inside an entry are reported on their own.
+    assert_eq!(starts("{a: 1 * (2, 3) * [4,, 5], b: 2 +, c: 3}"), [10, 20, 32]);
+
+    // The entries around an error are kept, the bad one becomes an error node.
+    let (ast, diags) = parser::parse_recovering("{a: 1 +, b: 2}");
+    assert_eq!(diags.len(), 1);
+    let Expr::Block(defs) = &ast.unwrap().body.0 else { panic!() };
+    assert!(matches!(defs[0].1 .0, Expr::Error));
+    assert!(matches!(defs[1].1 .0, Expr::Number(_)));
+
+    // Recovered bodies are not reported twice; a real second body is.
+    assert_eq!(starts("x: 1 2, x"), [5]);
+    assert_eq!(run("1, 2"), "[parse error]\nparse error: multiple body expressions");
+}
+
 #[test]
 fn errors_no_field() {
     assert_snapshot!(
@@ -274,3 +659,120 @@ fn errors_no_field() {
         @"[runtime error] no such field: b: field not found"
     );
 }
+
+#[test]
+fn match_literals_and_wildcard() {
+    let src = r#"
+        name: (n) => match n { 0 => "zero", 1 => "one", _ => "many" },
+        [name(0), name(1), name(7)]
+    "#;
+    assert_snapshot!(run(src), @r###"["zero", "one", "many"]"###);
+    assert_snapshot!(run(r#"match "b" { "a" => 1, "b" => 2, _ => 3 }"#), @"2");
+    assert_snapshot!(run("match null { null => true, _ => false }"), @"true");
+}
+
+#[test]
+fn match_list_patterns() {
+    let src = r#"
+        sum: (xs) => match xs { [] => 0, [h, ...t] => h + sum(t) },
+        sum(List.range(1, 11))
+    "#;
+    assert_snapshot!(run(src), @"55");
+    assert_snapshot!(run("match [1, 2, 3] { [a, b] => 0, [a, ...] => a }"), @"1");
+    assert_snapshot!(run("match [1, 2, 3] { [_, ...rest] => rest }"), @"[2, 3]");
+}
+
+#[test]
+fn match_record_patterns() {
+    let src = r#"
+        p: {x: 3, y: 4},
+        match p { {x: 0, y} => y, {x, y} => x * y }
+    "#;
+    assert_snapshot!(run(src), @"12");
+    assert_snapshot!(
+        run("match {a: {b: 5}} { {a: {b}} => b + 1 }"),
+        @"6"
+    );
+}
+
+#[test]
+fn match_guards() {
+    let src = r#"
+        sign: (n) => match n { x if x < 0 => "neg", 0 => "zero", _ => "pos" },
+        [sign(-2), sign(0), sign(9)]
+    "#;
+    assert_snapshot!(run(src), @r###"["neg", "zero", "pos"]"###);
+}
+
+#[test]
+fn match_tail_position() {
+    let src = r#"
+        count: (n, acc) => match n { 0 => acc, _ => count(n - 1, acc + 1) },
+        count(100000, 0)
+    "#;
+    assert_snapshot!(run(src), @"100000");
+}
+
+#[test]
+fn errors_match_no_arm() {
+    assert_snapshot!(
+        run("match 3 { 1 => 1, 2 => 2 }"),
+        @"[runtime error] no match arm matched number: non-exhaustive match"
+    );
+}
+
+#[test]
+fn errors_match_duplicate_binder() {
+    assert_snapshot!(
+        run("match [1, 2] { [x, x] => x }"),
+        @"[resolve error] duplicate binding in pattern: x: already bound in this pattern"
+    );
+}
+
+#[test]
+fn optional_chaining() {
+    let src = r#"
+        port: (c) => c?.server?.port,
+        [port({server: {port: 8080}}), port({server: null}), port(null)]
+    "#;
+    assert_snapshot!(run(src), @"[8080, null, null]");
+    assert_snapshot!(
+        run(r#"greet: (u) => u?.hello("bob"), [greet({hello: (n) => "hi ${n}"}), greet(null)]"#),
+        @r###"["hi bob", null]"###
+    );
+    // The skipped call doesn't evaluate its arguments either.
+    assert_snapshot!(run("null?.f(1 + true)"), @"null");
+    // An optional field some of the records lack reads as null too.
+    assert_snapshot!(
+        run("xs: [{a: 1}, {a: 2, b: 3}], List.map(xs, (x) => x.a + (x?.b ?? 10))"),
+        @"[11, 5]"
+    );
+    assert_snapshot!(run("{}?.f(1)"), @"null");
+}
+
+#[test]
+fn null_coalescing() {
+    assert_snapshot!(run("null ?? 1"), @"1");
+    assert_snapshot!(run("2 ?? 1"), @"2");
+    assert_snapshot!(run("false ?? true"), @"false");
+    assert_snapshot!(run("null ?? null ?? 3"), @"3");
+    // Short-circuits: the right side is never evaluated.
+    assert_snapshot!(run("1 ?? 1 + true"), @"1");
+    assert_snapshot!(
+        run("port: (c) => c?.port ?? 80, [port({port: 1}), port(null)]"),
+        @"[1, 80]"
+    );
+}
+
+#[test]
+fn nullable_values() {
+    assert_snapshot!(run("xs: [1, null, 2], [xs[1] == null, xs[0] ?? 0, xs[1] ?? 0]"), @"[true, 1, 0]");
+    assert_snapshot!(
+        run("pick: (b) => if b then {a: 1} else null, [pick(true), pick(false)]"),
+        @r###"[{"a": 1}, null]"###
+    );
+    assert_snapshot!(
+        run("half: (v) => match v { null => 0, n => n / 2 }, [half(4), half(null)]"),
+        @"[2, 0]"
+    );
+}
@@ -0,0 +1,255 @@
+//! Type-level tests: inferred program types and typeck warnings.
+use spctr::diag::Severity;
+use spctr::{interp, parser, resolver, typeck};
+
+fn check(src: &str) -> (String, Vec<String>) {
+    let ast = parser::parse(src).expect("parse");
+    resolver::resolve(&ast, &interp::ROOT_NAMES).expect("resolve");
+    let result = typeck::check(&ast, &interp::root_types());
+    let warnings = result
+        .warnings
+        .iter()
+        .map(|d| format!("{}: {}", d.message, d.label))
+        .collect();
+    (result.program_type.to_string(), warnings)
+}
+
+#[test]
+fn match_infers_arm_type() {
+    let (ty, warnings) = check("(xs) => match xs { [] => 0, [h, ...t] => h }");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "(list<number>) -> number");
+}
+
+#[test]
+fn match_exhaustive_bool_and_list() {
+    let (_, warnings) = check("(b) => match b { true => 1, false => 0 }");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    let (_, warnings) = check("(xs) => match xs { [] => 0, [_] => 1, [_, _, ...] => 2 }");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+#[test]
+fn match_non_exhaustive_warns() {
+    let (_, warnings) = check("(b) => match b { true => 1 }");
+    assert_eq!(
+        warnings,
+        vec!["non-exhaustive match: some values are not covered by any arm"]
+    );
+    let (_, warnings) = check("(xs) => match xs { [h, ...t] => h }");
+    assert_eq!(warnings.len(), 1);
+    let (_, warnings) = check("(n) => match n { x if x > 0 => 1, 0 => 0 }");
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn match_arm_type_mismatch_warns() {
+    let (_, warnings) = check(r#"match 1 { 0 => "a", _ => 1 }"#);
+    assert!(!warnings.is_empty());
+}
+
+#[test]
+fn row_field_access_generalizes() {
+    let (ty, warnings) = check("(r) => r.x + r.y");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "({x: number, y: number | α}) -> number");
+}
+
+#[test]
+fn row_function_applies_to_different_shapes() {
+    let src = r#"
+        getx: (r) => r.x + 1,
+        [getx({x: 1}), getx({y: "s", x: 2}), getx({x: 3, z: true})]
+    "#;
+    let (ty, warnings) = check(src);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "list<number>");
+}
+
+#[test]
+fn row_missing_field_warns() {
+    let (_, warnings) = check("getx: (r) => r.x, getx({y: 1})");
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].ends_with("missing field 'x'"), "{:?}", warnings);
+}
+
+#[test]
+fn row_field_types_flow_back() {
+    let (ty, warnings) = check(r#"name: (r) => r.name, name({name: "a", age: 3})"#);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "string");
+    let (_, warnings) = check(r#"f: (r) => r.n + 1, f({n: "a"})"#);
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn optional_chaining_is_nullable() {
+    let (ty, warnings) = check("(u) => u?.name");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "({name: α | β}?) -> α?");
+    let (ty, warnings) = check("(u) => u?.a?.b ?? 0");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "({a: {b: number | α}? | β}?) -> number");
+    let (ty, _) = check("r: {a: 1}, r?.a");
+    assert_eq!(ty, "number");
+    let (ty, _) = check("null?.a");
+    assert_eq!(ty, "null");
+}
+
+#[test]
+fn possibly_null_operands_warn() {
+    let (_, warnings) = check("f: (u) => u?.a + 1, f({a: 1})");
+    assert_eq!(
+        warnings,
+        vec!["possibly null value of type any?: may be null"]
+    );
+    let (_, warnings) = check("f: (u) => (u?.a ?? 0) + 1, f({a: 1})");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    let (_, warnings) = check("f: (u) => u?.a.b, f({a: {b: 1}})");
+    assert_eq!(warnings, vec!["field access on possibly null any?: may be null"]);
+}
+
+#[test]
+fn mismatches_point_at_both_sides() {
+    let src = "c: true,\nif c then 1 else \"one\"";
+    let ast = parser::parse(src).unwrap();
+    resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+    let result = typeck::check(&ast, &interp::root_types());
+    let w = &result.warnings[0];
+    assert_eq!((w.severity, w.code), (Severity::Error, Some("E0201")));
+    assert_eq!(&src[w.span.clone()], "\"one\"");
+    assert_eq!(w.details.labels, vec![(19..20, "this is number".to_string())]);
+}
+
+#[test]
+fn missing_fields_suggest_similar_ones() {
+    let help = |src: &str| {
+        let ast = parser::parse(src).unwrap();
+        resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+        let result = typeck::check(&ast, &interp::root_types());
+        result.warnings.into_iter().map(|w| w.details.help).collect::<Vec<_>>()
+    };
+    assert_eq!(help("List.lenght([1])"), [Some("did you mean `length`?".to_string())]);
+    assert_eq!(help("r: {port: 1}, r.prot"), [Some("did you mean `port`?".to_string())]);
+    assert_eq!(
+        help("match {port: 1} { {Port} => Port }"),
+        [Some("did you mean `port`?".to_string())]
+    );
+    assert_eq!(help("String.uper(\"a\")"), [None]);
+}
+
+#[test]
+fn null_merges_into_nullable() {
+    assert_eq!(check("if true then 1 else null").0, "number?");
+    assert_eq!(check("if true then null else null").0, "null");
+    assert_eq!(check("[1, null, 2]").0, "list<number?>");
+    assert_eq!(check("[null, \"a\"]").0, "list<string?>");
+    let (ty, warnings) = check("[{a: 1, b: null}, {a: null, b: \"x\"}]");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "list<{a: number?, b: string?}>");
+    // A field only some records have is optional.
+    let (ty, warnings) = check("[{a: 1}, {b: \"x\", a: 2}, {a: 3, c: {d: null}}]");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "list<{a: number, b: string?, c: {d: null}?}>");
+    assert_eq!(check("match 1 { 1 => null, _ => true }").0, "bool?");
+    let (ty, warnings) = check("(v) => match v { null => 0, n => n + 1 }");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "(number?) -> number");
+}
+
+#[test]
+fn deeply_nested_literals_check_quickly() {
+    // Each literal used to re-merge everything nested in it.
+    let src = format!("{}{}", "[".repeat(300), "]".repeat(300));
+    let (ty, warnings) = check(&src);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert!(ty.starts_with("list<list<"), "{}", ty);
+}
+
+#[test]
+fn possibly_null_arguments_warn() {
+    let (_, warnings) = check(r#"ss: ["a", null], String.length(ss[1])"#);
+    assert_eq!(warnings.len(), 1);
+    let (_, warnings) = check("f: (x) => x ?? 0, f(if true then 1 else null)");
+    assert!(warnings.is_empty(), "{:?}", warnings);
+}
+
+#[test]
+fn literal_imports_are_typed() {
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+    let (ty, warnings) = check(&format!(
+        r#"math: import("{examples}/math.spc"), m: import("{examples}/middle.spc"),
+        {{a: math.square(5), b: m.inc_then_double(3)}}"#
+    ));
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "{a: number, b: number}");
+    let (_, warnings) = check(&format!(r#"math: import("{examples}/math.spc"), math.sqaure(5)"#));
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("no field 'sqaure' on module"), "{:?}", warnings);
+
+    use std::fs;
+    let dir = std::env::temp_dir().join(format!("spctr-typeck-import-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("poly.spc"), "{id: (x) => x}\n").unwrap();
+    let (ty, warnings) = check(&format!(
+        r#"p: import("{}"), [p.id(1), String.length(p.id("ab"))]"#,
+        dir.join("poly.spc").display()
+    ));
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "list<number>");
+    let _ = fs::remove_dir_all(&dir);
+
+    // A computed path can't be followed; the result stays unchecked.
+    let (_, warnings) = check(r#"p: "nowhere.spc", import(p).anything"#);
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    let (ty, _) = check(r#"p: "nowhere.spc", import(p)"#);
+    assert_eq!(ty, "any");
+}
+
+#[test]
+fn imports_typecheck_through_the_installed_resolver() {
+    use spctr::stdlib::imports::{self, DenyResolver, MapResolver};
+    use std::rc::Rc;
+    let files: MapResolver = [("m.spc", "{id: (x) => x}")].into_iter().collect();
+    let (ty, warnings) = imports::with_resolver(Rc::new(files), || {
+        check(r#"m: import("m.spc"), {a: m.id(1), b: m.id(true)}"#)
+    });
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(ty, "{a: number, b: bool}");
+    let (_, warnings) = imports::with_resolver(Rc::new(DenyResolver), || check(r#"import("m.spc")"#));
+    assert_eq!(warnings, vec!["imports are disabled (importing m.spc): import error"]);
+
+    // A warning inside the module points into it, and back at the import.
+    let files: MapResolver = [("m.spc", "{n: 1 + \"s\"}")].into_iter().collect();
+    let src = "x: 1,\nimport(\"m.spc\")";
+    let ast = parser::parse(src).unwrap();
+    resolver::resolve(&ast, &interp::ROOT_NAMES).unwrap();
+    let result = imports::with_resolver(Rc::new(files), || typeck::check(&ast, &interp::root_types()));
+    let w = &result.warnings[0];
+    let file = w.details.file.as_deref();
+    assert_eq!((file, w.span.clone()), (Some(std::path::Path::new("m.spc")), 8..11));
+    assert_eq!(w.details.imported_from, vec![(None, 6..21)]);
+}
+
+#[test]
+fn rows_sharing_a_tail_must_list_the_same_fields() {
+    use spctr::symbol::intern;
+    use spctr::types::{Scheme, Type, TypeVar};
+    // both: forall ρ. ({x: number | ρ}, {x: number, y: number | ρ}) -> number
+    let (x, y, rho) = (intern("x"), intern("y"), TypeVar(0));
+    let both = Scheme {
+        vars: vec![rho],
+        ty: Type::Fn(
+            vec![
+                Type::Row(vec![(x, Type::Number)], rho),
+                Type::Row(vec![(x, Type::Number), (y, Type::Number)], rho),
+            ],
+            Box::new(Type::Number),
+        ),
+    };
+    let ast = parser::parse("(r) => both(r, r)").unwrap();
+    resolver::resolve(&ast, &["both"]).unwrap();
+    let result = typeck::check_in(&ast, &[both], None);
+    let labels: Vec<_> = result.warnings.iter().map(|d| d.label.as_str()).collect();
+    assert_eq!(labels, vec!["missing field 'y'"]);
+}